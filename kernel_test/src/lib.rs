@@ -0,0 +1,72 @@
+//! `#[kernel_test]` 属性宏
+//!
+//! 内核运行在裸机上, 无法使用标准的 `#[test]`, 因此借助 `custom_test_frameworks`:
+//! 被标记的函数会生成一个测试描述符 `crate::ktest::KernelTest`,
+//! 它同时带有 `#[test_case]`, 由编译器收集后传给内核的测试运行器,
+//! 并被放入 `.kernel_test` 段, 便于在 ELF 中查看内核包含哪些测试
+//!
+//! 测试函数与描述符都只在 `cfg(test)` 下编译, 普通的内核不受影响
+//! 使用它的内核需要提供 `ktest` 模块, 其中定义了 `KernelTest { name, func }`
+//!
+//! ```ignore
+//! #[kernel_test]
+//! fn frame_allocator_test() {
+//!     assert!(frame_alloc().is_some());
+//! }
+//! ```
+
+use proc_macro::{Delimiter, Ident, Span, TokenStream, TokenTree};
+
+#[proc_macro_attribute]
+pub fn kernel_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return compile_error("#[kernel_test] does not take arguments");
+    }
+    let name = match fn_name(&item) {
+        Some(name) => name,
+        None => return compile_error("#[kernel_test] can only be applied to a function"),
+    };
+    let desc = Ident::new(
+        &format!("__KERNEL_TEST_{}", name.to_string().to_uppercase()),
+        Span::call_site(),
+    );
+    let mut output: TokenStream = "#[cfg(test)]".parse().unwrap();
+    output.extend(item);
+    let desc_def = format!(
+        r#"
+        #[cfg(test)]
+        #[test_case]
+        #[link_section = ".kernel_test"]
+        #[used]
+        static {desc}: crate::ktest::KernelTest = crate::ktest::KernelTest {{
+            name: concat!(module_path!(), "::", stringify!({name})),
+            func: {name},
+        }};
+        "#
+    );
+    output.extend(desc_def.parse::<TokenStream>().unwrap());
+    output
+}
+
+// 找到 fn 关键字之后的函数名, 跳过属性、可见性等前缀
+fn fn_name(item: &TokenStream) -> Option<Ident> {
+    let mut tokens = item.clone().into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident.to_string() == "fn" => {
+                return match tokens.next()? {
+                    TokenTree::Ident(name) => Some(name),
+                    _ => None,
+                };
+            }
+            // 函数体之前不应出现花括号
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn compile_error(msg: &str) -> TokenStream {
+    format!("compile_error!({:?});", msg).parse().unwrap()
+}
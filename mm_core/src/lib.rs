@@ -0,0 +1,24 @@
+//! 内存管理中与内核其余部分无关的逻辑: Sv39 的地址类型、页表项编码、物理页帧分配器以及页表遍历
+//!
+//! 这个 crate 是 `no_std` 的, 同时可以在主机上编译:
+//! 页表遍历通过 [`PhysMemory`] 访问物理内存, 内核中物理内存是恒等映射的,
+//! 而主机上的测试(`cargo test`)用一块缓冲区模拟物理内存
+#![no_std]
+
+extern crate alloc;
+
+pub mod address;
+pub mod frame_allocator;
+pub mod page_table;
+
+pub use address::{
+    PhysAddr, PhysPageNum, SimpleRange, SimpleRangeIterator, StepByOne, VPNRange, VirtAddr,
+    VirtPageNum,
+};
+pub use frame_allocator::{FrameAllocator, StackFrameAllocator};
+pub use page_table::{PTEFlags, PageTableEntry, PhysMemory};
+
+// 页内偏移的位宽
+pub const PAGE_SIZE_BITS: usize = 12;
+// 每个页面的大小
+pub const PAGE_SIZE: usize = 0x1000; //4096, 4K
@@ -0,0 +1,173 @@
+use crate::address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use bitflags::*;
+
+/*
+Page Table Entry, 每个页表项为64b, 即8字节
+
+Reserved: [63:54]
+PPN[2]:   [53:28]
+PPN[1]:   [27:19]
+PPN[0]:   [18:10]
+RSW:      [9:8]
+DAGUXWRV: [7:0]
+
+三级页表:
+假设有虚拟地址 VA(由VPN_0, VPN_1, VPN_2, offset等组成)
+- 首先会记录装载「当前所用的一级页表的物理页」的页号到 satp 寄存器中
+- 把 VPN_0 作为偏移在一级页表的物理页中找到二级页表的物理页号
+- 把 VPN_1 作为偏移在二级页表的物理页中找到三级页表的物理页号
+- 把 VPN_2 作为偏移在三级页表的物理页中找到要访问位置的物理页号
+物理页号对应的物理页基址（即物理页号左移12位）加上offset 就是VA对应的PA
+
+*/
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct PageTableEntry {
+    pub bits: usize,
+}
+
+bitflags! {
+    #[derive(Debug, PartialEq)]
+    pub struct PTEFlags:u8 {
+        const V = 1 << 0;// 仅当V为1时, 页表项才是合法的
+        const R = 1 << 1;//对应的虚拟页面是否可读
+        const W = 1 << 2;//对应的虚拟页面是否可写
+        const X = 1 << 3;//对应的虚拟页面是否可执行
+        const U = 1 << 4;//对应的虚拟页面,在CPU处于U时是否可访问
+        const G = 1 << 5;//reserve
+        const A = 1 << 6;//Accessed,处理器动态地 记录自从页表项上的这一位被清零之后,页表项对应的虚拟页面是否被访问过
+        const D = 1 << 7;//Dirty,处理器动态地 记录自从页表项上的这一位被清零之后,页表项对应的虚拟页面是否被修改过
+    }
+}
+
+impl PageTableEntry {
+    pub fn new(ppn: PhysPageNum, flags: PTEFlags) -> Self {
+        PageTableEntry {
+            bits: ppn.0 << 10 | flags.bits() as usize,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self { bits: 0 }
+    }
+
+    pub fn ppn(&self) -> PhysPageNum {
+        (self.bits >> 10 & ((1usize << 44) - 1)).into()
+    }
+    pub fn flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.bits as u8).unwrap()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (self.flags() & PTEFlags::V) != PTEFlags::empty()
+    }
+
+    pub fn readable(&self) -> bool {
+        (self.flags() & PTEFlags::R) != PTEFlags::empty()
+    }
+    pub fn writable(&self) -> bool {
+        (self.flags() & PTEFlags::W) != PTEFlags::empty()
+    }
+    pub fn executable(&self) -> bool {
+        (self.flags() & PTEFlags::X) != PTEFlags::empty()
+    }
+    pub fn is_user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
+}
+
+/// 页表所在物理内存的访问方式
+///
+/// 页表中保存的都是物理页号, 遍历页表时需要把它们转换为当前能访问的地址:
+/// 内核中物理内存是恒等映射的, 物理地址即可直接访问; 主机上的测试则映射到模拟物理内存的缓冲区中
+///
+/// # Safety
+/// 对于页表中可能出现的每个物理页号, phys_to_virt 都必须返回一段可读写的、至少一页大小的内存,
+/// 并且在返回的 'static 引用使用期间一直有效
+pub unsafe trait PhysMemory {
+    fn phys_to_virt(&self, pa: PhysAddr) -> *mut u8;
+
+    //返回一个字节数组(4K)的可变引用，可以以字节为粒度对物理页帧上的数据进行访问
+    fn get_bytes_array(&self, ppn: PhysPageNum) -> &'static mut [u8] {
+        let ptr = self.phys_to_virt(ppn.into());
+        unsafe { core::slice::from_raw_parts_mut(ptr, crate::PAGE_SIZE) }
+    }
+
+    //返回一个页表项定长数组的可变引用，代表多级页表中的一个节点
+    fn get_pte_array(&self, ppn: PhysPageNum) -> &'static mut [PageTableEntry] {
+        let ptr = self.phys_to_virt(ppn.into()) as *mut PageTableEntry;
+        unsafe { core::slice::from_raw_parts_mut(ptr, 512) }
+    }
+
+    //可以获取一个恰好放在一个物理页帧开头的类型为 T 的数据的可变引用
+    fn get_mut<T>(&self, ppn: PhysPageNum) -> &'static mut T {
+        let ptr = self.phys_to_virt(ppn.into()) as *mut T;
+        unsafe { ptr.as_mut().unwrap() }
+    }
+}
+
+// satp 中 MODE 字段的 8 表示 Sv39, 低 44 位为根页表的物理页号
+pub fn token(root_ppn: PhysPageNum) -> usize {
+    8usize << 60 | root_ppn.0
+}
+
+pub fn root_ppn_from_token(satp: usize) -> PhysPageNum {
+    PhysPageNum::from(satp & ((1usize << 44) - 1))
+}
+
+// 在多级页表找到一个虚拟页号对应的页表项的可变引用
+// 如果在遍历的过程中发现有节点尚未创建则会用 alloc 分配一个新的节点
+// alloc 返回的页帧必须已经清零, 分配失败时返回 None
+pub fn find_pte_create(
+    mem: &impl PhysMemory,
+    root_ppn: PhysPageNum,
+    vpn: VirtPageNum,
+    mut alloc: impl FnMut() -> Option<PhysPageNum>,
+) -> Option<&'static mut PageTableEntry> {
+    let idxs = vpn.indexes();
+    let mut ppn = root_ppn;
+    for (i, idx) in idxs.into_iter().enumerate() {
+        let pte = &mut mem.get_pte_array(ppn)[idx];
+        if i == 2 {
+            return Some(pte);
+        }
+        if !pte.is_valid() {
+            *pte = PageTableEntry::new(alloc()?, PTEFlags::V);
+        }
+        ppn = pte.ppn();
+    }
+    unreachable!()
+}
+
+//当找不到合法叶子节点的时候不会新建叶子节点而是直接返回 None 即查找失败
+pub fn find_pte(
+    mem: &impl PhysMemory,
+    root_ppn: PhysPageNum,
+    vpn: VirtPageNum,
+) -> Option<&'static mut PageTableEntry> {
+    let idxs = vpn.indexes();
+    let mut ppn = root_ppn;
+    for (i, idx) in idxs.into_iter().enumerate() {
+        let pte = &mut mem.get_pte_array(ppn)[idx];
+        if i == 2 {
+            return Some(pte);
+        }
+        if !pte.is_valid() {
+            return None;
+        }
+        ppn = pte.ppn();
+    }
+    unreachable!()
+}
+
+// 把虚拟地址翻译为物理地址, 只要求叶子页表项合法, 不检查权限
+pub fn translate_va(
+    mem: &impl PhysMemory,
+    root_ppn: PhysPageNum,
+    va: VirtAddr,
+) -> Option<PhysAddr> {
+    let pte = find_pte(mem, root_ppn, va.floor()).filter(|pte| pte.is_valid())?;
+    let pa: PhysAddr = pte.ppn().into();
+    Some(PhysAddr(pa.0 + va.page_offset()))
+}
@@ -0,0 +1,94 @@
+use mm_core::{PhysAddr, PhysPageNum, SimpleRange, VirtAddr, VirtPageNum, PAGE_SIZE};
+use proptest::prelude::*;
+
+#[test]
+fn phys_addr_floor_ceil() {
+    let pa = PhysAddr::from(0x8020_0000);
+    assert_eq!(pa.floor(), PhysPageNum(0x80200));
+    assert_eq!(pa.ceil(), PhysPageNum(0x80200));
+    let pa = PhysAddr::from(0x8020_0001);
+    assert_eq!(pa.floor(), PhysPageNum(0x80200));
+    assert_eq!(pa.ceil(), PhysPageNum(0x80201));
+    assert_eq!(pa.page_offset(), 1);
+}
+
+#[test]
+fn phys_addr_is_56_bits() {
+    assert_eq!(PhysAddr::from(usize::MAX).0, (1 << 56) - 1);
+    assert_eq!(PhysPageNum::from(usize::MAX).0, (1 << 44) - 1);
+}
+
+#[test]
+fn virt_addr_sign_extension() {
+    // 跳板页位于地址空间的最高处
+    let trampoline = usize::MAX - PAGE_SIZE + 1;
+    let va = VirtAddr::from(trampoline);
+    assert_eq!(va.0, (1 << 39) - PAGE_SIZE);
+    assert_eq!(usize::from(va), trampoline);
+    assert_eq!(va.floor().indexes(), [511, 511, 511]);
+}
+
+#[test]
+fn vpn_indexes() {
+    let vpn = VirtPageNum(1 << 18 | 2 << 9 | 3);
+    assert_eq!(vpn.indexes(), [1, 2, 3]);
+}
+
+#[test]
+fn simple_range_iterates_half_open() {
+    let range = SimpleRange::new(VirtPageNum(3), VirtPageNum(6));
+    let vpns: Vec<_> = range.into_iter().collect();
+    assert_eq!(vpns, [VirtPageNum(3), VirtPageNum(4), VirtPageNum(5)]);
+    assert_eq!(range.get_start(), VirtPageNum(3));
+    assert_eq!(range.get_end(), VirtPageNum(6));
+    assert_eq!(
+        SimpleRange::new(VirtPageNum(3), VirtPageNum(3))
+            .into_iter()
+            .count(),
+        0
+    );
+}
+
+#[test]
+#[should_panic]
+fn simple_range_rejects_reversed_bounds() {
+    SimpleRange::new(VirtPageNum(6), VirtPageNum(3));
+}
+
+proptest! {
+    #[test]
+    fn floor_ceil_bound_the_address(addr in 0usize..(1 << 56)) {
+        let pa = PhysAddr::from(addr);
+        prop_assert!(PhysAddr::from(pa.floor()).0 <= addr);
+        prop_assert!(PhysAddr::from(pa.ceil()).0 >= addr);
+        prop_assert!(pa.ceil().0 - pa.floor().0 <= 1);
+        prop_assert_eq!(PhysAddr::from(pa.floor()).0 + pa.page_offset(), addr);
+    }
+
+    // 合法的 Sv39 虚拟地址的高 25 位与第 38 位相同, 转换后应保持不变
+    #[test]
+    fn canonical_virt_addr_round_trip(low in 0usize..(1 << 38), high in any::<bool>()) {
+        let addr = if high { low | !((1 << 38) - 1) } else { low };
+        prop_assert_eq!(usize::from(VirtAddr::from(addr)), addr);
+    }
+
+    #[test]
+    fn vpn_indexes_recompose(vpn in 0usize..(1 << 27)) {
+        let [l2, l1, l0] = VirtPageNum(vpn).indexes();
+        prop_assert!(l2 < 512 && l1 < 512 && l0 < 512);
+        prop_assert_eq!(l2 << 18 | l1 << 9 | l0, vpn);
+    }
+
+    #[test]
+    fn virt_page_round_trip(vpn in 0usize..(1 << 27)) {
+        let va: VirtAddr = VirtPageNum(vpn).into();
+        prop_assert!(va.aligned());
+        prop_assert_eq!(VirtPageNum::from(va), VirtPageNum(vpn));
+    }
+
+    #[test]
+    fn simple_range_len(start in 0usize..1000, len in 0usize..1000) {
+        let range = SimpleRange::new(VirtPageNum(start), VirtPageNum(start + len));
+        prop_assert_eq!(range.into_iter().count(), len);
+    }
+}
@@ -0,0 +1,87 @@
+//! 主机上的测试用一块缓冲区模拟物理内存
+
+#![allow(dead_code)]
+
+use mm_core::page_table::find_pte_create;
+use mm_core::{
+    FrameAllocator, PTEFlags, PageTableEntry, PhysAddr, PhysMemory, PhysPageNum,
+    StackFrameAllocator, VirtPageNum, PAGE_SIZE,
+};
+
+// 模拟的物理内存, 覆盖物理页号 [base, base + pages)
+// 缓冲区被泄漏, 因此 PhysMemory 返回的 'static 引用始终有效
+pub struct SimRam {
+    base: PhysPageNum,
+    pages: usize,
+    ptr: *mut u8,
+}
+
+impl SimRam {
+    pub fn new(base: PhysPageNum, pages: usize) -> Self {
+        // 用 u64 保证页表项按 8 字节对齐
+        let mem = vec![0u64; pages * PAGE_SIZE / 8].leak();
+        Self {
+            base,
+            pages,
+            ptr: mem.as_mut_ptr() as *mut u8,
+        }
+    }
+
+    pub fn contains(&self, ppn: PhysPageNum) -> bool {
+        ppn.0 >= self.base.0 && ppn.0 < self.base.0 + self.pages
+    }
+}
+
+unsafe impl PhysMemory for SimRam {
+    fn phys_to_virt(&self, pa: PhysAddr) -> *mut u8 {
+        assert!(
+            self.contains(pa.floor()),
+            "{:?} is out of the simulated RAM",
+            pa
+        );
+        let offset = pa.0 - PhysAddr::from(self.base).0;
+        unsafe { self.ptr.add(offset) }
+    }
+}
+
+// 一块模拟内存及其上的页帧分配器, 第一个页帧作为根页表
+pub struct Machine {
+    pub ram: SimRam,
+    pub allocator: StackFrameAllocator,
+    pub root: PhysPageNum,
+}
+
+// 与 QEMU virt 的物理内存起始地址 0x8000_0000 一致
+pub const RAM_BASE: PhysPageNum = PhysPageNum(0x80000);
+
+impl Machine {
+    pub fn new(pages: usize) -> Self {
+        let ram = SimRam::new(RAM_BASE, pages);
+        let mut allocator = StackFrameAllocator::new();
+        allocator.init(PhysPageNum(RAM_BASE.0 + 1), PhysPageNum(RAM_BASE.0 + pages));
+        Self {
+            ram,
+            allocator,
+            root: RAM_BASE,
+        }
+    }
+
+    // 与内核中 PageTable::map 相同: 叶子页表项此前必须是不合法的
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let Self {
+            ram,
+            allocator,
+            root,
+        } = self;
+        let pte = find_pte_create(ram, *root, vpn, || alloc_zeroed(ram, allocator)).unwrap();
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+}
+
+// 分配一个清零的页帧, 与内核中 FrameTracker::new 的行为相同
+pub fn alloc_zeroed(ram: &SimRam, allocator: &mut StackFrameAllocator) -> Option<PhysPageNum> {
+    let ppn = allocator.alloc()?;
+    ram.get_bytes_array(ppn).fill(0);
+    Some(ppn)
+}
@@ -0,0 +1,78 @@
+use mm_core::{FrameAllocator, PhysPageNum, StackFrameAllocator};
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+
+fn allocator(l: usize, r: usize) -> StackFrameAllocator {
+    let mut allocator = StackFrameAllocator::new();
+    allocator.init(PhysPageNum(l), PhysPageNum(r));
+    allocator
+}
+
+#[test]
+fn alloc_in_order_until_exhausted() {
+    let mut allocator = allocator(10, 13);
+    assert_eq!(allocator.alloc(), Some(PhysPageNum(10)));
+    assert_eq!(allocator.alloc(), Some(PhysPageNum(11)));
+    assert_eq!(allocator.alloc(), Some(PhysPageNum(12)));
+    assert_eq!(allocator.alloc(), None);
+}
+
+#[test]
+fn recycled_frames_are_reused_first() {
+    let mut allocator = allocator(10, 20);
+    let a = allocator.alloc().unwrap();
+    let b = allocator.alloc().unwrap();
+    allocator.dealloc(a);
+    allocator.dealloc(b);
+    // 回收的页帧以 LIFO 的方式分配
+    assert_eq!(allocator.alloc(), Some(b));
+    assert_eq!(allocator.alloc(), Some(a));
+    assert_eq!(allocator.alloc(), Some(PhysPageNum(12)));
+}
+
+#[test]
+#[should_panic]
+fn double_free_panics() {
+    let mut allocator = allocator(10, 20);
+    let a = allocator.alloc().unwrap();
+    allocator.dealloc(a);
+    allocator.dealloc(a);
+}
+
+#[test]
+#[should_panic]
+fn free_unallocated_panics() {
+    let mut allocator = allocator(10, 20);
+    allocator.dealloc(PhysPageNum(15));
+}
+
+proptest! {
+    // 随机地分配与回收, 已分配的页帧互不相同且都在管理的范围内, 总数不超过范围大小
+    #[test]
+    fn live_frames_are_unique(ops in prop::collection::vec(any::<Option<prop::sample::Index>>(), 0..200)) {
+        const L: usize = 100;
+        const R: usize = 164;
+        let mut allocator = allocator(L, R);
+        let mut live: Vec<PhysPageNum> = Vec::new();
+        for op in ops {
+            match op {
+                // None 表示分配, Some 表示回收一个已分配的页帧
+                None => match allocator.alloc() {
+                    Some(ppn) => {
+                        prop_assert!((L..R).contains(&ppn.0));
+                        prop_assert!(!live.contains(&ppn));
+                        live.push(ppn);
+                    }
+                    None => prop_assert_eq!(live.len(), R - L),
+                },
+                Some(index) if !live.is_empty() => {
+                    let ppn = live.swap_remove(index.index(live.len()));
+                    allocator.dealloc(ppn);
+                }
+                Some(_) => {}
+            }
+        }
+        let unique: BTreeSet<_> = live.iter().collect();
+        prop_assert_eq!(unique.len(), live.len());
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::{alloc_zeroed, Machine, RAM_BASE};
+use mm_core::page_table::{find_pte, find_pte_create, root_ppn_from_token, token, translate_va};
+use mm_core::{PTEFlags, PageTableEntry, PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+#[test]
+fn pte_encoding() {
+    let pte = PageTableEntry::new(
+        PhysPageNum(0x80201),
+        PTEFlags::V | PTEFlags::R | PTEFlags::X,
+    );
+    assert_eq!(pte.bits, 0x80201 << 10 | 0b1011);
+    assert_eq!(pte.ppn(), PhysPageNum(0x80201));
+    assert!(pte.is_valid() && pte.readable() && pte.executable());
+    assert!(!pte.writable() && !pte.is_user());
+    assert!(!PageTableEntry::empty().is_valid());
+}
+
+#[test]
+fn token_round_trip() {
+    let satp = token(PhysPageNum(0x80123));
+    assert_eq!(satp >> 60, 8);
+    assert_eq!(root_ppn_from_token(satp), PhysPageNum(0x80123));
+}
+
+#[test]
+fn map_and_translate() {
+    let mut machine = Machine::new(16);
+    let vpn = VirtPageNum(0x12345);
+    let ppn = PhysPageNum(0x80400);
+    machine.map(vpn, ppn, PTEFlags::R | PTEFlags::W | PTEFlags::U);
+
+    let pte = find_pte(&machine.ram, machine.root, vpn).unwrap();
+    assert!(pte.is_valid() && pte.readable() && pte.writable() && pte.is_user());
+    assert_eq!(pte.ppn(), ppn);
+
+    let va = VirtAddr(usize::from(VirtAddr::from(vpn)) + 0x123);
+    assert_eq!(
+        translate_va(&machine.ram, machine.root, va),
+        Some(PhysAddr(0x8040_0123))
+    );
+    // 同一个叶子页表中的相邻页没有映射
+    let next = find_pte(&machine.ram, machine.root, VirtPageNum(0x12346)).unwrap();
+    assert!(!next.is_valid());
+    assert_eq!(
+        translate_va(&machine.ram, machine.root, VirtPageNum(0x12346).into()),
+        None
+    );
+    // 其他一级页表项下的页连中间节点都不存在
+    assert!(find_pte(&machine.ram, machine.root, VirtPageNum(1 << 18)).is_none());
+}
+
+#[test]
+fn intermediate_nodes_are_shared() {
+    let mut machine = Machine::new(16);
+    machine.map(VirtPageNum(0), PhysPageNum(0x90000), PTEFlags::R);
+    let used = 16 - 1 - count_free(&mut machine);
+    // 根页表之外新建了二级与三级页表各一个
+    assert_eq!(used, 2);
+
+    let mut machine = Machine::new(16);
+    machine.map(VirtPageNum(0), PhysPageNum(0x90000), PTEFlags::R);
+    machine.map(VirtPageNum(1), PhysPageNum(0x90001), PTEFlags::R);
+    machine.map(VirtPageNum(1 << 9), PhysPageNum(0x90002), PTEFlags::R);
+    assert_eq!(16 - 1 - count_free(&mut machine), 3);
+}
+
+#[test]
+fn find_pte_create_reports_allocation_failure() {
+    let mut machine = Machine::new(2);
+    let Machine {
+        ram,
+        allocator,
+        root,
+    } = &mut machine;
+    // 只剩一个空闲页帧, 无法同时建立二级与三级页表
+    assert!(find_pte_create(ram, *root, VirtPageNum(0), || alloc_zeroed(ram, allocator)).is_none());
+}
+
+#[test]
+#[should_panic]
+fn map_twice_panics() {
+    let mut machine = Machine::new(16);
+    machine.map(VirtPageNum(7), PhysPageNum(0x90000), PTEFlags::R);
+    machine.map(VirtPageNum(7), PhysPageNum(0x90001), PTEFlags::R);
+}
+
+// 耗尽分配器, 返回剩余的空闲页帧数
+fn count_free(machine: &mut Machine) -> usize {
+    std::iter::from_fn(|| alloc_zeroed(&machine.ram, &mut machine.allocator)).count()
+}
+
+proptest! {
+    #[test]
+    fn pte_round_trip(ppn in 0usize..(1 << 44), bits in any::<u8>()) {
+        let pte = PageTableEntry::new(PhysPageNum(ppn), PTEFlags::from_bits_truncate(bits));
+        prop_assert_eq!(pte.ppn(), PhysPageNum(ppn));
+        prop_assert_eq!(pte.flags().bits(), bits);
+        prop_assert_eq!(pte.is_valid(), bits & PTEFlags::V.bits() != 0);
+        prop_assert_eq!(pte.writable(), bits & PTEFlags::W.bits() != 0);
+    }
+
+    // 随机建立一组映射, 之后每个虚拟页都能翻译到对应的物理页, 其余页翻译失败
+    #[test]
+    fn walk_matches_model(
+        mappings in prop::collection::btree_map(0usize..(1 << 27), 0usize..(1 << 44), 1..32),
+        probes in prop::collection::vec(0usize..(1 << 27), 0..32),
+        offset in 0usize..4096,
+    ) {
+        // 每个映射最多需要新建两个页表
+        let mut machine = Machine::new(1 + 2 * mappings.len());
+        let model: BTreeMap<VirtPageNum, PhysPageNum> = mappings
+            .into_iter()
+            .map(|(vpn, ppn)| (VirtPageNum(vpn), PhysPageNum(ppn)))
+            .collect();
+        for (&vpn, &ppn) in &model {
+            machine.map(vpn, ppn, PTEFlags::R | PTEFlags::U);
+        }
+        // 页表全部位于模拟的内存中
+        prop_assert_eq!(machine.root, RAM_BASE);
+        for vpn in model.keys().copied().chain(probes.into_iter().map(VirtPageNum)) {
+            let va = VirtAddr(usize::from(VirtAddr::from(vpn)) + offset);
+            let expected = model
+                .get(&vpn)
+                .map(|&ppn| PhysAddr(PhysAddr::from(ppn).0 + offset));
+            prop_assert_eq!(translate_va(&machine.ram, machine.root, va), expected);
+        }
+    }
+}
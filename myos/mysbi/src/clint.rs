@@ -0,0 +1,44 @@
+//! QEMU virt 平台的 CLINT(Core Local Interruptor)
+//!
+//! 每个 hart 有一个 msip 寄存器(写 1 触发 M 模式软件中断)和一个 mtimecmp 寄存器
+
+use riscv::register::{mie, mip};
+
+const VIRT_CLINT_ADDR: usize = 0x200_0000;
+const VIRT_CLINT_MSIP: usize = VIRT_CLINT_ADDR; // 每个 hart 4 字节
+const VIRT_CLINT_TIMER_CMP: usize = VIRT_CLINT_ADDR + 0x4000; // 每个 hart 8 字节
+const VIRT_CLINT_TIMER_VAL: usize = VIRT_CLINT_ADDR + 0xbff8; // 所有 hart 共享的 mtime
+
+// 设置 hart 的下一次时钟中断
+// 到期后先进入 M 模式, 再由 timer_process 转交给 S 模式
+pub fn timer_event_start(hart: usize, next_event: usize) {
+    unsafe {
+        ((VIRT_CLINT_TIMER_CMP + 8 * hart) as *mut usize).write_volatile(next_event);
+        // 清S模式的timer pending irq
+        mip::clear_stimer();
+        // 使能M模式的timer中断
+        mie::set_mtimer();
+    }
+}
+
+pub fn timer_process() {
+    unsafe {
+        // 关闭M模式的timer 中断
+        mie::clear_mtimer();
+        // 使能S模式的timer pending 中断
+        mip::set_stimer();
+    }
+}
+
+// 读取 mtime, 用于模拟 time CSR
+pub fn mtime() -> usize {
+    unsafe { (VIRT_CLINT_TIMER_VAL as *const usize).read_volatile() }
+}
+
+pub fn send_soft(hart: usize) {
+    unsafe { ((VIRT_CLINT_MSIP + 4 * hart) as *mut u32).write_volatile(1) }
+}
+
+pub fn clear_soft(hart: usize) {
+    unsafe { ((VIRT_CLINT_MSIP + 4 * hart) as *mut u32).write_volatile(0) }
+}
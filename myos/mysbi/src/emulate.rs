@@ -0,0 +1,191 @@
+//! M 模式下的指令模拟
+//!
+//! 1. 非对齐访存: 硬件不支持时会触发 Load/StoreMisaligned 异常, 这里按字节完成访存
+//! 2. rdtime: 没有实现 time CSR 的 CPU 上读取 time 会触发非法指令异常, 这里用 CLINT 的 mtime 代替
+//!
+//! 无法模拟时返回 Err, 由 sbi_trap 把异常转交给 S 模式
+
+use crate::clint;
+use crate::sbi_trap::SbiTrapRegs;
+use crate::unpriv::{self, UnprivFault};
+
+// mcause 中的异常号
+const CAUSE_INSTRUCTION_FAULT: usize = 1;
+const CAUSE_ILLEGAL_INSTRUCTION: usize = 2;
+const CAUSE_LOAD_FAULT: usize = 5;
+const CAUSE_INSTRUCTION_PAGE_FAULT: usize = 12;
+const CAUSE_LOAD_PAGE_FAULT: usize = 13;
+
+const CSR_TIME: usize = 0xc01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    // 宽度(字节), 是否符号扩展, 目的寄存器
+    Load {
+        width: usize,
+        signed: bool,
+        rd: usize,
+    },
+    // 宽度(字节), 源寄存器
+    Store {
+        width: usize,
+        rs2: usize,
+    },
+}
+
+fn bits(insn: u32, hi: u32, lo: u32) -> usize {
+    ((insn >> lo) & ((1 << (hi - lo + 1)) - 1)) as usize
+}
+
+// 取出 mepc 处的指令及其长度
+// 取指出错时, 把 load 类的异常转换为取指异常再交给 S 模式
+fn fetch_insn(pc: usize) -> Result<(u32, usize), UnprivFault> {
+    let fetch = |addr| {
+        unpriv::load_u16(addr).map_err(|fault| UnprivFault {
+            cause: match fault.cause {
+                CAUSE_LOAD_FAULT => CAUSE_INSTRUCTION_FAULT,
+                CAUSE_LOAD_PAGE_FAULT => CAUSE_INSTRUCTION_PAGE_FAULT,
+                cause => cause,
+            },
+            tval: fault.tval,
+        })
+    };
+    let lo = fetch(pc)? as u32;
+    if lo & 0b11 != 0b11 {
+        return Ok((lo, 2));
+    }
+    let hi = fetch(pc + 2)? as u32;
+    Ok((lo | hi << 16, 4))
+}
+
+fn decode_32(insn: u32) -> Option<Access> {
+    let funct3 = bits(insn, 14, 12);
+    match bits(insn, 6, 0) {
+        // LB/LH/LW/LD/LBU/LHU/LWU
+        0b0000011 => {
+            let (width, signed) = match funct3 {
+                0 => (1, true),
+                1 => (2, true),
+                2 => (4, true),
+                3 => (8, true),
+                4 => (1, false),
+                5 => (2, false),
+                6 => (4, false),
+                _ => return None,
+            };
+            Some(Access::Load {
+                width,
+                signed,
+                rd: bits(insn, 11, 7),
+            })
+        }
+        // SB/SH/SW/SD
+        0b0100011 if funct3 <= 3 => Some(Access::Store {
+            width: 1 << funct3,
+            rs2: bits(insn, 24, 20),
+        }),
+        _ => None,
+    }
+}
+
+fn decode_16(insn: u32) -> Option<Access> {
+    // 压缩指令中 3 位的寄存器编号对应 x8~x15
+    let rd_prime = bits(insn, 4, 2) + 8;
+    let rd = bits(insn, 11, 7);
+    let rs2 = bits(insn, 6, 2);
+    match (bits(insn, 1, 0), bits(insn, 15, 13)) {
+        // C.LW / C.LD
+        (0b00, 0b010) => Some(Access::Load {
+            width: 4,
+            signed: true,
+            rd: rd_prime,
+        }),
+        (0b00, 0b011) => Some(Access::Load {
+            width: 8,
+            signed: true,
+            rd: rd_prime,
+        }),
+        // C.SW / C.SD
+        (0b00, 0b110) => Some(Access::Store {
+            width: 4,
+            rs2: rd_prime,
+        }),
+        (0b00, 0b111) => Some(Access::Store {
+            width: 8,
+            rs2: rd_prime,
+        }),
+        // C.LWSP / C.LDSP, rd 不能为 x0
+        (0b10, 0b010) if rd != 0 => Some(Access::Load {
+            width: 4,
+            signed: true,
+            rd,
+        }),
+        (0b10, 0b011) if rd != 0 => Some(Access::Load {
+            width: 8,
+            signed: true,
+            rd,
+        }),
+        // C.SWSP / C.SDSP
+        (0b10, 0b110) => Some(Access::Store { width: 4, rs2 }),
+        (0b10, 0b111) => Some(Access::Store { width: 8, rs2 }),
+        _ => None,
+    }
+}
+
+// 模拟 mepc 处的非对齐访存指令, addr 为 mtval 中记录的访存地址
+// 浮点访存与 AMO 指令无法模拟, 原样返回当前的异常
+pub fn misaligned(regs: &mut SbiTrapRegs, cause: usize, addr: usize) -> Result<(), UnprivFault> {
+    let unsupported = UnprivFault { cause, tval: addr };
+    let (insn, len) = fetch_insn(regs.mepc)?;
+    let access = if len == 4 {
+        decode_32(insn)
+    } else {
+        decode_16(insn)
+    };
+    match access.ok_or(unsupported)? {
+        Access::Load { width, signed, rd } => {
+            let mut value: usize = 0;
+            for i in 0..width {
+                value |= (unpriv::load_u8(addr + i)? as usize) << (8 * i);
+            }
+            if signed && width < 8 {
+                let shift = 64 - 8 * width;
+                value = (((value << shift) as isize) >> shift) as usize;
+            }
+            regs.set_reg(rd, value);
+        }
+        Access::Store { width, rs2 } => {
+            let value = regs.reg(rs2);
+            // 与硬件一样, 中途出错时前面的字节可能已经写入
+            for i in 0..width {
+                unpriv::store_u8(addr + i, (value >> (8 * i)) as u8)?;
+            }
+        }
+    }
+    regs.mepc += len;
+    Ok(())
+}
+
+// 模拟非法指令, 目前只支持读取 time CSR
+// mtval 中通常记录了出错的指令, 为 0 时从 mepc 处读取
+pub fn illegal_insn(regs: &mut SbiTrapRegs, tval: usize) -> Result<(), UnprivFault> {
+    let unsupported = UnprivFault {
+        cause: CAUSE_ILLEGAL_INSTRUCTION,
+        tval,
+    };
+    let insn = if tval != 0 {
+        tval as u32
+    } else {
+        fetch_insn(regs.mepc)?.0
+    };
+    // csrrs/csrrc/csrrsi/csrrci 在 rs1(uimm) 为 0 时只读不写
+    let read_only_csr = bits(insn, 6, 0) == 0b1110011
+        && matches!(bits(insn, 14, 12), 2 | 3 | 6 | 7)
+        && bits(insn, 19, 15) == 0;
+    if !read_only_csr || bits(insn, 31, 20) != CSR_TIME {
+        return Err(unsupported);
+    }
+    regs.set_reg(bits(insn, 11, 7), clint::mtime());
+    regs.mepc += 4;
+    Ok(())
+}
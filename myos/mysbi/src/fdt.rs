@@ -0,0 +1,79 @@
+//! 设备树(FDT)的最小解析, 只用来找出 /cpus 下有哪些 hart
+//!
+//! 头部的第 3 个字(偏移 8)为结构块的偏移, 结构块由一串大端 u32 token 组成:
+//! - FDT_BEGIN_NODE 后跟以 0 结尾、按 4 字节对齐的节点名
+//! - FDT_PROP 后跟值的长度、属性名的偏移与按 4 字节对齐的值
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+fn read_be32(addr: usize) -> u32 {
+    u32::from_be(unsafe { (addr as *const u32).read_volatile() })
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn node_name(addr: usize) -> &'static [u8] {
+    let mut len = 0;
+    while unsafe { ((addr + len) as *const u8).read_volatile() } != 0 {
+        len += 1;
+    }
+    unsafe { core::slice::from_raw_parts(addr as *const u8, len) }
+}
+
+fn parse_hex(s: &[u8]) -> Option<usize> {
+    if s.is_empty() {
+        return None;
+    }
+    s.iter().try_fold(0, |acc: usize, &c| {
+        let digit = (c as char).to_digit(16)? as usize;
+        acc.checked_mul(16)?.checked_add(digit)
+    })
+}
+
+// 返回 /cpus 下 cpu@<hartid> 节点的 hartid 位图, 只记录小于 64 的 hartid
+// fdt 不是合法的设备树时返回 None
+pub fn hart_mask(fdt: usize) -> Option<u64> {
+    if fdt == 0 || read_be32(fdt) != FDT_MAGIC {
+        return None;
+    }
+    let mut pos = fdt + read_be32(fdt + 8) as usize;
+    // 根节点的深度为 1, /cpus 为 2, /cpus/cpu@N 为 3
+    let mut depth = 0;
+    let mut in_cpus = false;
+    let mut mask = 0u64;
+    loop {
+        let token = read_be32(pos);
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = node_name(pos);
+                pos += align4(name.len() + 1);
+                depth += 1;
+                if depth == 2 {
+                    in_cpus = name == b"cpus";
+                } else if depth == 3 && in_cpus {
+                    if let Some(hartid) = name.strip_prefix(b"cpu@").and_then(parse_hex) {
+                        if hartid < 64 {
+                            mask |= 1 << hartid;
+                        }
+                    }
+                }
+            }
+            FDT_END_NODE => depth -= 1,
+            FDT_PROP => {
+                let len = read_be32(pos) as usize;
+                pos += 8 + align4(len);
+            }
+            FDT_NOP => {}
+            FDT_END => return Some(mask),
+            _ => return None,
+        }
+    }
+}
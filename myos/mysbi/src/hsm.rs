@@ -0,0 +1,111 @@
+//! SBI HSM(Hart State Management) 扩展
+//!
+//! 除启动 hart 外, 其他 hart 上电后停在 park 中(STOPPED)
+//! S 模式通过 hart_start 指定入口地址与参数, 再由 IPI 唤醒目标 hart 跳转过去
+//! 已启动的 hart 可以通过 hart_stop 回到 park 中, 等待下一次 hart_start
+
+use crate::enter_supervisor;
+use crate::ipi;
+use crate::println;
+use crate::sbi_ecall::{SbiRet, SBI_ERR_FAILED, SBI_ERR_INVALID_PARAM};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// 与 sbi_boot.S 中的 SBI_MAX_HARTS 保持一致
+pub const MAX_HARTS: usize = 8;
+
+const HART_STARTED: usize = 0;
+const HART_STOPPED: usize = 1;
+const HART_START_PENDING: usize = 2;
+// 该 hart 不存在(没有执行到 sbi_main)
+const HART_ABSENT: usize = usize::MAX;
+
+const SBI_ERR_ALREADY_AVAILABLE: isize = -6;
+
+static STATE: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(HART_ABSENT) }; MAX_HARTS];
+static START_ADDR: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+static OPAQUE: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+// START_ADDR/OPAQUE 写好后才置位, 避免目标 hart 被无关的 IPI 唤醒时读到旧值
+static START_READY: [AtomicBool; MAX_HARTS] = [const { AtomicBool::new(false) }; MAX_HARTS];
+
+pub fn hart_is_present(hartid: usize) -> bool {
+    hartid < MAX_HARTS && STATE[hartid].load(Ordering::Acquire) != HART_ABSENT
+}
+
+// 启动 hart 进入内核之前调用, 等待设备树中除 boot_hart 外的每个 hart 进入 STOPPED 状态
+// mask 为设备树中 hart 的位图, 设备树无法解析时为 None, 此时不等待
+pub fn wait_for_harts(boot_hart: usize, mask: Option<u64>) {
+    let Some(mask) = mask else {
+        println!("no valid fdt, not waiting for other harts");
+        return;
+    };
+    for hartid in (0..64).filter(|&id| id != boot_hart && mask & (1 << id) != 0) {
+        if hartid >= MAX_HARTS {
+            println!("hart {} exceeds MAX_HARTS, left parked", hartid);
+            continue;
+        }
+        while STATE[hartid].load(Ordering::Acquire) != HART_STOPPED {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+pub fn mark_started(hartid: usize) {
+    STATE[hartid].store(HART_STARTED, Ordering::Release);
+}
+
+// 停在 M 模式, 直到 hart_start 唤醒本 hart
+pub fn park(hartid: usize) -> ! {
+    STATE[hartid].store(HART_STOPPED, Ordering::Release);
+    loop {
+        // M 模式下全局中断是关闭的, 但 mie.MSIE 置位时, 软件中断仍能唤醒 wfi
+        unsafe { core::arch::asm!("wfi") };
+        // 清除 msip, 顺便处理其他 hart 发来的栅栏请求
+        ipi::handle_msoft();
+        if START_READY[hartid].swap(false, Ordering::AcqRel) {
+            let start_addr = START_ADDR[hartid].load(Ordering::Acquire);
+            let opaque = OPAQUE[hartid].load(Ordering::Acquire);
+            STATE[hartid].store(HART_STARTED, Ordering::Release);
+            enter_supervisor(hartid, start_addr, opaque);
+        }
+    }
+}
+
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> SbiRet {
+    if !hart_is_present(hartid) {
+        return SbiRet::error(SBI_ERR_INVALID_PARAM);
+    }
+    if STATE[hartid]
+        .compare_exchange(
+            HART_STOPPED,
+            HART_START_PENDING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return SbiRet::error(SBI_ERR_ALREADY_AVAILABLE);
+    }
+    START_ADDR[hartid].store(start_addr, Ordering::Release);
+    OPAQUE[hartid].store(opaque, Ordering::Release);
+    START_READY[hartid].store(true, Ordering::Release);
+    ipi::wake(hartid);
+    SbiRet::success(0)
+}
+
+// 停止当前 hart, 成功时不会返回
+pub fn hart_stop(hartid: usize) -> SbiRet {
+    if STATE[hartid].load(Ordering::Acquire) != HART_STARTED {
+        return SbiRet::error(SBI_ERR_FAILED);
+    }
+    // 停止后不再需要时钟中断
+    unsafe { riscv::register::mie::clear_mtimer() };
+    // 当前还在 trap 处理流程中, 但 mscratch 保存的是栈顶, 丢弃当前栈帧不会有影响
+    park(hartid)
+}
+
+pub fn hart_get_status(hartid: usize) -> SbiRet {
+    if !hart_is_present(hartid) {
+        return SbiRet::error(SBI_ERR_INVALID_PARAM);
+    }
+    SbiRet::success(STATE[hartid].load(Ordering::Acquire))
+}
@@ -0,0 +1,130 @@
+//! 核间中断与远程栅栏
+//!
+//! S 模式无法直接触发其他 hart 的软件中断, 需要经由 SBI:
+//! 发送方在目标 hart 的 pending 中记下要做的事, 再写目标 hart 的 msip 触发 M 模式软件中断
+//! 目标 hart 在 handle_msoft 中完成这些事(置位 SSIP, 执行 fence.i/sfence.vma)
+
+use crate::clint;
+use crate::hsm::{hart_is_present, MAX_HARTS};
+use crate::sbi_ecall::{SbiRet, SBI_ERR_INVALID_PARAM};
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::{mhartid, mie, mip};
+
+// 每个 hart 待处理的请求
+const IPI_SSOFT: usize = 1 << 0;
+const IPI_FENCE_I: usize = 1 << 1;
+const IPI_SFENCE_VMA: usize = 1 << 2;
+
+static PENDING: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+
+pub fn init() {
+    unsafe {
+        // 接收其他 hart 发来的 M 模式软件中断
+        mie::set_msoft();
+    }
+}
+
+// 按照 SBI 规范解析 (hart_mask, hart_mask_base)
+// hart_mask_base 为 usize::MAX 时表示所有 hart
+fn for_each_hart(
+    hart_mask: usize,
+    hart_mask_base: usize,
+    mut f: impl FnMut(usize),
+) -> Result<(), SbiRet> {
+    if hart_mask_base == usize::MAX {
+        (0..MAX_HARTS)
+            .filter(|&hart| hart_is_present(hart))
+            .for_each(f);
+        return Ok(());
+    }
+    // 先检查所有目标 hart, 避免只完成了一部分
+    for bit in 0..usize::BITS as usize {
+        if hart_mask & (1 << bit) != 0 {
+            match hart_mask_base.checked_add(bit) {
+                Some(hart) if hart_is_present(hart) => {}
+                _ => return Err(SbiRet::error(SBI_ERR_INVALID_PARAM)),
+            }
+        }
+    }
+    for bit in 0..usize::BITS as usize {
+        if hart_mask & (1 << bit) != 0 {
+            f(hart_mask_base + bit);
+        }
+    }
+    Ok(())
+}
+
+fn do_local(ops: usize) {
+    unsafe {
+        if ops & IPI_SSOFT != 0 {
+            mip::set_ssoft();
+        }
+        if ops & IPI_FENCE_I != 0 {
+            asm!("fence.i");
+        }
+        if ops & IPI_SFENCE_VMA != 0 {
+            asm!("sfence.vma");
+        }
+    }
+}
+
+// 请求目标 hart 完成 ops, wait 为 true 时等待对方完成
+fn send(hart: usize, ops: usize, wait: bool) {
+    if hart == mhartid::read() {
+        do_local(ops);
+        return;
+    }
+    PENDING[hart].fetch_or(ops, Ordering::AcqRel);
+    clint::send_soft(hart);
+    if wait {
+        // 等待目标 hart 取走请求
+        // 期间对方可能也在等待本 hart, 而 M 模式下中断是关闭的, 因此顺便处理发给自己的请求
+        let me = mhartid::read();
+        while PENDING[hart].load(Ordering::Acquire) & ops != 0 {
+            do_local(PENDING[me].swap(0, Ordering::AcqRel));
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// 唤醒停在 park 中的 hart
+pub fn wake(hart: usize) {
+    clint::send_soft(hart);
+}
+
+// M 模式软件中断处理
+pub fn handle_msoft() {
+    let hart = mhartid::read();
+    clint::clear_soft(hart);
+    let ops = PENDING[hart].swap(0, Ordering::AcqRel);
+    do_local(ops);
+}
+
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) -> SbiRet {
+    match for_each_hart(hart_mask, hart_mask_base, |hart| {
+        send(hart, IPI_SSOFT, false)
+    }) {
+        Ok(()) => SbiRet::success(0),
+        Err(ret) => ret,
+    }
+}
+
+pub fn remote_fence_i(hart_mask: usize, hart_mask_base: usize) -> SbiRet {
+    match for_each_hart(hart_mask, hart_mask_base, |hart| {
+        send(hart, IPI_FENCE_I, true)
+    }) {
+        Ok(()) => SbiRet::success(0),
+        Err(ret) => ret,
+    }
+}
+
+// 不区分地址范围与 ASID, 一律刷新整个 TLB, 结果是保守而正确的
+pub fn remote_sfence_vma(hart_mask: usize, hart_mask_base: usize) -> SbiRet {
+    match for_each_hart(hart_mask, hart_mask_base, |hart| {
+        send(hart, IPI_SFENCE_VMA, true)
+    }) {
+        Ok(()) => SbiRet::success(0),
+        Err(ret) => ret,
+    }
+}
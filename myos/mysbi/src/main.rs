@@ -5,17 +5,65 @@ use core::arch::asm;
 use core::arch::global_asm;
 use riscv::register::{mepc, mstatus, satp, sie, stvec};
 
+mod clint;
 mod console;
+mod emulate;
+mod fdt;
+mod hsm;
+mod ipi;
 mod lang_item;
+mod sbi_ecall;
 mod sbi_trap;
 mod uart;
+mod unpriv;
 
 global_asm!(include_str!("sbi_boot.S"));
 
-const FW_JUMP_ADDR: usize = 0x8020_0000;
+// 内核的入口地址, 默认为 0x8020_0000
+// 可以在编译时通过环境变量 MYSBI_JUMP_ADDR 指定, 如 MYSBI_JUMP_ADDR=0x80400000
+const FW_JUMP_ADDR: usize = match option_env!("MYSBI_JUMP_ADDR") {
+    Some(addr) => parse_addr(addr),
+    None => 0x8020_0000,
+};
 
+// 编译期解析十六进制(0x 前缀)或十进制的地址, 格式错误时编译失败
+const fn parse_addr(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let (radix, mut i) = if bytes.len() > 2 && bytes[0] == b'0' && (bytes[1] | 0x20) == b'x' {
+        (16, 2)
+    } else {
+        (10, 0)
+    };
+    assert!(i < bytes.len(), "MYSBI_JUMP_ADDR is empty");
+    let mut addr = 0;
+    while i < bytes.len() {
+        let digit = match bytes[i] {
+            b'_' => {
+                i += 1;
+                continue;
+            }
+            c @ b'0'..=b'9' => (c - b'0') as usize,
+            c @ b'a'..=b'f' if radix == 16 => (c - b'a' + 10) as usize,
+            c @ b'A'..=b'F' if radix == 16 => (c - b'A' + 10) as usize,
+            _ => panic!("invalid MYSBI_JUMP_ADDR"),
+        };
+        addr = addr * radix + digit;
+        i += 1;
+    }
+    addr
+}
+
+// 启动 hart, 由它完成 UART 等全局初始化并跳转到内核
+const BOOT_HART: usize = 0;
+
+// hartid 与 fdt 由 QEMU 在复位时通过 a0/a1 传入
 #[no_mangle]
-fn sbi_main() {
+fn sbi_main(hartid: usize, fdt: usize) -> ! {
+    if hartid != BOOT_HART {
+        // 其他 hart 只做本 hart 的初始化, 然后停在 M 模式等待 HSM hart_start
+        hart_init();
+        hsm::park(hartid);
+    }
     uart::init();
     println!(
         r"
@@ -25,24 +73,54 @@ fn sbi_main() {
 --------/-----------
 "
     );
+    println!("boot hart: {}, fdt: {:#x}", hartid, fdt);
+    println!("jump to {:#x} in S mode", FW_JUMP_ADDR);
+    hart_init();
+    // 等其余 hart 都停进 park 后再进入内核, 否则内核调用 hart_start 时它们可能还不存在
+    hsm::wait_for_harts(hartid, fdt::hart_mask(fdt));
+    hsm::mark_started(hartid);
+    // 按照 SBI 的启动约定, 内核入口处 a0 = hartid, a1 = fdt
+    enter_supervisor(hartid, FW_JUMP_ADDR, fdt)
+}
+
+// 每个 hart 都需要执行的 M 模式初始化
+fn hart_init() {
     // 设置M模式的异常向量表
     sbi_trap::init();
 
+    sbi_trap::delegate();
+    ipi::init();
+
+    // 新版 QEMU 会检查 PMP, 没有任何表项匹配时 S/U 模式的访存都会失败
+    // 这里用一个 NAPOT 表项覆盖整个地址空间, 并开放读/写/执行权限
+    // 同时允许 S/U 模式读取 cycle/time/instret 计数器
+    unsafe {
+        asm!(
+            "csrw pmpaddr0, {addr}",
+            "csrw pmpcfg0, {cfg}",
+            "csrw mcounteren, {cnt}",
+            addr = in(reg) usize::MAX >> 10,
+            cfg = in(reg) 0x1f,
+            cnt = in(reg) 0x7,
+        );
+    }
+}
+
+// 以 S 模式跳转到 start_addr, a0 为 hartid, a1 为 opaque
+pub fn enter_supervisor(hartid: usize, start_addr: usize, opaque: usize) -> ! {
     // 设置跳转模式为S模式
     let mut val = mstatus::read();
     val.set_mpp(mstatus::MPP::Supervisor);
     val.set_mpie(false);
     mstatus::write(val);
 
-    sbi_trap::delegate();
-
     // 设置M模式的异常程序计数器, 用于 mret 跳转
-    mepc::write(FW_JUMP_ADDR);
+    mepc::write(start_addr);
 
     unsafe {
         // 设置S模式的异常向量表入口地址
         //stvec::write(FW_JUMP_ADDR, stvec::TrapMode::Vectored);
-        stvec::write(FW_JUMP_ADDR, stvec::TrapMode::Direct);
+        stvec::write(start_addr, stvec::TrapMode::Direct);
 
         // 关闭S模式的中断
         sie::clear_ssoft();
@@ -55,6 +133,11 @@ fn sbi_main() {
 
     unsafe {
         // 切换到S模式
-        asm!("mret");
+        asm!(
+            "mret",
+            in("a0") hartid,
+            in("a1") opaque,
+            options(noreturn)
+        );
     }
 }
@@ -0,0 +1,238 @@
+//! SBI 调用的分发与各扩展的实现
+//!
+//! 调用约定: a7 为扩展号(EID), a6 为功能号(FID), a0~a5 为参数
+//! SBI v0.2 之后的扩展返回 SbiRet, 即 (a0: 错误码, a1: 返回值)
+//! EID 0x00~0x0F 为 legacy 扩展, 只通过 a0 返回一个值
+
+use crate::{clint, hsm, ipi, uart};
+use core::arch::asm;
+use riscv::register::mhartid;
+
+pub const SBI_SUCCESS: isize = 0;
+pub const SBI_ERR_FAILED: isize = -1;
+pub const SBI_ERR_NOT_SUPPORTED: isize = -2;
+pub const SBI_ERR_INVALID_PARAM: isize = -3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SbiRet {
+    pub error: isize,
+    pub value: usize,
+}
+
+impl SbiRet {
+    pub fn success(value: usize) -> Self {
+        Self {
+            error: SBI_SUCCESS,
+            value,
+        }
+    }
+
+    pub fn error(error: isize) -> Self {
+        Self { error, value: 0 }
+    }
+
+    pub fn not_supported() -> Self {
+        Self::error(SBI_ERR_NOT_SUPPORTED)
+    }
+}
+
+// legacy 扩展
+const SBI_SET_TIMER: usize = 0;
+const SBI_CONSOLE_PUTCHAR: usize = 1;
+const SBI_CONSOLE_GETCHAR: usize = 2;
+const SBI_SHUTDOWN: usize = 8;
+const SBI_LEGACY_END: usize = 0x0f;
+
+const SBI_EXT_BASE: usize = 0x10;
+const SBI_EXT_TIME: usize = 0x5449_4D45; // "TIME"
+const SBI_EXT_IPI: usize = 0x0073_5049; // "sPI"
+const SBI_EXT_RFENCE: usize = 0x5246_4E43; // "RFNC"
+const SBI_EXT_HSM: usize = 0x0048_534D; // "HSM"
+const SBI_EXT_SRST: usize = 0x5352_5354; // "SRST"
+const SBI_EXT_DBCN: usize = 0x4442_434E; // "DBCN", Debug Console
+
+// 实现的 SBI 规范版本 v2.0: [30:24] 主版本号, [23:0] 次版本号
+const SBI_SPEC_VERSION: usize = 2 << 24;
+// 实现 ID, 0~9 已被 OpenSBI/RustSBI 等占用, 这里取 "mysb"
+const SBI_IMPL_ID: usize = 0x6d79_7362;
+const SBI_IMPL_VERSION: usize = 1;
+
+pub fn sbi_ecall_handle(eid: usize, fid: usize, args: [usize; 6]) -> SbiRet {
+    match eid {
+        SBI_SET_TIMER => {
+            clint::timer_event_start(mhartid::read(), args[0]);
+            SbiRet::success(0)
+        }
+        SBI_CONSOLE_PUTCHAR => {
+            uart::putchar(args[0]);
+            SbiRet::success(0)
+        }
+        // 没有输入时返回 -1
+        SBI_CONSOLE_GETCHAR => match uart::getchar() {
+            Some(c) => SbiRet::success(c as usize),
+            None => SbiRet::error(SBI_ERR_FAILED),
+        },
+        SBI_SHUTDOWN => system_reset(SRST_SHUTDOWN, SRST_NO_REASON),
+        SBI_EXT_BASE => sbi_base_handle(fid, args[0]),
+        SBI_EXT_TIME => match fid {
+            0 => {
+                clint::timer_event_start(mhartid::read(), args[0]);
+                SbiRet::success(0)
+            }
+            _ => SbiRet::not_supported(),
+        },
+        SBI_EXT_IPI => match fid {
+            0 => ipi::send_ipi(args[0], args[1]),
+            _ => SbiRet::not_supported(),
+        },
+        SBI_EXT_RFENCE => match fid {
+            0 => ipi::remote_fence_i(args[0], args[1]),
+            // remote_sfence_vma / remote_sfence_vma_asid
+            1 | 2 => ipi::remote_sfence_vma(args[0], args[1]),
+            _ => SbiRet::not_supported(),
+        },
+        SBI_EXT_HSM => match fid {
+            0 => hsm::hart_start(args[0], args[1], args[2]),
+            1 => hsm::hart_stop(mhartid::read()),
+            2 => hsm::hart_get_status(args[0]),
+            _ => SbiRet::not_supported(),
+        },
+        SBI_EXT_SRST => match fid {
+            0 => system_reset(args[0], args[1]),
+            _ => SbiRet::not_supported(),
+        },
+        SBI_EXT_DBCN => sbi_dbcn_handle(fid, args[0], args[1], args[2]),
+        _ => SbiRet::not_supported(),
+    }
+}
+
+pub fn is_legacy(eid: usize) -> bool {
+    eid <= SBI_LEGACY_END
+}
+
+fn probe_extension(eid: usize) -> bool {
+    matches!(
+        eid,
+        SBI_SET_TIMER
+            | SBI_CONSOLE_PUTCHAR
+            | SBI_CONSOLE_GETCHAR
+            | SBI_SHUTDOWN
+            | SBI_EXT_BASE
+            | SBI_EXT_TIME
+            | SBI_EXT_IPI
+            | SBI_EXT_RFENCE
+            | SBI_EXT_HSM
+            | SBI_EXT_SRST
+            | SBI_EXT_DBCN
+    )
+}
+
+fn read_csr(csr: usize) -> usize {
+    let value: usize;
+    unsafe {
+        match csr {
+            0xf11 => asm!("csrr {}, mvendorid", out(reg) value),
+            0xf12 => asm!("csrr {}, marchid", out(reg) value),
+            _ => asm!("csrr {}, mimpid", out(reg) value),
+        }
+    }
+    value
+}
+
+fn sbi_base_handle(fid: usize, a0: usize) -> SbiRet {
+    match fid {
+        0 => SbiRet::success(SBI_SPEC_VERSION),
+        1 => SbiRet::success(SBI_IMPL_ID),
+        2 => SbiRet::success(SBI_IMPL_VERSION),
+        // 扩展存在时返回 1, 否则返回 0
+        3 => SbiRet::success(probe_extension(a0) as usize),
+        4 => SbiRet::success(read_csr(0xf11)),
+        5 => SbiRet::success(read_csr(0xf12)),
+        6 => SbiRet::success(read_csr(0xf13)),
+        _ => SbiRet::not_supported(),
+    }
+}
+
+// QEMU virt 平台的 sifive_test 设备, 写入特定的值即可关机或重启
+const VIRT_TEST: usize = 0x10_0000;
+const VIRT_TEST_FAIL: u32 = 0x3333;
+const VIRT_TEST_PASS: u32 = 0x5555;
+const VIRT_TEST_RESET: u32 = 0x7777;
+
+const SRST_SHUTDOWN: usize = 0;
+const SRST_COLD_REBOOT: usize = 1;
+const SRST_WARM_REBOOT: usize = 2;
+const SRST_NO_REASON: usize = 0;
+const SRST_SYSTEM_FAILURE: usize = 1;
+
+fn system_reset(reset_type: usize, reason: usize) -> SbiRet {
+    let value = match (reset_type, reason) {
+        (SRST_SHUTDOWN, SRST_NO_REASON) => VIRT_TEST_PASS,
+        // 退出码放在高 16 位, QEMU 以该退出码退出
+        (SRST_SHUTDOWN, SRST_SYSTEM_FAILURE) => VIRT_TEST_FAIL | 1 << 16,
+        (SRST_SHUTDOWN, _) => VIRT_TEST_FAIL | (reason as u32 & 0xffff) << 16,
+        (SRST_COLD_REBOOT | SRST_WARM_REBOOT, _) => VIRT_TEST_RESET,
+        _ => return SbiRet::error(SBI_ERR_INVALID_PARAM),
+    };
+    unsafe {
+        (VIRT_TEST as *mut u32).write_volatile(value);
+    }
+    // 写入后 QEMU 立即退出, 不会执行到这里
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+const SBI_EXT_DBCN_WRITE: usize = 0;
+const SBI_EXT_DBCN_READ: usize = 1;
+const SBI_EXT_DBCN_WRITE_BYTE: usize = 2;
+
+// S模式的内核没有开启分页, 传入的物理地址需要落在 DRAM 中
+const DRAM_START: usize = 0x8000_0000;
+const DRAM_END: usize = 0x8800_0000;
+
+// 检查 [base_lo, base_lo + len) 是否为合法的物理地址区间
+// RV64 上物理地址不超过 56 位, 高位部分 base_hi 必须为 0
+fn dbcn_buffer(len: usize, base_lo: usize, base_hi: usize) -> Option<usize> {
+    let end = base_lo.checked_add(len)?;
+    if base_hi != 0 || base_lo < DRAM_START || end > DRAM_END {
+        return None;
+    }
+    Some(base_lo)
+}
+
+fn sbi_dbcn_handle(fid: usize, a0: usize, a1: usize, a2: usize) -> SbiRet {
+    match fid {
+        // 将内存中的 a0 个字节写到控制台, 返回实际写入的字节数
+        SBI_EXT_DBCN_WRITE => match dbcn_buffer(a0, a1, a2) {
+            Some(base) => {
+                for i in 0..a0 {
+                    let c = unsafe { ((base + i) as *const u8).read_volatile() };
+                    uart::putchar(c as usize);
+                }
+                SbiRet::success(a0)
+            }
+            None => SbiRet::error(SBI_ERR_INVALID_PARAM),
+        },
+        // 从控制台最多读取 a0 个字节, 不会阻塞, 返回实际读到的字节数
+        SBI_EXT_DBCN_READ => match dbcn_buffer(a0, a1, a2) {
+            Some(base) => {
+                let mut count = 0;
+                while count < a0 {
+                    match uart::getchar() {
+                        Some(c) => unsafe { ((base + count) as *mut u8).write_volatile(c) },
+                        None => break,
+                    }
+                    count += 1;
+                }
+                SbiRet::success(count)
+            }
+            None => SbiRet::error(SBI_ERR_INVALID_PARAM),
+        },
+        SBI_EXT_DBCN_WRITE_BYTE => {
+            uart::putchar(a0 & 0xff);
+            SbiRet::success(0)
+        }
+        _ => SbiRet::not_supported(),
+    }
+}
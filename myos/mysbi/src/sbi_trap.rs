@@ -1,14 +1,18 @@
-use core::{arch::global_asm, panic};
+use core::{
+    arch::{asm, global_asm},
+    panic,
+};
 use riscv::{
     interrupt::machine::{Exception, Interrupt},
     register::{
         mcause::{self, Trap},
-        medeleg, mepc, mideleg, mie, mip, mstatus, mtval, mtvec,
+        medeleg, mepc, mideleg, mie, mstatus, mtval, mtvec, stvec,
     },
-    ExceptionNumber,
 };
 
-use crate::{print, println, uart};
+use crate::sbi_ecall::{is_legacy, sbi_ecall_handle, SBI_SUCCESS};
+use crate::unpriv::UnprivFault;
+use crate::{clint, emulate, ipi, print, println};
 
 global_asm!(include_str!("sbi_entry.S"));
 
@@ -58,67 +62,113 @@ pub fn delegate() {
     }
 }
 
+// mepc 之后依次是 x1~x31, 因此 x[i] 恰好位于下标 i 处
 #[repr(C)]
-struct SbiTrapRegs {
-    mepc: usize,
-    ra: usize,
-    sp: usize,
-    gp: usize,
-    tp: usize,
-    t0: usize,
-    t1: usize,
-    t2: usize,
-    s0: usize,
-    s1: usize,
-    a0: usize,
-    a1: usize,
-    a2: usize,
-    a3: usize,
-    a4: usize,
-    a5: usize,
-    a6: usize,
-    a7: usize,
-    s2: usize,
-    s3: usize,
-    s4: usize,
-    s5: usize,
-    s6: usize,
-    s7: usize,
-    s8: usize,
-    s9: usize,
-    s10: usize,
-    s11: usize,
-    t3: usize,
-    t4: usize,
-    t5: usize,
-    t6: usize,
-    mstatus: usize,
+pub struct SbiTrapRegs {
+    pub mepc: usize,
+    pub ra: usize,
+    pub sp: usize,
+    pub gp: usize,
+    pub tp: usize,
+    pub t0: usize,
+    pub t1: usize,
+    pub t2: usize,
+    pub s0: usize,
+    pub s1: usize,
+    pub a0: usize,
+    pub a1: usize,
+    pub a2: usize,
+    pub a3: usize,
+    pub a4: usize,
+    pub a5: usize,
+    pub a6: usize,
+    pub a7: usize,
+    pub s2: usize,
+    pub s3: usize,
+    pub s4: usize,
+    pub s5: usize,
+    pub s6: usize,
+    pub s7: usize,
+    pub s8: usize,
+    pub s9: usize,
+    pub s10: usize,
+    pub s11: usize,
+    pub t3: usize,
+    pub t4: usize,
+    pub t5: usize,
+    pub t6: usize,
+    pub mstatus: usize,
+}
+
+impl SbiTrapRegs {
+    fn as_array(&mut self) -> &mut [usize; 33] {
+        unsafe { &mut *(self as *mut Self as *mut [usize; 33]) }
+    }
+
+    // 读取通用寄存器 x[i], x0 恒为 0
+    pub fn reg(&mut self, i: usize) -> usize {
+        if i == 0 {
+            0
+        } else {
+            self.as_array()[i]
+        }
+    }
+
+    // 写入通用寄存器 x[i], 写 x0 无效
+    pub fn set_reg(&mut self, i: usize, value: usize) {
+        if i != 0 && i < 32 {
+            self.as_array()[i] = value;
+        }
+    }
 }
 
 #[no_mangle]
 //pub fn sbi_trap_handler(regs:&mut SbiTrapRegs) {
 // 是否有必要返回可变引用呢?
 pub fn sbi_trap_handler(regs: &mut SbiTrapRegs) -> &mut SbiTrapRegs {
-    let mut ret = 233;
+    let ret;
     let mcause = mcause::read();
-    let ecall_id = regs.a7;
     let mut msg = "which handle ???";
     let raw_trap = mcause.cause();
     let trap: Trap<Interrupt, Exception> = raw_trap.try_into().unwrap();
     match trap {
         Trap::Exception(exception) => {
-            if exception.number() == 0x09 {
-                //println!("sbi syscalled {}", ecall_id);
-                ret = sbi_ecall_handle(ecall_id, regs);
-                msg = "ecall handle failed!";
-            } else {
-                println!("which exception {:?}", exception);
-            }
+            let cause = mcause.code();
+            let tval = mtval::read();
+            let result = match exception {
+                Exception::SupervisorEnvCall => {
+                    //println!("sbi syscalled {}", ecall_id);
+                    sbi_ecall(regs);
+                    Ok(())
+                }
+                Exception::LoadMisaligned | Exception::StoreMisaligned => {
+                    emulate::misaligned(regs, cause, tval)
+                }
+                Exception::IllegalInstruction => emulate::illegal_insn(regs, tval),
+                // 其余异常没有被委托给 S 模式, 原样转交
+                _ => Err(UnprivFault { cause, tval }),
+            };
+            // 无法处理的异常, 或模拟过程中产生的新异常, 都交给 S 模式处理
+            ret = match result {
+                Ok(()) => 0,
+                Err(fault) => match redirect_to_smode(regs, fault) {
+                    Ok(()) => 0,
+                    Err(()) => {
+                        println!("exception {:?} tval {:#x}", exception, tval);
+                        msg = "unhandled exception in M mode";
+                        2
+                    }
+                },
+            };
         }
         Trap::Interrupt(interrupt) => match interrupt {
             Interrupt::MachineTimer => {
                 //println!("interrupt triggered {:?}", interrupt);
-                sbi_timer_process();
+                clint::timer_process();
+                ret = 0;
+            }
+            Interrupt::MachineSoft => {
+                ipi::handle_msoft();
                 ret = 0;
             }
             _ => {
@@ -134,53 +184,74 @@ pub fn sbi_trap_handler(regs: &mut SbiTrapRegs) -> &mut SbiTrapRegs {
     regs
 }
 
-const SBI_SET_TIMER: usize = 0;
-const SBI_CONSOLE_PUTCHAR: usize = 1;
-const SBI_CONSOLE_GETCHAR: usize = 2;
-fn sbi_ecall_handle(id: usize, regs: &mut SbiTrapRegs) -> usize {
-    let ret = match id {
-        SBI_SET_TIMER => {
-            clint_timer_event_start(regs.a0);
-            0
-        }
-        SBI_CONSOLE_PUTCHAR => {
-            uart::putchar(regs.a0);
-            0
-        }
-        SBI_CONSOLE_GETCHAR => 0,
-        _ => 1,
-    };
+// 处理 S 模式的 ecall, 返回值写回 a0/a1
+fn sbi_ecall(regs: &mut SbiTrapRegs) {
+    let eid = regs.a7;
+    let ret = sbi_ecall_handle(
+        eid,
+        regs.a6,
+        [regs.a0, regs.a1, regs.a2, regs.a3, regs.a4, regs.a5],
+    );
+    if is_legacy(eid) {
+        // legacy 扩展只通过 a0 返回, 出错时返回错误码
+        regs.a0 = if ret.error == SBI_SUCCESS {
+            ret.value
+        } else {
+            ret.error as usize
+        };
+    } else {
+        regs.a0 = ret.error as usize;
+        regs.a1 = ret.value;
+    }
 
     /* 系统调用返回的是系统调用指令
     （例如ECALL指令）的下一条指令 */
-    if ret == 0 {
-        regs.mepc += 4;
-    }
-
-    ret
+    regs.mepc += 4;
 }
 
-const VIRT_CLINT_ADDR: usize = 0x200_0000;
-const VIRT_CLINT_TIMER_CMP: usize = VIRT_CLINT_ADDR + 0x4000;
-const VIRT_CLINT_TIMER_VAL: usize = VIRT_CLINT_ADDR + 0xbff8;
-const CLINT_TIMER_BASE_FREQ: usize = 0x1000_0000;
-fn clint_timer_event_start(next_event: usize) {
-    unsafe {
-        (VIRT_CLINT_TIMER_CMP as *mut usize).write_volatile(next_event);
-        // 清S模式的timer pending irq
-        mip::clear_stimer();
-        // 使能M模式的timer中断
-        mie::set_mtimer();
-    }
-}
+const MSTATUS_SIE: usize = 1 << 1;
+const MSTATUS_SPIE: usize = 1 << 5;
+const MSTATUS_SPP: usize = 1 << 8;
+const MSTATUS_MPP_SHIFT: usize = 11;
+const MSTATUS_MPP_MASK: usize = 0b11 << MSTATUS_MPP_SHIFT;
+const PRV_S: usize = 1;
+const PRV_M: usize = 3;
 
-fn sbi_timer_process() {
+// 把异常转交给 S 模式, 效果与硬件把该异常委托给 S 模式相同:
+// 设置 scause/stval/sepc 与 sstatus 的 SPP/SPIE/SIE, 然后从 stvec 开始以 S 模式执行
+// M 模式自身产生的异常无法转交
+fn redirect_to_smode(regs: &mut SbiTrapRegs, fault: UnprivFault) -> Result<(), ()> {
+    let prev_mode = (regs.mstatus & MSTATUS_MPP_MASK) >> MSTATUS_MPP_SHIFT;
+    if prev_mode == PRV_M {
+        return Err(());
+    }
     unsafe {
-        // 关闭M模式的timer 中断
-        mie::clear_mtimer();
-        // 使能S模式的timer pending 中断
-        mip::set_stimer();
+        asm!(
+            "csrw scause, {cause}",
+            "csrw stval, {tval}",
+            "csrw sepc, {epc}",
+            cause = in(reg) fault.cause,
+            tval = in(reg) fault.tval,
+            epc = in(reg) regs.mepc,
+        );
+    }
+    let mut status = regs.mstatus;
+    if prev_mode == PRV_S {
+        status |= MSTATUS_SPP;
+    } else {
+        status &= !MSTATUS_SPP;
+    }
+    if status & MSTATUS_SIE != 0 {
+        status |= MSTATUS_SPIE;
+    } else {
+        status &= !MSTATUS_SPIE;
     }
+    status &= !MSTATUS_SIE;
+    // mret 后进入 S 模式
+    status = (status & !MSTATUS_MPP_MASK) | (PRV_S << MSTATUS_MPP_SHIFT);
+    regs.mstatus = status;
+    regs.mepc = stvec::read().address();
+    Ok(())
 }
 
 fn sbi_trap_error(regs: &mut SbiTrapRegs, msg: &str, ret: usize) {
@@ -35,7 +35,7 @@ const UART_LSR_BI: usize = 0x10; /* 传输被打断 */
 const UART_LSR_FE: usize = 0x08; /* 接收到没有停止位的帧 */
 const UART_LSR_PE: usize = 0x04; /* 奇偶校验错误位 */
 const UART_LSR_OE: usize = 0x02; /* 数据溢出 */
-const UART_LSR_DR: usize = 0x01; /* FIFO有数据 */
+const UART_LSR_DR: u8 = 0x01; /* FIFO有数据 */
 
 const UART_DEFAULT_BAUD: usize = 115200;
 const UART_16500_CLK: usize = 1843200;
@@ -45,6 +45,7 @@ pub fn init() {
 
     unsafe {
         // disable uart irq
+        // UART 的中断已委托给 S 模式, 由内核驱动开启; SBI 自身只以轮询方式收发
         (UART_IER as *mut u8).write_volatile(0);
 
         // enable DLAB for baud settings
@@ -67,3 +68,14 @@ pub fn putchar(c: usize) {
         (UART_DAT as *mut u8).write_volatile(c as u8);
     }
 }
+
+// 接收FIFO中没有数据时返回 None
+pub fn getchar() -> Option<u8> {
+    unsafe {
+        if (UART_LSR as *const u8).read_volatile() & UART_LSR_DR == 0 {
+            None
+        } else {
+            Some((UART_DAT as *const u8).read_volatile())
+        }
+    }
+}
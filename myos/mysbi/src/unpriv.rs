@@ -0,0 +1,90 @@
+//! 以陷入前特权级的身份访问内存
+//!
+//! 模拟指令时, 出错的指令与访存地址都是 S/U 模式下的虚拟地址
+//! 这里临时打开 mstatus.MPRV, 让访存按照 MPP 所记录的特权级进行地址转换与权限检查
+//! 同时打开 MXR, 以便读取只有执行权限的页面中的指令
+//!
+//! 访存出错时会再次陷入 M 模式, 此时 mtvec 临时指向 sbi_unpriv_trap
+//! 它只记录 mcause/mtval 并跳过出错的访存指令, 由调用者决定如何处理
+
+use core::arch::{asm, global_asm};
+
+const MSTATUS_MPRV: usize = 1 << 17;
+const MSTATUS_MXR: usize = 1 << 19;
+
+// 访存出错时的 mcause/mtval
+#[derive(Debug, Clone, Copy)]
+pub struct UnprivFault {
+    pub cause: usize,
+    pub tval: usize,
+}
+
+// 访存指令需要是 4 字节的, 否则 sbi_unpriv_trap 无法正确跳过它
+global_asm!(
+    r"
+    .section .text
+    .align 2
+    .global sbi_unpriv_trap
+sbi_unpriv_trap:
+    .option push
+    .option norvc
+    csrr a1, mcause
+    csrr a2, mtval
+    csrr a3, mepc
+    addi a3, a3, 4
+    csrw mepc, a3
+    mret
+    .option pop
+"
+);
+
+extern "C" {
+    fn sbi_unpriv_trap();
+}
+
+// 生成一次非特权访存, a1 非 0 表示访存出错
+// $input 为写入的值, 读出的值存入 $output
+macro_rules! unpriv_access {
+    ($insn:literal, $addr:expr, $input:expr => $output:tt) => {{
+        let cause: usize;
+        let tval: usize;
+        asm!(
+            ".option push",
+            ".option norvc",
+            "csrrw {mtvec}, mtvec, {handler}",
+            "csrrs {mstatus}, mstatus, {mprv}",
+            concat!($insn, " {val}, 0({addr})"),
+            "csrw mstatus, {mstatus}",
+            "csrw mtvec, {mtvec}",
+            ".option pop",
+            handler = in(reg) sbi_unpriv_trap as usize,
+            mprv = in(reg) MSTATUS_MPRV | MSTATUS_MXR,
+            addr = in(reg) $addr,
+            val = inout(reg) $input => $output,
+            mtvec = out(reg) _,
+            mstatus = out(reg) _,
+            inout("a1") 0usize => cause,
+            out("a2") tval,
+            out("a3") _,
+        );
+        if cause == 0 {
+            Ok(())
+        } else {
+            Err(UnprivFault { cause, tval })
+        }
+    }};
+}
+
+pub fn load_u8(addr: usize) -> Result<u8, UnprivFault> {
+    let val: usize;
+    unsafe { unpriv_access!("lbu", addr, 0usize => val) }.map(|_| val as u8)
+}
+
+pub fn load_u16(addr: usize) -> Result<u16, UnprivFault> {
+    let val: usize;
+    unsafe { unpriv_access!("lhu", addr, 0usize => val) }.map(|_| val as u16)
+}
+
+pub fn store_u8(addr: usize, byte: u8) -> Result<(), UnprivFault> {
+    unsafe { unpriv_access!("sb", addr, byte as usize => _) }
+}
@@ -0,0 +1,16 @@
+use std::fs::File;
+use std::io::Result;
+use std::path::Path;
+
+fn main() {
+    ensure_ksyms().unwrap();
+}
+
+// 内核符号表由 xtask 在第一遍编译后生成, 第一次编译时先用一个空的符号表
+fn ensure_ksyms() -> Result<()> {
+    const KSYMS_PATH: &str = "src/ksyms.txt";
+    if !Path::new(KSYMS_PATH).exists() {
+        File::create(KSYMS_PATH)?;
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use core::arch::{asm, naked_asm};
+use kernel_test::kernel_test;
 
 /// bltu:无符号小于时分支跳转
 /// bltu rs1, rs2, offset if (rs1 < rs2) ...
@@ -82,3 +83,14 @@ pub unsafe fn is_zero(a: u64) -> bool {
         true
     }
 }
+
+#[kernel_test]
+fn compare_test() {
+    unsafe {
+        assert!(is_little_than(100, 1000));
+        assert!(!is_little_than(1000, 100));
+        assert!(!is_little_than(100, 100));
+        assert!(is_zero(0));
+        assert!(!is_zero(2));
+    }
+}
@@ -1,5 +1,6 @@
 use core::arch::asm;
 
+use crate::ksyms;
 use crate::println;
 
 /*
@@ -12,23 +13,55 @@ use crate::println;
  * 导致没有结束的标志, 可能永远追不到头?
  */
 
+// boot.S 中为内核预留的栈的大小
+const STACK_SIZE: usize = 4096;
+// 回溯的最大深度, 防止栈被破坏时陷入死循环
+const MAX_DEPTH: usize = 32;
+
+// ra 指向 call 的下一条指令, 用 ra - 1 查找, 避免 call 位于函数末尾时找到下一个函数
+fn print_frame(ra: usize, fp: usize) {
+    match ksyms::lookup(ra - 1) {
+        Some((name, offset)) => {
+            let offset = offset + 1;
+            println!(
+                "ra = 0x{:016x} {}+{:#x}, fp = 0x{:016x}",
+                ra, name, offset, fp
+            );
+        }
+        None => {
+            println!("ra = 0x{:016x} ???, fp = 0x{:016x}", ra, fp);
+        }
+    }
+}
+
+// 只在内核栈 [stacks_start, stacks_start + STACK_SIZE] 范围内回溯
+// 栈帧链被破坏或者没有结束标志时, 也不会访问到栈以外的内存
 pub unsafe fn print_backtrace() {
     extern "C" {
-        fn kernel_main();
+        fn stacks_start();
     }
+    let bottom = stacks_start as usize;
+    let top = bottom + STACK_SIZE;
 
-    let mut fp: *const usize = core::ptr::null_mut();
-    let mut stop_fp: *const usize = core::ptr::null_mut();
+    let mut fp: usize;
     asm!("mv {}, fp", out(reg) fp);
-    asm!("la t0, {}",  sym kernel_main );
-    asm!("mv {}, t0",out(reg) stop_fp );
-    //stop_fp = 0x80205000 as *const usize;
 
-    while !fp.is_null() {
-        //while fp != stop_fp {
-        let saved_ra = *fp.sub(1);
-        let saved_fp = *fp.sub(2);
-        println!("ra = 0x{:016x}, fp = 0x{:016x}", saved_ra, saved_fp);
-        fp = saved_fp as *const usize;
+    for _ in 0..MAX_DEPTH {
+        // fp 必须按 8 字节对齐, 且 ra/fp 两项都位于栈内
+        if fp % 8 != 0 || fp < bottom + 16 || fp > top {
+            break;
+        }
+        let frame = fp as *const usize;
+        let saved_ra = *frame.sub(1);
+        let saved_fp = *frame.sub(2);
+        if saved_ra == 0 {
+            break;
+        }
+        print_frame(saved_ra, saved_fp);
+        // 栈向低地址增长, 上一帧的 fp 一定更大
+        if saved_fp <= fp {
+            break;
+        }
+        fp = saved_fp;
     }
 }
@@ -1,4 +1,5 @@
 use core::arch::asm;
+use kernel_test::kernel_test;
 
 pub unsafe fn global_asm_test() {
     extern "C" {
@@ -90,3 +91,14 @@ pub fn memset(addr: u64, val: u8, len: u64) {
 
     // solution4: asm code? make use of sb & sd
 }
+
+#[kernel_test]
+fn memcpy_test() {
+    // my_memcpy_test 每次复制 8 字节
+    let src: [u64; 4] = [0x1234_5678_abcd_abcd, 0, 1, u64::MAX];
+    let mut dst = [0u64; 4];
+    unsafe {
+        memcpy(src.as_ptr() as u64, dst.as_mut_ptr() as u64, 32);
+    }
+    assert_eq!(src, dst);
+}
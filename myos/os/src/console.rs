@@ -5,7 +5,8 @@ struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
+        // 按字节输出, 多字节的 UTF-8 字符也能正确显示
+        for c in s.bytes() {
             putchar(c as usize);
         }
         Ok(())
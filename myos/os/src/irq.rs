@@ -0,0 +1,73 @@
+//! 外部中断的注册与分发
+//!
+//! 设备驱动(UART, virtio 等)调用 register_irq 注册中断处理函数, 同时在 PLIC 中使能该中断
+//! SupervisorExternal 中断到来时, do_exception 调用 handle_external_irq 完成 claim/分发/complete
+
+use crate::plic::{self, Mode, PLIC_MAX_IRQ};
+use crate::println;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub type IrqHandler = fn(usize);
+
+#[derive(Debug, PartialEq)]
+pub enum IrqError {
+    InvalidIrq,
+    AlreadyRegistered,
+    NotRegistered,
+}
+
+// 目前只启动了 hart 0
+const BOOT_HART: usize = 0;
+const DEFAULT_PRIORITY: u32 = 1;
+
+// 处理函数的地址, 0 表示未注册
+// 没有堆和锁, 这里用原子变量保存函数指针
+static IRQ_HANDLERS: [AtomicUsize; PLIC_MAX_IRQ] = [const { AtomicUsize::new(0) }; PLIC_MAX_IRQ];
+
+pub fn init() {
+    // S 模式接收所有优先级不为 0 的中断
+    plic::set_threshold(BOOT_HART, Mode::Supervisor, 0);
+}
+
+pub fn register_irq(irq: usize, handler: IrqHandler) -> Result<(), IrqError> {
+    if irq == 0 || irq >= PLIC_MAX_IRQ {
+        return Err(IrqError::InvalidIrq);
+    }
+    IRQ_HANDLERS[irq]
+        .compare_exchange(0, handler as usize, Ordering::AcqRel, Ordering::Acquire)
+        .map_err(|_| IrqError::AlreadyRegistered)?;
+    plic::set_priority(irq, DEFAULT_PRIORITY);
+    plic::enable(BOOT_HART, Mode::Supervisor, irq);
+    Ok(())
+}
+
+pub fn unregister_irq(irq: usize) -> Result<(), IrqError> {
+    if irq == 0 || irq >= PLIC_MAX_IRQ {
+        return Err(IrqError::InvalidIrq);
+    }
+    plic::disable(BOOT_HART, Mode::Supervisor, irq);
+    plic::set_priority(irq, 0);
+    match IRQ_HANDLERS[irq].swap(0, Ordering::AcqRel) {
+        0 => Err(IrqError::NotRegistered),
+        _ => Ok(()),
+    }
+}
+
+pub fn handle_external_irq() {
+    loop {
+        let irq = plic::claim(BOOT_HART, Mode::Supervisor);
+        if irq == 0 {
+            break;
+        }
+        match IRQ_HANDLERS[irq as usize].load(Ordering::Acquire) {
+            0 => {
+                println!("unhandled external irq {}", irq);
+            }
+            addr => {
+                let handler: IrqHandler = unsafe { core::mem::transmute(addr) };
+                handler(irq as usize);
+            }
+        }
+        plic::complete(BOOT_HART, Mode::Supervisor, irq);
+    }
+}
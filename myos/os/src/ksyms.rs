@@ -0,0 +1,50 @@
+//! 内嵌到内核中的符号表
+//!
+//! 与 rCore 内核(os/src/ksyms.rs)相同, 符号表由 `cargo xtask make` 调用 os/scripts/gen-ksyms.sh
+//! 在第一遍编译后从内核 ELF 中提取, 再编译进 .ksyms 段
+//! 每行格式为 "地址(十六进制) 符号名", 按地址升序排列
+//! 查找时只通过链接脚本中的 __start_ksyms/__stop_ksyms 访问它,
+//! 这样符号表的大小不会以立即数的形式出现在代码中, 两遍编译得到的代码完全相同
+
+#[link_section = ".ksyms"]
+#[used]
+static KSYMS: [u8; include_bytes!("ksyms.txt").len()] = *include_bytes!("ksyms.txt");
+
+fn ksyms() -> &'static [u8] {
+    extern "C" {
+        fn __start_ksyms();
+        fn __stop_ksyms();
+    }
+    let start = __start_ksyms as usize;
+    let end = __stop_ksyms as usize;
+    unsafe { core::slice::from_raw_parts(start as *const u8, end - start) }
+}
+
+fn parse_line(line: &[u8]) -> Option<(usize, &str)> {
+    let space = line.iter().position(|&c| c == b' ')?;
+    let addr = core::str::from_utf8(&line[..space]).ok()?;
+    let addr = usize::from_str_radix(addr, 16).ok()?;
+    let name = core::str::from_utf8(&line[space + 1..]).ok()?;
+    Some((addr, name))
+}
+
+// 查找 pc 所在的函数, 返回函数名与 pc 相对函数入口的偏移
+// pc 不在内核代码段中, 或符号表为空(第一遍编译)时返回 None
+pub fn lookup(pc: usize) -> Option<(&'static str, usize)> {
+    extern "C" {
+        fn stext();
+        fn etext();
+    }
+    if pc < stext as usize || pc >= etext as usize {
+        return None;
+    }
+    let mut found = None;
+    for line in ksyms().split(|&c| c == b'\n') {
+        match parse_line(line) {
+            Some((addr, _)) if addr > pc => break,
+            Some((addr, name)) => found = Some((name, pc - addr)),
+            None => continue,
+        }
+    }
+    found
+}
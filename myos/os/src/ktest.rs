@@ -0,0 +1,48 @@
+//! 内核自测框架, 与 rCore 内核(os/src/ktest.rs)的输出格式相同, 由 `cargo xtask test` 解析
+//!
+//! 用 `#[kernel_test]` 标记的函数在测试内核中依次执行, 全部通过后以成功状态退出 QEMU
+//! 测试失败即 panic, 由 panic 处理函数调用 [`report_failure`] 后以失败状态退出
+
+use crate::syscall::sbi_shutdown;
+use crate::uart;
+use crate::{print, println};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub struct KernelTest {
+    pub name: &'static str,
+    pub func: fn(),
+}
+
+// 已经通过的测试数量
+static PASSED: AtomicUsize = AtomicUsize::new(0);
+// 正在执行测试函数时为 true, 只有这时的 panic 才算作测试失败
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub fn run(tests: &[&KernelTest]) -> ! {
+    println!("running {} tests", tests.len());
+    for test in tests {
+        print!("test {} ... ", test.name);
+        RUNNING.store(true, Ordering::Relaxed);
+        (test.func)();
+        RUNNING.store(false, Ordering::Relaxed);
+        println!("ok");
+        PASSED.fetch_add(1, Ordering::Relaxed);
+    }
+    println!(
+        "test result: ok. {} passed; 0 failed",
+        PASSED.load(Ordering::Relaxed)
+    );
+    uart::flush();
+    sbi_shutdown(false)
+}
+
+pub fn report_failure() {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    println!("FAILED");
+    println!(
+        "test result: FAILED. {} passed; 1 failed",
+        PASSED.load(Ordering::Relaxed)
+    );
+}
@@ -1,11 +1,26 @@
 use core::panic::PanicInfo;
 
 use crate::base::fp::print_backtrace;
+#[cfg(test)]
+use crate::println;
+use crate::{trap::arch_local_irq_enable, uart};
 
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
+    // 关闭中断, 之后的输出都以轮询的方式直接送出
+    arch_local_irq_enable(false);
+    uart::flush();
+    // 测试内核中 panic 意味着当前测试失败
+    #[cfg(test)]
+    {
+        crate::ktest::report_failure();
+        println!("{}", _info);
+    }
     unsafe {
         print_backtrace();
     }
+    #[cfg(test)]
+    crate::syscall::sbi_shutdown(true);
+    #[cfg(not(test))]
     loop {}
 }
@@ -1,13 +1,22 @@
 #![no_std]
 #![no_main]
 #![feature(naked_functions)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::ktest::run)]
+#![reexport_test_harness_main = "test_main"]
 use core::arch::asm;
 use core::arch::global_asm;
 
 mod config;
 mod console;
+mod irq;
+mod ksyms;
+#[cfg(test)]
+mod ktest;
 mod lang_item;
 mod mm;
+mod plic;
+mod ring_buffer;
 mod syscall;
 mod timer;
 mod trap;
@@ -62,10 +71,14 @@ unsafe fn base_asm_test() {
     base::csr::csrrw();
 }
 
+// 设备树头部的魔数, 按大端序存放
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
 #[no_mangle]
-fn kernel_main() -> ! {
+fn kernel_main(hartid: usize, fdt: usize) -> ! {
     clear_bss();
     syscall::sbi_put_string("Hello SBI syscall!\n");
+    syscall::sbi_debug_console_write("Hello SBI debug console!\n").unwrap();
     let a = 12u8;
     let b = 32u8;
     let c = a + b;
@@ -76,8 +89,21 @@ fn kernel_main() -> ! {
     uart::putchar('c' as usize);
     uart::putchar('v' as usize);
     trap::init();
+    irq::init();
+    uart::enable_irq();
 
     println!("hello myOS, {}", c);
+    println!("boot hart: {}, fdt: {:#x}", hartid, fdt);
+    if fdt != 0 {
+        let magic = u32::from_be(unsafe { (fdt as *const u32).read_volatile() });
+        if magic != FDT_MAGIC {
+            println!("invalid fdt magic {:#x}", magic);
+        }
+    }
+
+    // 测试内核(cargo xtask test)在这里运行全部 #[kernel_test] 并退出 QEMU
+    #[cfg(test)]
+    test_main();
 
     timer::init();
 
@@ -89,7 +115,12 @@ fn kernel_main() -> ! {
         //base_asm_test();
     }
 
-    loop {}
+    // 回显串口收到的字符, 数据由 UART 中断放入接收缓冲区
+    loop {
+        if let Some(c) = uart::getchar() {
+            uart::putchar(c as usize);
+        }
+    }
 }
 
 fn clear_bss() {
@@ -0,0 +1,82 @@
+//! QEMU virt 平台的 PLIC(Platform-Level Interrupt Controller)
+//!
+//! PLIC 将外设的中断路由到各个 hart 的各个特权级, 每个 (hart, 特权级) 称为一个 context
+//! QEMU virt 平台上, hart i 的 M 模式 context 为 2i, S 模式 context 为 2i + 1
+
+const VIRT_PLIC_ADDR: usize = 0x0c00_0000;
+/* 中断源的优先级, 每个中断源 4 字节, 0 号中断源保留 */
+const PLIC_PRIORITY: usize = VIRT_PLIC_ADDR + 0x0000;
+/* 中断源的待处理位, 每个中断源 1 bit */
+const PLIC_PENDING: usize = VIRT_PLIC_ADDR + 0x1000;
+/* 每个 context 的中断使能位, 每个 context 0x80 字节 */
+const PLIC_ENABLE: usize = VIRT_PLIC_ADDR + 0x2000;
+/* 每个 context 的优先级阈值, 每个 context 0x1000 字节 */
+const PLIC_THRESHOLD: usize = VIRT_PLIC_ADDR + 0x20_0000;
+/* 每个 context 的 claim/complete 寄存器, 紧跟在阈值之后 */
+const PLIC_CLAIM: usize = VIRT_PLIC_ADDR + 0x20_0004;
+
+const PLIC_ENABLE_STRIDE: usize = 0x80;
+const PLIC_CONTEXT_STRIDE: usize = 0x1000;
+
+pub const PLIC_MAX_IRQ: usize = 128;
+pub const PLIC_MAX_PRIORITY: u32 = 7;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Mode {
+    Machine = 0,
+    Supervisor = 1,
+}
+
+fn context(hart: usize, mode: Mode) -> usize {
+    hart * 2 + mode as usize
+}
+
+fn read(addr: usize) -> u32 {
+    unsafe { (addr as *const u32).read_volatile() }
+}
+
+fn write(addr: usize, val: u32) {
+    unsafe { (addr as *mut u32).write_volatile(val) }
+}
+
+pub fn set_priority(irq: usize, priority: u32) {
+    assert!(irq > 0 && irq < PLIC_MAX_IRQ && priority <= PLIC_MAX_PRIORITY);
+    write(PLIC_PRIORITY + irq * 4, priority);
+}
+
+pub fn is_pending(irq: usize) -> bool {
+    read(PLIC_PENDING + irq / 32 * 4) & (1 << (irq % 32)) != 0
+}
+
+fn enable_addr(hart: usize, mode: Mode, irq: usize) -> usize {
+    PLIC_ENABLE + context(hart, mode) * PLIC_ENABLE_STRIDE + irq / 32 * 4
+}
+
+pub fn enable(hart: usize, mode: Mode, irq: usize) {
+    let addr = enable_addr(hart, mode, irq);
+    write(addr, read(addr) | 1 << (irq % 32));
+}
+
+pub fn disable(hart: usize, mode: Mode, irq: usize) {
+    let addr = enable_addr(hart, mode, irq);
+    write(addr, read(addr) & !(1 << (irq % 32)));
+}
+
+// 只有优先级严格大于阈值的中断才会送达该 context
+pub fn set_threshold(hart: usize, mode: Mode, threshold: u32) {
+    assert!(threshold <= PLIC_MAX_PRIORITY);
+    write(
+        PLIC_THRESHOLD + context(hart, mode) * PLIC_CONTEXT_STRIDE,
+        threshold,
+    );
+}
+
+// 领取优先级最高的待处理中断, 0 表示没有待处理的中断
+pub fn claim(hart: usize, mode: Mode) -> u32 {
+    read(PLIC_CLAIM + context(hart, mode) * PLIC_CONTEXT_STRIDE)
+}
+
+// 通知 PLIC 该中断已处理完, 之后它才能再次触发
+pub fn complete(hart: usize, mode: Mode, irq: u32) {
+    write(PLIC_CLAIM + context(hart, mode) * PLIC_CONTEXT_STRIDE, irq);
+}
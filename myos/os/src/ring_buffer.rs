@@ -0,0 +1,51 @@
+//! 定长环形缓冲区, 用于 UART 的收发
+//!
+//! head 指向下一个要读出的位置, tail 指向下一个要写入的位置
+//! 为区分空与满, 额外记录元素个数 len
+
+pub struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            tail: 0,
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    // 缓冲区已满时返回 false, 数据被丢弃
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[self.tail] = byte;
+        self.tail = (self.tail + 1) % N;
+        self.len += 1;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+}
@@ -34,3 +34,83 @@ pub fn sbi_put_string(str: &str) {
         sbi_putchar(c);
     });
 }
+
+// 没有输入时返回 -1
+pub fn sbi_getchar() -> isize {
+    syscall(SBI_CONSOLE_GETCHAR, [0, 0, 0])
+}
+
+/// SBI v0.2 之后的调用约定: a7 为扩展号(EID), a6 为功能号(FID)
+/// 返回 (a0: 错误码, a1: 返回值)
+fn sbi_call(eid: usize, fid: usize, args: [usize; 3]) -> (isize, usize) {
+    let error: isize;
+    let value: usize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a6") fid,
+            in("a7") eid
+        );
+    }
+
+    (error, value)
+}
+
+const SBI_EXT_DBCN: usize = 0x4442_434E;
+const SBI_EXT_DBCN_WRITE: usize = 0;
+const SBI_EXT_DBCN_READ: usize = 1;
+
+/// 通过 Debug Console 扩展一次写出整个字符串, 返回写出的字节数
+/// 内核没有开启分页, 虚拟地址即物理地址
+pub fn sbi_debug_console_write(str: &str) -> Result<usize, isize> {
+    let (error, value) = sbi_call(
+        SBI_EXT_DBCN,
+        SBI_EXT_DBCN_WRITE,
+        [str.len(), str.as_ptr() as usize, 0],
+    );
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error)
+    }
+}
+
+/// 非阻塞地读取控制台输入, 返回读到的字节数
+pub fn sbi_debug_console_read(buf: &mut [u8]) -> Result<usize, isize> {
+    let (error, value) = sbi_call(
+        SBI_EXT_DBCN,
+        SBI_EXT_DBCN_READ,
+        [buf.len(), buf.as_mut_ptr() as usize, 0],
+    );
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error)
+    }
+}
+
+/// 通过 System Reset 扩展关机, failure 为 true 时 QEMU 以失败状态退出
+/// 只有测试内核在测试结束后需要关机
+#[cfg(test)]
+pub fn sbi_shutdown(failure: bool) -> ! {
+    const SBI_EXT_SRST: usize = 0x5352_5354;
+    const SBI_EXT_SRST_RESET: usize = 0;
+    const SBI_SRST_TYPE_SHUTDOWN: usize = 0;
+    const SBI_SRST_REASON_NONE: usize = 0;
+    const SBI_SRST_REASON_FAILURE: usize = 1;
+
+    let reason = if failure {
+        SBI_SRST_REASON_FAILURE
+    } else {
+        SBI_SRST_REASON_NONE
+    };
+    sbi_call(
+        SBI_EXT_SRST,
+        SBI_EXT_SRST_RESET,
+        [SBI_SRST_TYPE_SHUTDOWN, reason, 0],
+    );
+    unreachable!()
+}
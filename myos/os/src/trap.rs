@@ -8,7 +8,7 @@ use riscv::{
     },
 };
 
-use crate::{println, timer::handle_timer_irq};
+use crate::{irq::handle_external_irq, println, timer::handle_timer_irq};
 
 global_asm!(include_str!("entry.S"));
 
@@ -22,6 +22,23 @@ pub fn arch_local_irq_enable(is_enable: bool) {
     }
 }
 
+pub fn local_irq_enabled() -> bool {
+    sstatus::read().sie()
+}
+
+// 关闭 S 模式中断, 返回之前的中断状态, 供 local_irq_restore 恢复
+pub fn local_irq_save() -> bool {
+    let flags = local_irq_enabled();
+    arch_local_irq_enable(false);
+    flags
+}
+
+pub fn local_irq_restore(flags: bool) {
+    if flags {
+        arch_local_irq_enable(true);
+    }
+}
+
 pub fn init() {
     extern "C" {
         fn do_exception_vector();
@@ -63,7 +80,7 @@ fn do_exception(regs: &mut KernelTrapRegs, scause: usize) {
                 println!("SupervisorSoft handle");
             }
             Interrupt::SupervisorExternal => {
-                println!("SupervisorExternal handle");
+                handle_external_irq();
             }
         },
         Trap::Exception(exception) => match exception {
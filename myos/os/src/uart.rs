@@ -14,6 +14,11 @@
 //     unreachable!()
 // }
 
+use crate::irq::register_irq;
+use crate::ring_buffer::RingBuffer;
+use crate::trap::{local_irq_enabled, local_irq_restore, local_irq_save};
+use core::cell::UnsafeCell;
+
 const UART_BASE_ADDR: usize = 0x10000000;
 /* THR:transmitter holding register */
 const UART_DAT: usize = UART_BASE_ADDR + 0x00; /* 数据寄存器*/
@@ -30,40 +35,189 @@ const UART_DLM: usize = UART_BASE_ADDR + 0x01; /*预分频寄存器高8位*/
 
 const UART_LSR_ERROR: usize = 0x80; /* 出错 */
 const UART_LSR_EMPTY: u8 = 0x40; /* 传输FIFO和移位寄存器为空 */
-const UART_LSR_TFE: usize = 0x20; /* 传输FIFO为空 */
+const UART_LSR_TFE: u8 = 0x20; /* 传输FIFO为空 */
 const UART_LSR_BI: usize = 0x10; /* 传输被打断 */
 const UART_LSR_FE: usize = 0x08; /* 接收到没有停止位的帧 */
 const UART_LSR_PE: usize = 0x04; /* 奇偶校验错误位 */
 const UART_LSR_OE: usize = 0x02; /* 数据溢出 */
-const UART_LSR_DR: usize = 0x01; /* FIFO有数据 */
+const UART_LSR_DR: u8 = 0x01; /* FIFO有数据 */
+
+const UART_IER_RDI: u8 = 0x01; /* 接收数据可用中断 */
+const UART_IER_THRI: u8 = 0x02; /* 发送保持寄存器空中断 */
+
+const UART_IIR_NO_INT: u8 = 0x01; /* 没有待处理的中断 */
+const UART_IIR_ID: u8 = 0x0e; /* 中断类型 */
+const UART_IIR_THRI: u8 = 0x02; /* 发送保持寄存器空 */
+const UART_IIR_RDI: u8 = 0x04; /* 接收数据可用 */
+const UART_IIR_RX_TIMEOUT: u8 = 0x0c; /* 接收超时, FIFO 中的数据未达到水位 */
+const UART_IIR_RLSI: u8 = 0x06; /* 线路状态变化 */
+
+const UART_MCR_DTR: u8 = 0x01;
+const UART_MCR_RTS: u8 = 0x02;
+const UART_MCR_OUT2: u8 = 0x08; /* 部分硬件需要置位 OUT2 才会向外送出中断 */
+
+// 发送 FIFO 的深度, 每次 THR 空中断最多可以写入这么多字节
+const UART_TX_FIFO_SIZE: usize = 16;
+
+// QEMU virt 平台上 UART0 的外部中断号
+pub const UART_IRQ: usize = 10;
+
+const UART_BUF_SIZE: usize = 1024;
 
 const UART_DEFAULT_BAUD: usize = 115200;
 const UART_16500_CLK: usize = 1843200;
 
+fn read_reg(reg: usize) -> u8 {
+    unsafe { (reg as *const u8).read_volatile() }
+}
+
+fn write_reg(reg: usize, val: u8) {
+    unsafe { (reg as *mut u8).write_volatile(val) }
+}
+
+struct UartState {
+    rx: RingBuffer<UART_BUF_SIZE>,
+    tx: RingBuffer<UART_BUF_SIZE>,
+    // 是否已经注册中断处理函数并打开了 UART 中断
+    irq_enabled: bool,
+}
+
+// 缓冲区在普通流程与中断处理函数之间共享
+// 目前只有一个 hart, 访问期间关闭 S 模式中断即可保证互斥
+struct UartCell(UnsafeCell<UartState>);
+
+unsafe impl Sync for UartCell {}
+
+impl UartCell {
+    fn with<R>(&self, f: impl FnOnce(&mut UartState) -> R) -> R {
+        let flags = local_irq_save();
+        let ret = f(unsafe { &mut *self.0.get() });
+        local_irq_restore(flags);
+        ret
+    }
+}
+
+static UART: UartCell = UartCell(UnsafeCell::new(UartState {
+    rx: RingBuffer::new(),
+    tx: RingBuffer::new(),
+    irq_enabled: false,
+}));
+
 pub fn init() {
     let divisor = UART_16500_CLK / (16 * UART_DEFAULT_BAUD);
 
-    unsafe {
-        // disable uart irq
-        (UART_IER as *mut u8).write_volatile(0);
+    // disable uart irq, 等 PLIC 初始化完成后再由 enable_irq 打开
+    write_reg(UART_IER, 0);
+
+    // enable DLAB for baud settings
+    write_reg(UART_LCR, 0x80);
+    write_reg(UART_DLL, divisor as u8);
+    write_reg(UART_DLM, (divisor >> 8) as u8);
+
+    // set uart format
+    /*8 bits, no parity, one stop bit*/
+    write_reg(UART_LCR, 0x03u8);
 
-        // enable DLAB for baud settings
-        (UART_LCR as *mut u8).write_volatile(0x80);
-        (UART_DLL as *mut u8).write_volatile(divisor as u8);
-        (UART_DLM as *mut u8).write_volatile((divisor >> 8) as u8);
+    // enable fifo, clear fifo, and watermark at 14B
+    write_reg(UART_FCR, 0xc7u8);
+
+    write_reg(UART_MCR, UART_MCR_DTR | UART_MCR_RTS | UART_MCR_OUT2);
+}
+
+// 注册中断处理函数, 打开接收中断
+// 之后的输出先写入发送缓冲区, 由 THR 空中断搬运到硬件 FIFO
+pub fn enable_irq() {
+    register_irq(UART_IRQ, handle_irq).unwrap();
+    UART.with(|uart| {
+        uart.irq_enabled = true;
+        write_reg(UART_IER, UART_IER_RDI);
+    });
+}
+
+// 把发送缓冲区中的数据写入硬件 FIFO, 最多写满一次 FIFO
+fn fill_tx_fifo(uart: &mut UartState) {
+    if read_reg(UART_LSR) & UART_LSR_TFE == 0 {
+        return;
+    }
+    for _ in 0..UART_TX_FIFO_SIZE {
+        match uart.tx.pop() {
+            Some(c) => write_reg(UART_DAT, c),
+            None => break,
+        }
+    }
+}
 
-        // set uart format
-        /*8 bits, no parity, one stop bit*/
-        (UART_LCR as *mut u8).write_volatile(0x03u8);
+// 轮询发送一个字节
+fn putchar_sync(c: u8) {
+    while read_reg(UART_LSR) & UART_LSR_EMPTY == 0 {}
+    write_reg(UART_DAT, c);
+}
 
-        // enable fifo, clear fifo, and watermark at 14B
-        (UART_FCR as *mut u8).write_volatile(0xc7u8);
+// 以轮询的方式清空发送缓冲区
+fn flush_sync(uart: &mut UartState) {
+    while let Some(c) = uart.tx.pop() {
+        putchar_sync(c);
     }
 }
 
 pub fn putchar(c: usize) {
-    unsafe {
-        while (UART_LSR as *const u8).read_volatile() & UART_LSR_EMPTY == 0 {}
-        (UART_DAT as *mut u8).write_volatile(c as u8);
+    let irq_on = local_irq_enabled();
+    UART.with(|uart| {
+        // 中断未打开(启动早期, 或在中断/异常处理流程中)时, 没有中断来搬运数据, 只能轮询
+        if !uart.irq_enabled || !irq_on {
+            flush_sync(uart);
+            putchar_sync(c as u8);
+            return;
+        }
+        // 缓冲区满时先轮询腾出一个 FIFO 的空间
+        while uart.tx.is_full() {
+            while read_reg(UART_LSR) & UART_LSR_TFE == 0 {}
+            fill_tx_fifo(uart);
+        }
+        uart.tx.push(c as u8);
+        fill_tx_fifo(uart);
+        if !uart.tx.is_empty() {
+            write_reg(UART_IER, UART_IER_RDI | UART_IER_THRI);
+        }
+    });
+}
+
+// 从接收缓冲区中取出一个字节, 没有数据时返回 None
+pub fn getchar() -> Option<u8> {
+    UART.with(|uart| uart.rx.pop())
+}
+
+// panic 等场景下, 确保缓冲区中的输出全部送出
+pub fn flush() {
+    UART.with(flush_sync);
+}
+
+fn handle_irq(_irq: usize) {
+    // 中断处理期间 S 模式中断已关闭, 可直接访问缓冲区
+    let uart = unsafe { &mut *UART.0.get() };
+    loop {
+        let iir = read_reg(UART_IIR);
+        if iir & UART_IIR_NO_INT != 0 {
+            break;
+        }
+        match iir & UART_IIR_ID {
+            UART_IIR_RDI | UART_IIR_RX_TIMEOUT => {
+                while read_reg(UART_LSR) & UART_LSR_DR != 0 {
+                    // 接收缓冲区满时丢弃新数据
+                    uart.rx.push(read_reg(UART_DAT));
+                }
+            }
+            UART_IIR_THRI => {
+                fill_tx_fifo(uart);
+                if uart.tx.is_empty() {
+                    write_reg(UART_IER, UART_IER_RDI);
+                }
+            }
+            UART_IIR_RLSI => {
+                // 读 LSR 即可清除线路状态中断
+                read_reg(UART_LSR);
+            }
+            _ => break,
+        }
     }
 }
@@ -0,0 +1,223 @@
+//! 查看编译出的 ELF: 符号表、段大小与地址符号化
+//!
+//! 依赖 cargo-binutils 提供的 rust-nm/rust-size/rust-objdump, 以及主机上的 addr2line
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub addr: usize,
+    pub size: usize,
+    pub name: String,
+}
+
+impl Symbol {
+    // nm 没有给出大小时, 以下一个符号的起始地址作为结束地址
+    pub fn end(&self, symbols: &[Symbol]) -> usize {
+        if self.size != 0 {
+            return self.addr + self.size;
+        }
+        symbols
+            .iter()
+            .map(|sym| sym.addr)
+            .find(|&addr| addr > self.addr)
+            .unwrap_or(self.addr + 4)
+    }
+
+    // name 可以是完整路径, 也可以只是末尾的几段, 如 rust_main 或 trap::trap_handler
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.name.ends_with(&format!("::{}", name))
+    }
+}
+
+fn run(cmd: &mut Command) -> String {
+    let output = cmd
+        .output()
+        .unwrap_or_else(|err| panic!("failed to run {:?}: {}", cmd, err));
+    if !output.status.success() {
+        panic!(
+            "{:?} failed: {}",
+            cmd,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// 按地址升序返回 ELF 中的函数符号, 与 os/scripts/gen-ksyms.sh 一样去掉 rust 符号末尾的哈希
+pub fn symbols(elf: &Path) -> Vec<Symbol> {
+    let output = run(Command::new("rust-nm")
+        .args(["--defined-only", "-n", "-C", "-S"])
+        .arg(elf));
+    parse_nm(&output)
+}
+
+// 每行格式为 "地址 [大小] 类型 符号名", 没有大小的符号只有三列
+pub fn parse_nm(output: &str) -> Vec<Symbol> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.splitn(4, ' ').collect();
+            let (addr, size, kind, name) = match fields[..] {
+                // 第二列是一个字符时为类型, 否则是 16 位十六进制的大小
+                [addr, kind, ..] if kind.len() == 1 => {
+                    (addr, "0", kind, line.splitn(3, ' ').nth(2)?)
+                }
+                [addr, size, kind, name] if kind.len() == 1 => (addr, size, kind, name),
+                _ => return None,
+            };
+            if !matches!(kind, "t" | "T" | "w" | "W") {
+                return None;
+            }
+            let name = match name.rsplit_once("::h") {
+                Some((base, hash)) if hash.chars().all(|c| c.is_ascii_hexdigit()) => base,
+                _ => name,
+            };
+            Some(Symbol {
+                addr: usize::from_str_radix(addr, 16).ok()?,
+                size: usize::from_str_radix(size, 16).ok()?,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+// 返回包含 addr 的符号以及 addr 在符号内的偏移
+pub fn symbol_at(symbols: &[Symbol], addr: usize) -> Option<(&Symbol, usize)> {
+    let sym = symbols.iter().rev().find(|sym| sym.addr <= addr)?;
+    if addr >= sym.end(symbols) {
+        return None;
+    }
+    Some((sym, addr - sym.addr))
+}
+
+// 用 addr2line 查询地址对应的函数与源码位置, 内联的函数各占一项, 最内层在前
+// 主机上没有 addr2line 或者 ELF 中没有调试信息时返回空
+pub fn addr2line(elf: &Path, addr: usize) -> Vec<(String, String)> {
+    let output = Command::new("addr2line")
+        .args(["-f", "-i", "-C", "-e"])
+        .arg(elf)
+        .arg(format!("{:#x}", addr))
+        .output();
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    lines
+        .chunks(2)
+        // 没有行号信息的汇编代码会查到 "$x..." 之类的映射符号与 "??:?"
+        .filter(|frame| frame.len() == 2 && !frame[1].ends_with(":?") && !frame[1].ends_with(":0"))
+        .map(|frame| (frame[0].to_string(), frame[1].to_string()))
+        .collect()
+}
+
+// 段与 LOAD 段的大小, 键如 ".text" 或 "LOAD0 r-x"
+pub type SizeReport = BTreeMap<String, usize>;
+
+pub fn size_report(elf: &Path) -> SizeReport {
+    let sections = run(Command::new("rust-size").arg("-A").arg(elf));
+    let headers = run(Command::new("rust-objdump").arg("-p").arg(elf));
+    let mut report = parse_size(&sections);
+    report.extend(parse_program_headers(&headers));
+    report
+}
+
+// rust-size -A 的输出: "section size addr", 只保留会被加载的段(地址不为 0)
+pub fn parse_size(output: &str) -> SizeReport {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let size = fields.next()?.parse().ok()?;
+            let addr: usize = fields.next()?.parse().ok()?;
+            (name.starts_with('.') && addr != 0).then(|| (name.to_string(), size))
+        })
+        .collect()
+}
+
+// rust-objdump -p 中每个段占两行:
+//     LOAD off    0x... vaddr 0x80200000 paddr 0x... align 2**12
+//          filesz 0x... memsz 0x5ad58 flags r-x
+pub fn parse_program_headers(output: &str) -> SizeReport {
+    let lines: Vec<&str> = output.lines().collect();
+    lines
+        .windows(2)
+        .filter_map(|pair| {
+            let head: Vec<&str> = pair[0].split_whitespace().collect();
+            let tail: Vec<&str> = pair[1].split_whitespace().collect();
+            if head.first() != Some(&"LOAD") {
+                return None;
+            }
+            let memsz = field(&tail, "memsz")?;
+            let memsz = usize::from_str_radix(memsz.trim_start_matches("0x"), 16).ok()?;
+            Some((field(&tail, "flags")?, memsz))
+        })
+        // 段的地址会随内核大小变化, 因此以段的序号区分
+        .enumerate()
+        .map(|(i, (flags, memsz))| (format!("LOAD{} {}", i, flags), memsz))
+        .collect()
+}
+
+// 取 "key value" 形式的字段中 key 后面的值
+fn field<'a>(fields: &[&'a str], key: &str) -> Option<&'a str> {
+    let pos = fields.iter().position(|field| *field == key)?;
+    fields.get(pos + 1).copied()
+}
+
+// 上一次的大小报告保存在 ELF 旁边的 <elf>.size 中, 每行 "大小 名称"
+pub fn load_report(path: &Path) -> Option<SizeReport> {
+    let content = fs::read_to_string(path).ok()?;
+    Some(
+        content
+            .lines()
+            .filter_map(|line| {
+                let (size, name) = line.split_once(' ')?;
+                Some((name.to_string(), size.parse().ok()?))
+            })
+            .collect(),
+    )
+}
+
+pub fn save_report(path: &Path, report: &SizeReport) {
+    let content: String = report
+        .iter()
+        .map(|(name, size)| format!("{} {}\n", size, name))
+        .collect();
+    fs::write(path, content).unwrap();
+}
+
+pub fn print_report(report: &SizeReport, previous: Option<&SizeReport>) {
+    println!(
+        "{:<28} {:>10} {:>10} {:>10}",
+        "name", "size", "previous", "delta"
+    );
+    let names = report
+        .keys()
+        .chain(previous.into_iter().flat_map(|prev| prev.keys()))
+        .collect::<std::collections::BTreeSet<_>>();
+    for name in names {
+        let size = report.get(name).copied();
+        let prev = previous.and_then(|prev| prev.get(name).copied());
+        let show = |size: Option<usize>| size.map_or("-".to_string(), |size| size.to_string());
+        let delta = match (size, prev) {
+            (Some(size), Some(prev)) if size != prev => {
+                format!("{:+}", size as isize - prev as isize)
+            }
+            (Some(_), None) if previous.is_some() => "new".to_string(),
+            (None, Some(_)) => "removed".to_string(),
+            _ => String::new(),
+        };
+        println!(
+            "{:<28} {:>10} {:>10} {:>10}",
+            name,
+            show(size),
+            show(prev),
+            delta
+        );
+    }
+}
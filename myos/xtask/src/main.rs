@@ -1,7 +1,17 @@
 use clap::Parser;
 use log::{debug, error, info, warn};
 use os_xtask_utils::{BinUtil, Cargo, CommandExt, Qemu};
+use rcore::{AppsOpts, KernelOpts, RunOpts};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use test::{Expectation, TestReport};
+
+mod inspect;
+mod profile;
+mod rcore;
+mod test;
 
 //对该复合类型使用clap::Parser派生宏
 #[derive(Debug, Parser)]
@@ -18,10 +28,28 @@ enum SubCommand {
     //对子命令成员解释,如果不显式起名,则默认为该成员名的小写
     #[command(name = "asm", about = "asm args about")]
     Asm(AsmOpts),
+    #[command(
+        name = "addr2line",
+        about = "symbolize addresses, e.g. from a kernel backtrace"
+    )]
+    Addr2line(Addr2lineOpts),
     #[command(name = "qemu", about = "qemu args about")]
     Qemu(QemuOpts),
     #[command(name = "make", about = "build args about")]
     Make(BuildOpts),
+    #[command(name = "test", about = "run #[kernel_test] or user apps in qemu")]
+    Test(TestOpts),
+    #[command(name = "apps", about = "build the user apps")]
+    Apps(AppsOpts),
+    #[command(name = "kernel", about = "build the user apps and the os kernel")]
+    Kernel(KernelOpts),
+    #[command(name = "run", about = "build the os kernel and run it in qemu")]
+    Run(RunOpts),
+    #[command(
+        name = "profile",
+        about = "symbolize the samples of the os profile feature"
+    )]
+    Profile(ProfileOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -30,6 +58,30 @@ struct AsmOpts {
     make: BuildOpts,
     #[arg(short, long, default_value_t = false)]
     verbose: bool,
+    /// Only disassemble this function (full path or trailing segments, e.g. trap::trap_handler)
+    #[arg(long)]
+    symbol: Option<String>,
+    /// Only disassemble the function containing this address
+    #[arg(long)]
+    addr: Option<String>,
+    /// Interleave source code with the disassembly
+    #[arg(short = 'S', long, default_value_t = false)]
+    source: bool,
+    /// Print section and segment sizes, compared with the previous build
+    #[arg(long, default_value_t = false)]
+    size: bool,
+}
+
+#[derive(Debug, Parser)]
+struct Addr2lineOpts {
+    #[clap(flatten)]
+    make: BuildOpts,
+    /// Use this ELF instead of building --bin
+    #[arg(long)]
+    elf: Option<PathBuf>,
+    /// Addresses to symbolize, e.g. the ra values of a backtrace
+    #[arg(required = true)]
+    addrs: Vec<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -42,6 +94,38 @@ struct QemuOpts {
     gdb: Option<u16>,
     #[arg(short, long, default_value_t = false)]
     run: bool,
+    /// Number of harts
+    #[arg(long, default_value_t = 1)]
+    smp: usize,
+}
+
+#[derive(Debug, Parser)]
+struct TestOpts {
+    #[clap(flatten)]
+    make: BuildOpts,
+    /// Seconds to wait before killing qemu
+    #[arg(long, default_value_t = 60)]
+    timeout: u64,
+    /// Run the user apps on the os kernel and check their output against user/expected
+    #[arg(long, default_value_t = false)]
+    apps: bool,
+}
+
+#[derive(Debug, Parser)]
+struct ProfileOpts {
+    /// Serial output of a kernel built with --features profile
+    log: PathBuf,
+    /// The kernel and the apps were built in release mode
+    #[arg(long, default_value_t = false)]
+    release: bool,
+    #[arg(short, long, default_value = "riscv64gc-unknown-none-elf")]
+    target: String,
+    /// Print folded stacks for flamegraph.pl instead of a flat profile
+    #[arg(long, default_value_t = false)]
+    folded: bool,
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
@@ -57,6 +141,9 @@ struct BuildOpts {
     release: bool,
     #[arg(short, long, default_value = "riscv64gc-unknown-none-elf")]
     target: String,
+    /// Kernel entry address that mysbi jumps to
+    #[arg(long)]
+    jump_addr: Option<String>,
 }
 
 fn main() {
@@ -68,6 +155,16 @@ fn main() {
             let _ = build_opts.run();
         }
         Asm(asm_opts) => asm_opts.run(),
+        Addr2line(addr2line_opts) => addr2line_opts.run(),
+        Test(test_opts) => test_opts.run(),
+        Apps(apps_opts) => {
+            let _ = apps_opts.build();
+        }
+        Kernel(kernel_opts) => {
+            let _ = kernel_opts.build();
+        }
+        Run(run_opts) => run_opts.run(),
+        Profile(profile_opts) => profile_opts.run(),
     }
 }
 
@@ -109,11 +206,19 @@ impl QemuOpts {
         let qemu = binding
             .args(["-machine", "virt"])
             .args(["-m", "128M"])
+            .args(["-smp", self.smp.to_string().as_str()])
             .arg("-nographic")
             .arg("-bios")
             .arg(format!("{}", sbi.display()).as_str())
             .arg("-device")
-            .arg(format!("loader,file={},addr=0x80200000", os.display()).as_str())
+            .arg(
+                format!(
+                    "loader,file={},addr={}",
+                    os.display(),
+                    self.make.jump_addr.as_deref().unwrap_or("0x80200000")
+                )
+                .as_str(),
+            )
             .optional(&self.gdb, |qemu, gdb| {
                 if !self.run {
                     qemu.args(["-S", "-gdb", format!("tcp::{}", gdb).as_str()]);
@@ -127,20 +232,101 @@ impl QemuOpts {
 impl AsmOpts {
     fn run(&self) {
         let elf = self.make.run();
+        if self.size {
+            self.print_size(&elf);
+            return;
+        }
+        let filter = self.symbol.is_some() || self.addr.is_some();
+        if !filter {
+            self.objdump(&elf, None);
+            return;
+        }
+
+        let symbols = inspect::symbols(&elf);
+        let mut found: Vec<&inspect::Symbol> = symbols
+            .iter()
+            .filter(|sym| self.symbol.as_ref().is_some_and(|name| sym.matches(name)))
+            .collect();
+        if let Some(addr) = &self.addr {
+            match inspect::symbol_at(&symbols, rcore::parse_addr(addr)) {
+                Some((sym, _)) => found.push(sym),
+                None => warn!("no function contains {}", addr),
+            }
+        }
+        if found.is_empty() {
+            error!("no matching symbol in {}", elf.display());
+            std::process::exit(1);
+        }
+        for sym in found {
+            println!("{:#x} <{}>:", sym.addr, sym.name);
+            self.objdump(&elf, Some((sym.addr, sym.end(&symbols))));
+        }
+    }
+
+    fn objdump(&self, elf: &Path, range: Option<(usize, usize)>) {
         let mut binding = BinUtil::objdump();
         let bin_cmd = binding
             .arg(elf)
-            .arg(if self.verbose { "-d" } else { "-h" })
+            .arg(if self.verbose || range.is_some() {
+                "-d"
+            } else {
+                "-h"
+            })
+            .conditional(self.source, |binutil| {
+                binutil.args(["-S", "-C"]);
+            })
+            .optional(&range, |binutil, (start, stop)| {
+                binutil
+                    .arg("-C")
+                    .arg(format!("--start-address={:#x}", start))
+                    .arg(format!("--stop-address={:#x}", stop));
+            })
             .output()
             .stdout;
         let output = String::from_utf8(bin_cmd).unwrap();
         println!("{}", output);
     }
+
+    // 打印各段大小, 并与上一次 asm --size 保存的结果比较
+    fn print_size(&self, elf: &Path) {
+        let report = inspect::size_report(elf);
+        let saved = elf.with_extension("size");
+        let previous = inspect::load_report(&saved);
+        inspect::print_report(&report, previous.as_ref());
+        inspect::save_report(&saved, &report);
+    }
+}
+
+impl Addr2lineOpts {
+    // 0x80202d84: rust_main+0x10
+    //     rust_main at /path/to/os/src/main.rs:55
+    fn run(&self) {
+        let elf = match &self.elf {
+            Some(elf) => elf.clone(),
+            None => self.make.run(),
+        };
+        let symbols = inspect::symbols(&elf);
+        for addr in &self.addrs {
+            // 允许直接粘贴 backtrace 中的 "0x...," 片段
+            let addr = rcore::parse_addr(addr.trim_end_matches(','));
+            match inspect::symbol_at(&symbols, addr) {
+                Some((sym, offset)) => println!("{:#x}: {}+{:#x}", addr, sym.name, offset),
+                None => println!("{:#x}: ??", addr),
+            }
+            for (func, location) in inspect::addr2line(&elf, addr) {
+                println!("    {} at {}", func, location);
+            }
+        }
+    }
 }
 
 impl BuildOpts {
     fn run(&self) -> PathBuf {
         info!("build opt args {:?}", self);
+        // mysbi 在编译时通过 option_env! 读取内核入口地址
+        if let Some(addr) = &self.jump_addr {
+            std::env::set_var("MYSBI_JUMP_ADDR", addr);
+        }
         let mut path = PathBuf::new();
         let mut binding = Cargo::new("-C");
         let cargo_cmd = binding
@@ -155,10 +341,229 @@ impl BuildOpts {
         path.push(self.target.as_str());
         path.push(if self.release { "release" } else { "debug" });
         path.push(self.bin.as_str());
+        // os 内嵌了符号表, 与 rCore 内核一样, 符号表有变化时再编译一遍
+        if self.has_ksyms() {
+            let elf = path.canonicalize().unwrap();
+            if !rcore::gen_ksyms(Path::new(&self.bin), &elf) {
+                cargo_cmd.invoke();
+                rcore::check(
+                    rcore::gen_ksyms(Path::new(&self.bin), &elf),
+                    "kernel symbol table is not stable, building",
+                );
+            }
+        }
         info!("build success for {:?}", path);
         objcopy(&path, true);
         path
     }
+
+    // 编译测试内核, 返回 cargo 生成的可执行文件(文件名带有哈希)
+    fn build_test(&self) -> PathBuf {
+        info!("build test opt args {:?}", self);
+        let mut binding = Cargo::new("-C");
+        let cargo_cmd = binding
+            .arg(self.bin.as_str())
+            .args(["-Z", "unstable-options"])
+            .arg("test")
+            .args(["--no-run", "--message-format", "json"])
+            .package(self.bin.as_str())
+            .target(self.target.as_str());
+        info!("{:?}", cargo_cmd.info());
+        let mut build = || {
+            let output = cargo_cmd.output();
+            if !output.status.success() {
+                std::process::exit(output.status.code().unwrap_or(1));
+            }
+            // 每行一条 json 消息, 测试可执行文件出现在最后一条带 executable 的消息中
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            stdout
+                .lines()
+                .rev()
+                .find_map(|line| line.split_once("\"executable\":\"")?.1.split_once('"'))
+                .map(|(path, _)| PathBuf::from(path))
+                .expect("no test executable found")
+        };
+        let mut elf = build();
+        // 测试内核同样需要两遍编译生成符号表
+        if self.has_ksyms() && !rcore::gen_ksyms(Path::new(&self.bin), &elf) {
+            elf = build();
+            rcore::check(
+                rcore::gen_ksyms(Path::new(&self.bin), &elf),
+                "kernel symbol table is not stable, building",
+            );
+        }
+        info!("build test success for {:?}", elf);
+        elf
+    }
+
+    // 只有 os 内嵌了符号表(见 os/src/ksyms.rs), mysbi 没有
+    fn has_ksyms(&self) -> bool {
+        self.bin == "os"
+    }
+}
+
+impl TestOpts {
+    fn run(&self) {
+        info!("test opt args {:?}", self);
+        if self.apps {
+            self.run_apps()
+        } else {
+            self.run_kernel_tests()
+        }
+    }
+
+    // 编译测试内核, 在 qemu 中运行并解析 ktest 的输出
+    // 有测试失败、内核没有输出测试结果或者超时时, 以非 0 状态退出
+    fn run_kernel_tests(&self) {
+        let mysbi = BuildOpts {
+            bin: "mysbi".to_string(),
+            log: None,
+            release: self.make.release,
+            target: self.make.target.clone(),
+            jump_addr: self.make.jump_addr.clone(),
+        };
+        let sbi = mysbi.run().with_extension("bin");
+        let kernel = objcopy(self.make.build_test(), true);
+
+        let mut qemu = Command::new("qemu-system-riscv64");
+        qemu.args(["-machine", "virt", "-m", "128M", "-nographic"])
+            .arg("-bios")
+            .arg(&sbi)
+            .arg("-device")
+            .arg(format!(
+                "loader,file={},addr={}",
+                kernel.display(),
+                self.make.jump_addr.as_deref().unwrap_or("0x80200000")
+            ));
+        debug!("QEMU CMD: {:?}", qemu);
+        let output = test::run_captured(&mut qemu, Duration::from_secs(self.timeout));
+        let report = TestReport::parse(&output.lines);
+
+        println!();
+        println!(
+            "kernel tests: {} passed, {} failed",
+            report.passed.len(),
+            report.failed.len()
+        );
+        report
+            .failed
+            .iter()
+            .for_each(|name| println!("    FAILED {}", name));
+        let ok = if output.timed_out {
+            error!("qemu timed out after {}s", self.timeout);
+            false
+        } else if !report.finished {
+            error!("kernel exited without a test result ({})", output.status);
+            false
+        } else if !output.status.success() {
+            error!("qemu exited with {}", output.status);
+            false
+        } else {
+            report.failed.is_empty()
+        };
+        if !ok {
+            std::process::exit(1);
+        }
+    }
+
+    // 编译 user 中的应用与 os 内核, 在 qemu 中运行全部应用, 按 user/expected 检查各应用的输出
+    // os 内核在所有应用结束后总是以失败状态关机, 因此不检查 qemu 的退出状态, 而是依据退出汇总
+    fn run_apps(&self) {
+        let root = rcore::repo_root();
+        // 应用由 os 的 build.rs 打包进内核
+        let kernel = KernelOpts {
+            apps: AppsOpts {
+                release: self.make.release,
+                target: self.make.target.clone(),
+                app_base: None,
+                app_step: "0x20000".to_string(),
+            },
+            log: None,
+            features: None,
+        }
+        .build();
+
+        let mut qemu = Command::new("qemu-system-riscv64");
+        qemu.args(["-machine", "virt", "-nographic"])
+            .arg("-bios")
+            .arg(root.join("bootloader/rustsbi-qemu.bin"))
+            .arg("-device")
+            .arg(format!("loader,file={},addr=0x80200000", kernel.display()));
+        debug!("QEMU CMD: {:?}", qemu);
+        let output = test::run_captured(&mut qemu, Duration::from_secs(self.timeout));
+        // 保存完整的串口输出, 便于失败后查看
+        let log = root.join("os/target/test-apps.log");
+        fs::write(&log, output.lines.join("\n")).unwrap();
+
+        let expectations = Expectation::load_dir(&root.join("user/expected"));
+        let exits = test::parse_exit_summary(&output.lines);
+        println!();
+        let mut failed = 0;
+        for expectation in &expectations {
+            let result = match &exits {
+                Some(exits) => expectation.check(&output.lines, exits),
+                None => Err("kernel did not finish all apps".to_string()),
+            };
+            match result {
+                Ok(()) => println!("    ok     {}", expectation.app),
+                Err(reason) => {
+                    failed += 1;
+                    println!("    FAILED {}: {}", expectation.app, reason);
+                }
+            }
+        }
+        println!(
+            "app tests: {} passed, {} failed, serial log saved to {}",
+            expectations.len() - failed,
+            failed,
+            log.display()
+        );
+        if output.timed_out {
+            error!("qemu timed out after {}s", self.timeout);
+        }
+        if output.timed_out || failed > 0 {
+            std::process::exit(1);
+        }
+    }
+}
+
+impl ProfileOpts {
+    fn run(&self) {
+        info!("profile opt args {:?}", self);
+        let content = fs::read_to_string(&self.log)
+            .unwrap_or_else(|err| panic!("failed to read {}: {}", self.log.display(), err));
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let Some(samples) = profile::parse_samples(&lines) else {
+            error!(
+                "no samples in {}, is the kernel built with --features profile?",
+                self.log.display()
+            );
+            std::process::exit(1);
+        };
+        if samples.is_empty() {
+            warn!("the kernel recorded no samples");
+            return;
+        }
+
+        let root = rcore::repo_root();
+        let mode = if self.release { "release" } else { "debug" };
+        let mut symbolizer = profile::Symbolizer::new(
+            root.join("os/target")
+                .join(&self.target)
+                .join(mode)
+                .join("os"),
+            root.join("user/target").join(&self.target).join(mode),
+        );
+        let report = if self.folded {
+            profile::folded(&samples, &mut symbolizer)
+        } else {
+            profile::flat(&samples, &mut symbolizer)
+        };
+        match &self.output {
+            Some(output) => fs::write(output, report).unwrap(),
+            None => print!("{}", report),
+        }
+    }
 }
 
 fn objcopy(elf: impl AsRef<Path>, is_binary: bool) -> PathBuf {
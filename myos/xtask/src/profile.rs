@@ -0,0 +1,159 @@
+//! `cargo xtask profile`: 把 os 内核 profile feature 打印的样本转换为函数级的统计
+//!
+//! 样本格式见 os/src/profile.rs, 每个样本的 pc 用所属 ELF 的符号表与 addr2line 查询:
+//! 内核样本(`[kernel]`)用 os 内核的 ELF, 用户态样本用 user 中同名应用的 ELF
+//!
+//! - 平坦统计: 每个函数的样本数与占比, 按样本数降序排列
+//! - 折叠栈: 每行 `应用;外层函数;...;内层函数 样本数`, 可以直接交给 flamegraph.pl 或 inferno-flamegraph
+
+use crate::inspect::{self, Symbol};
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+const PREFIX: &str = "[profile] ";
+// 内核样本的应用名
+pub const KERNEL: &str = "[kernel]";
+
+#[derive(Debug, PartialEq)]
+pub struct Sample {
+    pub pc: usize,
+    pub app: String,
+}
+
+// 从串口输出中取出全部样本, pid/tid 不参与统计
+// 输出中没有样本表头时返回 None, 说明内核没有打开 profile feature 或者没有运行结束
+pub fn parse_samples(lines: &[String]) -> Option<Vec<Sample>> {
+    let start = lines
+        .iter()
+        .position(|line| line.contains("[profile] ----------------"))?;
+    let samples = lines[start + 1..]
+        .iter()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_once(PREFIX)?.1.split_whitespace().collect();
+            match fields[..] {
+                [pc, _pid, _tid, app] => Some(Sample {
+                    pc: usize::from_str_radix(pc.strip_prefix("0x")?, 16).ok()?,
+                    app: app.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    Some(samples)
+}
+
+// 一个 ELF 的符号表, 按需加载
+struct Image {
+    elf: PathBuf,
+    symbols: Vec<Symbol>,
+}
+
+pub struct Symbolizer {
+    kernel: PathBuf,
+    apps: PathBuf,
+    images: BTreeMap<String, Option<Image>>,
+}
+
+impl Symbolizer {
+    // kernel 为 os 内核的 ELF, apps 为应用 ELF 所在的目录
+    pub fn new(kernel: PathBuf, apps: PathBuf) -> Self {
+        Self {
+            kernel,
+            apps,
+            images: BTreeMap::new(),
+        }
+    }
+
+    fn image(&mut self, app: &str) -> Option<&Image> {
+        if !self.images.contains_key(app) {
+            let elf = match app {
+                KERNEL => self.kernel.clone(),
+                _ => self.apps.join(app),
+            };
+            let image = if elf.exists() {
+                Some(Image {
+                    symbols: inspect::symbols(&elf),
+                    elf,
+                })
+            } else {
+                warn!(
+                    "{} not found, samples of {} are not symbolized",
+                    elf.display(),
+                    app
+                );
+                None
+            };
+            self.images.insert(app.to_string(), image);
+        }
+        self.images[app].as_ref()
+    }
+
+    // pc 所在的函数, 找不到时返回 "??"
+    pub fn function(&mut self, app: &str, pc: usize) -> String {
+        self.image(app)
+            .and_then(|image| inspect::symbol_at(&image.symbols, pc))
+            .map_or_else(|| "??".to_string(), |(sym, _)| sym.name.clone())
+    }
+
+    // pc 处的调用链(包括内联的函数), 最外层在前
+    pub fn frames(&mut self, app: &str, pc: usize) -> Vec<String> {
+        let Some(image) = self.image(app) else {
+            return vec!["??".to_string()];
+        };
+        let mut frames: Vec<String> = inspect::addr2line(&image.elf, pc)
+            .into_iter()
+            .map(|(func, _)| func)
+            .collect();
+        if frames.is_empty() {
+            frames.push(self.function(app, pc));
+        }
+        frames.reverse();
+        frames
+    }
+}
+
+// 相同 (应用, pc) 的样本只查询一次
+fn count_pcs(samples: &[Sample]) -> BTreeMap<(&str, usize), usize> {
+    let mut counts = BTreeMap::new();
+    for sample in samples {
+        *counts.entry((sample.app.as_str(), sample.pc)).or_default() += 1;
+    }
+    counts
+}
+
+pub fn flat(samples: &[Sample], symbolizer: &mut Symbolizer) -> String {
+    let mut functions: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for ((app, pc), count) in count_pcs(samples) {
+        let function = symbolizer.function(app, pc);
+        *functions.entry((app.to_string(), function)).or_default() += count;
+    }
+    let mut functions: Vec<_> = functions.into_iter().collect();
+    functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{:>8} {:>7}  {:<20} function", "samples", "%", "app");
+    for ((app, function), count) in functions {
+        let percent = count as f64 * 100.0 / samples.len() as f64;
+        let _ = writeln!(
+            out,
+            "{:>8} {:>6.2}%  {:<20} {}",
+            count, percent, app, function
+        );
+    }
+    out
+}
+
+pub fn folded(samples: &[Sample], symbolizer: &mut Symbolizer) -> String {
+    let mut stacks: BTreeMap<String, usize> = BTreeMap::new();
+    for ((app, pc), count) in count_pcs(samples) {
+        let mut stack = vec![app.to_string()];
+        stack.extend(symbolizer.frames(app, pc));
+        *stacks.entry(stack.join(";")).or_default() += count;
+    }
+    stacks
+        .into_iter()
+        .map(|(stack, count)| format!("{} {}\n", stack, count))
+        .collect()
+}
@@ -0,0 +1,289 @@
+//! 编译并运行仓库根目录下的 os 内核与 user 应用
+//!
+//! 取代原先的 user/Makefile 与 user/build.py:
+//! 1. 编译 user 中的全部应用, 可以为每个应用指定不同的链接地址
+//! 2. 通过 APP_TARGET_PATH 告诉 os 的 build.rs 应用所在的目录, 编译内核并生成内嵌的符号表
+//! 3. 用 bootloader 中的 rustsbi-qemu.bin 或 myos 中的 mysbi 启动 qemu
+
+use crate::{objcopy, BuildOpts};
+use clap::{Parser, ValueEnum};
+use log::{debug, error, info};
+use os_xtask_utils::{Cargo, CommandExt, Qemu};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// os 内核的入口地址, 与 os/src/linker-qemu.ld 一致
+const KERNEL_ENTRY_PA: &str = "0x80200000";
+
+#[derive(Debug, Parser)]
+pub struct AppsOpts {
+    /// Builds in release mode
+    #[arg(long, default_value_t = false)]
+    pub release: bool,
+    #[arg(short, long, default_value = "riscv64gc-unknown-none-elf")]
+    pub target: String,
+    /// Link each app at its own address, starting from this one (e.g. 0x80400000)
+    #[arg(long)]
+    pub app_base: Option<String>,
+    /// Distance between the link addresses of adjacent apps
+    #[arg(long, default_value = "0x20000")]
+    pub app_step: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct KernelOpts {
+    #[clap(flatten)]
+    pub apps: AppsOpts,
+    /// Kernel log level
+    #[arg(long)]
+    pub log: Option<String>,
+    /// Extra cargo features of the kernel, e.g. gdbstub
+    #[arg(long)]
+    pub features: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sbi {
+    /// bootloader/rustsbi-qemu.bin
+    Rustsbi,
+    /// myos/mysbi
+    Mysbi,
+}
+
+#[derive(Debug, Parser)]
+pub struct RunOpts {
+    #[clap(flatten)]
+    pub kernel: KernelOpts,
+    /// Firmware to boot the kernel with
+    #[arg(long, value_enum, default_value_t = Sbi::Rustsbi)]
+    pub sbi: Sbi,
+    /// Number of harts
+    #[arg(long, default_value_t = 1)]
+    pub smp: usize,
+    /// Wait for gdb on this port before running
+    #[arg(long)]
+    pub gdb: Option<u16>,
+}
+
+// xtask 位于 myos/xtask, 仓库根目录在它的上两级
+pub fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .canonicalize()
+        .unwrap()
+}
+
+fn mode(release: bool) -> &'static str {
+    if release {
+        "release"
+    } else {
+        "debug"
+    }
+}
+
+pub fn parse_addr(addr: &str) -> usize {
+    let parsed = match addr.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => addr.parse(),
+    };
+    parsed.unwrap_or_else(|_| panic!("invalid address {}", addr))
+}
+
+// 用 os/scripts/gen-ksyms.sh 从内核 ELF 中提取符号表, 写入 kernel 目录下的 src/ksyms.txt
+// 符号表没有变化时返回 true, 否则需要重新编译内核
+pub fn gen_ksyms(kernel: &Path, elf: &Path) -> bool {
+    let status = Command::new("sh")
+        .current_dir(kernel)
+        .arg(repo_root().join("os/scripts/gen-ksyms.sh"))
+        .arg(elf)
+        .arg("src/ksyms.txt")
+        .status()
+        .expect("failed to run gen-ksyms.sh");
+    check(
+        status.code().is_some_and(|code| code <= 1),
+        "generating ksyms",
+    );
+    status.success()
+}
+
+pub fn check(ok: bool, what: &str) {
+    if !ok {
+        error!("{} failed", what);
+        std::process::exit(1);
+    }
+}
+
+impl AppsOpts {
+    // 编译全部应用, 返回应用 ELF 所在的目录
+    pub fn build(&self) -> PathBuf {
+        info!("apps opt args {:?}", self);
+        let user = repo_root().join("user");
+        let target_dir = user
+            .join("target")
+            .join(&self.target)
+            .join(mode(self.release));
+        let mut apps: Vec<String> = fs::read_dir(user.join("src/bin"))
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                path.file_stem().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        apps.sort();
+
+        match &self.app_base {
+            // 所有应用都使用 user/src/linker.ld 中的链接地址
+            None => self.cargo_build(&user, None),
+            Some(base) => {
+                let base = parse_addr(base);
+                let step = parse_addr(&self.app_step);
+                let linker = fs::read_to_string(user.join("src/linker.ld")).unwrap();
+                fs::create_dir_all(&target_dir).unwrap();
+                for (i, app) in apps.iter().enumerate() {
+                    let addr = base + step * i;
+                    // 在链接脚本的副本中修改 BASE_ADDRESS, 再单独编译这个应用
+                    let script = target_dir.join(format!("linker-{}.ld", app));
+                    let linker = linker
+                        .lines()
+                        .map(|line| match line.trim_start().starts_with("BASE_ADDRESS") {
+                            true => format!("BASE_ADDRESS = {:#x};", addr),
+                            false => line.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    fs::write(&script, linker).unwrap();
+                    // RUSTFLAGS 会覆盖 user/.cargo/config.toml 中的 rustflags
+                    std::env::set_var(
+                        "RUSTFLAGS",
+                        format!(
+                            "-Clink-args=-T{} -Cforce-frame-pointers=yes",
+                            script.display()
+                        ),
+                    );
+                    self.cargo_build(&user, Some(app));
+                    info!("application {} start with address {:#x}", app, addr);
+                }
+                std::env::remove_var("RUSTFLAGS");
+            }
+        }
+
+        for app in &apps {
+            objcopy(target_dir.join(app), true);
+        }
+        target_dir
+    }
+
+    fn cargo_build(&self, user: &Path, bin: Option<&str>) {
+        let mut binding = Cargo::new("-C");
+        let cargo_cmd = binding
+            .arg(user)
+            .args(["-Z", "unstable-options"])
+            .arg("build")
+            .target(self.target.as_str())
+            .conditional(self.release, |cargo| {
+                cargo.arg("--release");
+            })
+            .optional(&bin, |cargo, bin| {
+                cargo.args(["--bin", bin]);
+            });
+        info!("{:?}", cargo_cmd.info());
+        check(
+            cargo_cmd.as_mut().status().unwrap().success(),
+            "building user apps",
+        );
+    }
+}
+
+impl KernelOpts {
+    // 先编译应用, 再编译内核, 返回内核的 .bin 文件
+    pub fn build(&self) -> PathBuf {
+        let apps = self.apps.build();
+        info!("kernel opt args {:?}", self);
+        let os = repo_root().join("os");
+        let elf = os
+            .join("target")
+            .join(&self.apps.target)
+            .join(mode(self.apps.release))
+            .join("os");
+        std::env::set_var("APP_TARGET_PATH", &apps);
+        if let Some(log) = &self.log {
+            std::env::set_var("LOG", log);
+        }
+
+        // 第一遍编译后提取符号表, 符号表有变化时再编译一遍, 与 os/Makefile 的 kernel 目标相同
+        self.cargo_build(&os);
+        if !gen_ksyms(&os, &elf) {
+            self.cargo_build(&os);
+            check(
+                gen_ksyms(&os, &elf),
+                "kernel symbol table is not stable, building",
+            );
+        }
+        info!("build success for {:?}", elf);
+        objcopy(&elf, true)
+    }
+
+    fn cargo_build(&self, os: &Path) {
+        let mut binding = Cargo::new("-C");
+        let cargo_cmd = binding
+            .arg(os)
+            .args(["-Z", "unstable-options"])
+            .arg("build")
+            .target(self.apps.target.as_str())
+            .conditional(self.apps.release, |cargo| {
+                cargo.arg("--release");
+            })
+            .optional(&self.features, |cargo, features| {
+                cargo.args(["--features", features]);
+            });
+        info!("{:?}", cargo_cmd.info());
+        check(
+            cargo_cmd.as_mut().status().unwrap().success(),
+            "building the kernel",
+        );
+    }
+}
+
+impl RunOpts {
+    pub fn run(&self) {
+        let kernel = self.kernel.build();
+        let sbi = self.sbi_bin();
+        let mut binding = Qemu::system("riscv64");
+        let qemu = binding
+            .args(["-machine", "virt"])
+            .arg("-nographic")
+            .args(["-smp", self.smp.to_string().as_str()])
+            .arg("-bios")
+            .arg(&sbi)
+            .arg("-device")
+            .arg(format!(
+                "loader,file={},addr={}",
+                kernel.display(),
+                KERNEL_ENTRY_PA
+            ))
+            .optional(&self.gdb, |qemu, gdb| {
+                qemu.args(["-S", "-gdb", format!("tcp::{}", gdb).as_str()]);
+            });
+        debug!("QEMU CMD: {:?}", qemu.info());
+        qemu.invoke();
+    }
+
+    pub fn sbi_bin(&self) -> PathBuf {
+        match self.sbi {
+            Sbi::Rustsbi => repo_root().join("bootloader/rustsbi-qemu.bin"),
+            Sbi::Mysbi => {
+                let mysbi = BuildOpts {
+                    bin: "mysbi".to_string(),
+                    log: None,
+                    release: false,
+                    target: self.kernel.apps.target.clone(),
+                    jump_addr: Some(KERNEL_ENTRY_PA.to_string()),
+                };
+                repo_root()
+                    .join("myos")
+                    .join(mysbi.run().with_extension("bin"))
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! `cargo xtask test` 用到的 qemu 输出捕获与结果检查
+//!
+//! - 内核自测: 解析 ktest 输出的 `test <name> ... ok|FAILED` 与 `test result:`
+//! - 应用测试: 按 `user/expected/<app>.txt` 检查 os 内核运行全部应用后的串口输出
+
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+pub struct QemuOutput {
+    pub lines: Vec<String>,
+    pub timed_out: bool,
+    pub status: ExitStatus,
+}
+
+// 运行 qemu 并逐行捕获串口输出, 同时回显到终端; 超时后杀死 qemu
+pub fn run_captured(qemu: &mut Command, timeout: Duration) -> QemuOutput {
+    let mut child = qemu
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start qemu");
+
+    // 在另一个线程中读取串口输出, 以便主线程处理超时
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut lines = Vec::new();
+    let mut timed_out = false;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok(line) => {
+                println!("{}", line);
+                lines.push(line);
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                timed_out = true;
+                let _ = child.kill();
+                break;
+            }
+        }
+    }
+    let status = child.wait().expect("failed to wait qemu");
+    QemuOutput {
+        lines,
+        timed_out,
+        status,
+    }
+}
+
+// ktest 的输出格式:
+// test <name> ... ok / FAILED
+// test result: ok|FAILED. <n> passed; <m> failed
+#[derive(Default)]
+pub struct TestReport {
+    pub passed: Vec<String>,
+    pub failed: Vec<String>,
+    pub finished: bool,
+}
+
+impl TestReport {
+    pub fn parse(lines: &[String]) -> Self {
+        let mut report = Self::default();
+        lines.iter().for_each(|line| report.parse_line(line));
+        report
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.starts_with("test result:") {
+            self.finished = true;
+        } else if let Some((name, status)) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.split_once(" ... "))
+        {
+            match status {
+                "ok" => self.passed.push(name.to_string()),
+                "FAILED" => self.failed.push(name.to_string()),
+                _ => warn!("unknown test status: {}", line),
+            }
+        }
+    }
+}
+
+// 一个应用的期望输出, 文件格式:
+// - 以 # 开头的行与空行被忽略
+// - `exit: <原因>` 要求退出汇总中该应用的退出原因以 <原因> 开头, 如 `exit: exited(0)`
+// - 其余每一行都必须按顺序出现在串口输出中(子串匹配)
+// 多个应用并发运行, 输出会交错在一起, 因此只能检查各自的输出行是否按序出现
+pub struct Expectation {
+    pub app: String,
+    patterns: Vec<String>,
+    exit: Option<String>,
+}
+
+impl Expectation {
+    fn parse(app: &str, text: &str) -> Self {
+        let mut patterns = Vec::new();
+        let mut exit = None;
+        for line in text.lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix("exit:") {
+                Some(reason) => exit = Some(reason.trim().to_string()),
+                None => patterns.push(line.to_string()),
+            }
+        }
+        Self {
+            app: app.to_string(),
+            patterns,
+            exit,
+        }
+    }
+
+    // 读取目录下所有的 <app>.txt, 按应用名排序
+    pub fn load_dir(dir: &Path) -> Vec<Self> {
+        let mut expectations: Vec<_> = fs::read_dir(dir)
+            .unwrap_or_else(|err| panic!("failed to read {}: {}", dir.display(), err))
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .map(|path| {
+                let app = path.file_stem().unwrap().to_string_lossy().into_owned();
+                let text = fs::read_to_string(&path).unwrap();
+                Self::parse(&app, &text)
+            })
+            .collect();
+        expectations.sort_by(|a, b| a.app.cmp(&b.app));
+        expectations
+    }
+
+    // 检查输出是否满足期望, 返回第一个不满足的原因
+    pub fn check(&self, lines: &[String], exits: &BTreeMap<String, String>) -> Result<(), String> {
+        let mut rest = lines.iter();
+        for pattern in &self.patterns {
+            if !rest.any(|line| line.contains(pattern.as_str())) {
+                return Err(format!("missing output {:?}", pattern));
+            }
+        }
+        if let Some(expected) = &self.exit {
+            match exits.get(&self.app) {
+                Some(reason) if reason.starts_with(expected.as_str()) => {}
+                Some(reason) => {
+                    return Err(format!("exit reason {}, expected {}", reason, expected))
+                }
+                None => return Err("not found in the exit summary".to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+// 解析 os 内核在所有应用结束后打印的退出汇总, 返回应用名到退出原因的映射, 没有汇总时返回 None:
+// [kernel] ---------------- app exit summary ----------------
+// [kernel]  pid name                   user(us) kernel(us)  reason
+// [kernel]    0 00hello_world                12         34  exited(0)
+pub fn parse_exit_summary(lines: &[String]) -> Option<BTreeMap<String, String>> {
+    let start = lines
+        .iter()
+        .position(|line| line.contains("app exit summary"))?;
+    let exits = lines[start + 1..]
+        .iter()
+        .filter_map(|line| line.trim_end().strip_prefix("[kernel]"))
+        .filter_map(|row| {
+            let mut fields = row.split_whitespace();
+            fields.next()?.parse::<usize>().ok()?;
+            let name = fields.next()?;
+            // 跳过用户态与内核态时间
+            fields.nth(1)?;
+            let reason: Vec<_> = fields.collect();
+            Some((name.to_string(), reason.join(" ")))
+        })
+        .collect();
+    Some(exits)
+}
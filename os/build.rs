@@ -1,15 +1,36 @@
+use std::env;
 use std::fs::{read_dir, File};
 use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
 
-const APP_TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
+// 应用 ELF 所在的目录, xtask 会按编译模式通过环境变量 APP_TARGET_PATH 指定
+const DEFAULT_APP_TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
 
 fn main() {
+    let app_target_path = app_target_path();
     println!("cargo:rerun-if-changed=../user/src/");
-    println!("cargo:rerun-if-changed={}", APP_TARGET_PATH);
-    insert_app_data().unwrap();
+    println!("cargo:rerun-if-changed={}", app_target_path.display());
+    println!("cargo:rerun-if-env-changed=APP_TARGET_PATH");
+    insert_app_data(&app_target_path).unwrap();
+    ensure_ksyms().unwrap();
 }
 
-fn insert_app_data() -> Result<()> {
+fn app_target_path() -> PathBuf {
+    env::var_os("APP_TARGET_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_APP_TARGET_PATH))
+}
+
+// 内核符号表由 Makefile 在第一遍编译后生成, 第一次编译时先用一个空的符号表
+fn ensure_ksyms() -> Result<()> {
+    const KSYMS_PATH: &str = "src/ksyms.txt";
+    if !std::path::Path::new(KSYMS_PATH).exists() {
+        File::create(KSYMS_PATH)?;
+    }
+    Ok(())
+}
+
+fn insert_app_data(app_target_path: &Path) -> Result<()> {
     let mut f = File::create("src/link_app.S").unwrap();
 
     // 遍历app名称
@@ -43,19 +64,32 @@ _num_app:
     }
     writeln!(f, r#"    .quad app_{}_end"#, apps.len() - 1)?;
 
+    // 应用名称, 依次以 '\0' 结尾
+    writeln!(
+        f,
+        r#"
+    .global _app_names
+_app_names:"#
+    )?;
+    for app in apps.iter() {
+        writeln!(f, r#"    .string "{}""#, app)?;
+    }
+
     for (idx, app) in apps.iter().enumerate() {
         println!("app_{}: {}", idx, app);
-        // 注意这里 incbin 依赖app_xxx.bin 已生成
+        // 注意这里 incbin 依赖app的elf已生成, 内核需要解析elf来建立地址空间
         writeln!(
             f,
             r#"
     .section .data
     .global app_{0}_start
     .global app_{0}_end
+    .align 3
 app_{0}_start:
-    .incbin "{2}{1}.bin"
+    .incbin "{1}"
 app_{0}_end:"#,
-            idx, app, APP_TARGET_PATH
+            idx,
+            app_target_path.join(app).display()
         )?;
     }
 
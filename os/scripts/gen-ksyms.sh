@@ -0,0 +1,29 @@
+#!/bin/sh
+
+# 从内核 ELF 中提取函数符号, 生成内嵌到内核中的符号表 src/ksyms.txt
+# 每行格式为 "地址(十六进制) 符号名", 按地址升序排列
+# Argument1: 内核 ELF 文件
+# Argument2: 输出的符号表文件
+# 符号表没有变化时返回 0; 否则更新符号表并返回 1, 此时需要重新编译内核
+ELF=$1
+OUT=$2
+NM=${NM:-rust-nm}
+TMP=$OUT.tmp
+
+$NM --defined-only -n -C "$ELF" | awk '
+    $2 ~ /^[tTwW]$/ {
+        addr = $1
+        $1 = ""
+        $2 = ""
+        sub(/^ +/, "")
+        # 去掉 rust 符号末尾的哈希
+        sub(/::h[0-9a-f]+$/, "")
+        print addr, $0
+    }' > "$TMP" || exit 2
+
+if cmp -s "$TMP" "$OUT"; then
+    rm "$TMP"
+    exit 0
+fi
+mv "$TMP" "$OUT"
+exit 1
@@ -1,5 +1,22 @@
 //! Constants used in rCore for qemu
 
 pub const CLOCK_FREQ: usize = 12500000;
-pub const APP_BASE_ADDRESS: usize = 0x80400000;
-pub const APP_SIZE_LIMIT: usize = 0x20000;
+
+// QEMU virt 平台的 PLIC 与 16550 UART
+pub const VIRT_PLIC: usize = 0x0c00_0000;
+pub const VIRT_UART: usize = 0x1000_0000;
+// UART 在 PLIC 中的中断号
+pub const VIRT_UART_IRQ: usize = 10;
+// PCIe 的配置空间(ECAM)与 I/O 端口空间, 端口 n 位于 VIRT_PCIE_PIO + n
+pub const VIRT_PCIE_ECAM: usize = 0x3000_0000;
+pub const VIRT_PCIE_PIO: usize = 0x0300_0000;
+
+// 需要在内核地址空间中恒等映射的 MMIO 区域: (起始地址, 长度)
+pub const MMIO: &[(usize, usize)] = &[
+    (VIRT_PLIC, 0x40_0000), // PLIC
+    (VIRT_UART, 0x1000),    // UART
+    (0x1000_1000, 0x8000),  // VIRTIO
+    // 只映射 0 号总线的配置空间, 每个 function 占 4KiB
+    (VIRT_PCIE_ECAM, 0x10_0000), // PCIe ECAM
+    (VIRT_PCIE_PIO, 0x1_0000),   // PCIe I/O
+];
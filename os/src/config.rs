@@ -1,16 +1,14 @@
-pub use crate::board::APP_BASE_ADDRESS;
-pub use crate::board::APP_SIZE_LIMIT;
-pub use crate::board::CLOCK_FREQ;
+pub use crate::board::{CLOCK_FREQ, MMIO};
 
-pub const MAX_APP_NUM: usize = 9;
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+// 支持的最大 hart 数量, entry.asm 中按此数量预留启动栈, 更大的 hartid 会停在 entry.asm 中
+pub const MAX_HARTS: usize = 8;
+// 每个 hart 的启动栈大小
+pub const BOOT_STACK_SIZE: usize = 4096 * 16;
 
-// 页内偏移的位宽
-pub const PAGE_SIZE_BITS: usize = 12;
-// 每个页面的大小
-pub const PAGE_SIZE: usize = 0x1000; //4096, 4K
+pub use mm_core::PAGE_SIZE;
 
 // 之前是通过linker.ld的ekernel指明内核数据的终止物理地址
 // 起始物理地址则设定为 0x8000_0000
@@ -18,4 +16,6 @@ pub const PAGE_SIZE: usize = 0x1000; //4096, 4K
 pub const MEMORY_END: usize = 0x8080_0000;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+// 主线程(tid = 0)的 Trap 上下文所在的虚拟页
+// 其他线程的 Trap 上下文依次向下排布, 见 trap_cx_bottom_from_tid
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
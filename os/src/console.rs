@@ -1,4 +1,5 @@
-use crate::sbi::console_putchar;
+use crate::sbi::{console_getchar, console_putchar};
+use crate::sync::SpinNoIrq;
 use core::fmt::{self, Write};
 
 struct Stdout;
@@ -16,6 +17,13 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+// 原样输出字节, 供 sys_write 使用, 多字节的 UTF-8 字符可以跨越两次调用
+pub fn write_bytes(bytes: &[u8]) {
+    for &b in bytes {
+        console_putchar(b as usize);
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg:tt)+)?) => {
@@ -26,8 +34,54 @@ macro_rules! print {
 
 #[macro_export]
 macro_rules! println {
+    () => {
+        $crate::console::print(format_args!("\n"));
+    };
     ($fmt: literal $(, $($arg:tt)+)?) => {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
 
     };
 }
+
+// 标准输入的缓冲区容量, 写满后新收到的字符被丢弃
+const STDIN_BUF_SIZE: usize = 256;
+
+// 控制台串口的接收中断中读到的普通字符暂存在这里, 由 sys_read 取走
+struct StdinBuffer {
+    buf: [u8; STDIN_BUF_SIZE],
+    head: usize,
+    len: usize,
+}
+
+// 中断处理函数中会访问, 因此使用 SpinNoIrq
+static STDIN: SpinNoIrq<StdinBuffer> = SpinNoIrq::new(StdinBuffer {
+    buf: [0; STDIN_BUF_SIZE],
+    head: 0,
+    len: 0,
+});
+
+pub fn stdin_push(c: u8) {
+    let mut stdin = STDIN.lock();
+    if stdin.len == STDIN_BUF_SIZE {
+        return;
+    }
+    let tail = (stdin.head + stdin.len) % STDIN_BUF_SIZE;
+    stdin.buf[tail] = c;
+    stdin.len += 1;
+}
+
+// 先取缓冲区中的字符, 缓冲区为空时再轮询一次串口, 没有输入时返回 None
+pub fn stdin_pop() -> Option<u8> {
+    let mut stdin = STDIN.lock();
+    if stdin.len == 0 {
+        drop(stdin);
+        return match console_getchar() {
+            usize::MAX => None,
+            c => Some(c as u8),
+        };
+    }
+    let c = stdin.buf[stdin.head];
+    stdin.head = (stdin.head + 1) % STDIN_BUF_SIZE;
+    stdin.len -= 1;
+    Some(c)
+}
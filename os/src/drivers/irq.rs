@@ -0,0 +1,71 @@
+//! 外部中断的注册与分发
+//!
+//! 外设驱动通过 register_irq 为自己的中断号注册处理函数, 注册时会在 PLIC 中使能该中断
+//! 目前注册的只有控制台串口的接收中断
+//! 发生 SupervisorExternal 中断时, trap 处理流程调用 handle_external_irq:
+//! 从 PLIC 领取中断号, 调用对应的处理函数, 再通知 PLIC 处理完毕
+
+use super::plic::{IntrTargetPriority, MAX_IRQ};
+use super::PLIC_DEVICE;
+use crate::sync::UPSafeCell;
+use crate::task::hart_id;
+use lazy_static::*;
+use log::*;
+
+// 中断处理函数, 参数为中断号
+pub type IrqHandler = fn(usize);
+
+#[derive(Debug, PartialEq)]
+pub enum IrqError {
+    // 中断号超出 PLIC 的范围(0 号保留)
+    InvalidIrq,
+    // 该中断号已经注册过处理函数
+    AlreadyRegistered,
+}
+
+// 只有启动 hart 运行任务, 外部中断都路由到调用 init/register_irq 的 hart 的 S 态
+// 注册中断时使用的默认优先级
+const DEFAULT_PRIORITY: u32 = 1;
+
+lazy_static! {
+    static ref IRQ_HANDLERS: UPSafeCell<[Option<IrqHandler>; MAX_IRQ]> =
+        unsafe { UPSafeCell::new([None; MAX_IRQ]) };
+}
+
+// M 态的 context 由 SBI 管理, 内核只配置 S 态的 context
+pub fn init() {
+    // 阈值为 0, 即接收所有优先级不为 0 的中断
+    PLIC_DEVICE.set_threshold(hart_id(), IntrTargetPriority::Supervisor, 0);
+}
+
+pub fn register_irq(irq: usize, handler: IrqHandler) -> Result<(), IrqError> {
+    if irq == 0 || irq >= MAX_IRQ {
+        return Err(IrqError::InvalidIrq);
+    }
+    let mut handlers = IRQ_HANDLERS.exclusive_access();
+    if handlers[irq].is_some() {
+        return Err(IrqError::AlreadyRegistered);
+    }
+    handlers[irq] = Some(handler);
+    PLIC_DEVICE.set_priority(irq, DEFAULT_PRIORITY);
+    PLIC_DEVICE.enable(hart_id(), IntrTargetPriority::Supervisor, irq);
+    debug!("[kernel] register irq {}", irq);
+    Ok(())
+}
+
+// 处理所有待处理的外部中断
+pub fn handle_external_irq() {
+    loop {
+        let irq = PLIC_DEVICE.claim(hart_id(), IntrTargetPriority::Supervisor);
+        if irq == 0 {
+            break;
+        }
+        // 先释放借用再调用处理函数, 处理函数中可能会注册/注销中断
+        let handler = IRQ_HANDLERS.exclusive_access()[irq as usize];
+        match handler {
+            Some(handler) => handler(irq as usize),
+            None => warn!("[kernel] unhandled external irq {}", irq),
+        }
+        PLIC_DEVICE.complete(hart_id(), IntrTargetPriority::Supervisor, irq);
+    }
+}
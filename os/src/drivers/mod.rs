@@ -0,0 +1,39 @@
+//! 设备驱动
+//!
+//! PLIC 负责外部中断, 外设驱动通过 register_irq 注册自己的中断处理函数
+//! 控制台串口注册了接收中断, pci 与 uart 的轮询接口供 gdbstub 使用
+
+mod irq;
+#[cfg(feature = "gdbstub")]
+mod pci;
+mod plic;
+mod uart;
+
+pub use irq::{handle_external_irq, register_irq};
+#[cfg(feature = "gdbstub")]
+pub use pci::find_device;
+pub use uart::Uart16550;
+
+use crate::board::{VIRT_PLIC, VIRT_UART, VIRT_UART_IRQ};
+use lazy_static::*;
+use plic::PLIC;
+
+lazy_static! {
+    static ref PLIC_DEVICE: PLIC = unsafe { PLIC::new(VIRT_PLIC) };
+}
+
+// 控制台串口, 输出仍然经过 SBI, 这里只用它接收输入
+static CONSOLE_UART: Uart16550 = unsafe { Uart16550::new(VIRT_UART) };
+
+pub fn init() {
+    irq::init();
+    CONSOLE_UART.enable_rx_irq();
+    register_irq(VIRT_UART_IRQ, console_irq).expect("failed to register console irq");
+}
+
+// 读空接收缓冲, 交给 trap 模块处理 Ctrl-C 与标准输入
+fn console_irq(_irq: usize) {
+    while let Some(c) = CONSOLE_UART.getc() {
+        crate::trap::console_input(c);
+    }
+}
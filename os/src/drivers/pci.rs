@@ -0,0 +1,72 @@
+//! 最简单的 PCI 配置空间访问
+//!
+//! QEMU virt 平台通过 ECAM 暴露 PCIe 配置空间, 0 号总线上 function 的配置空间位于:
+//! VIRT_PCIE_ECAM + (device << 15 | function << 12)
+//!
+//! 固件不会为设备分配 BAR, 这里只支持给 I/O BAR 分配端口并打开 I/O 访问
+//! 目前只有 gdbstub 用到它来查找 pci-serial
+
+use crate::board::{VIRT_PCIE_ECAM, VIRT_PCIE_PIO};
+
+// 配置空间中的寄存器偏移
+const PCI_VENDOR_ID: usize = 0x00;
+const PCI_DEVICE_ID: usize = 0x02;
+const PCI_COMMAND: usize = 0x04;
+const PCI_BAR0: usize = 0x10;
+
+// COMMAND 寄存器: 允许 I/O 空间访问
+const PCI_COMMAND_IO: u16 = 1 << 0;
+// BAR 的最低位为 1 表示 I/O BAR
+const PCI_BAR_IO: u32 = 1 << 0;
+
+const MAX_DEVICE: usize = 32;
+const MAX_FUNCTION: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PciFunction {
+    device: usize,
+    function: usize,
+}
+
+impl PciFunction {
+    fn config_base(&self) -> usize {
+        VIRT_PCIE_ECAM + (self.device << 15 | self.function << 12)
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        unsafe { ((self.config_base() + offset) as *const u16).read_volatile() }
+    }
+
+    fn write_u16(&self, offset: usize, value: u16) {
+        unsafe { ((self.config_base() + offset) as *mut u16).write_volatile(value) }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        unsafe { ((self.config_base() + offset) as *const u32).read_volatile() }
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) {
+        unsafe { ((self.config_base() + offset) as *mut u32).write_volatile(value) }
+    }
+
+    // 把第 bar 个 I/O BAR 分配到端口 port 并打开 I/O 访问, 返回它在内核地址空间中的地址
+    // bar 不是 I/O BAR 时返回 None
+    pub fn map_io_bar(&self, bar: usize, port: u32) -> Option<usize> {
+        let offset = PCI_BAR0 + 4 * bar;
+        if self.read_u32(offset) & PCI_BAR_IO == 0 {
+            return None;
+        }
+        self.write_u32(offset, port);
+        self.write_u16(PCI_COMMAND, self.read_u16(PCI_COMMAND) | PCI_COMMAND_IO);
+        Some(VIRT_PCIE_PIO + port as usize)
+    }
+}
+
+// 在 0 号总线上查找指定的设备
+pub fn find_device(vendor_id: u16, device_id: u16) -> Option<PciFunction> {
+    (0..MAX_DEVICE)
+        .flat_map(|device| (0..MAX_FUNCTION).map(move |function| PciFunction { device, function }))
+        .find(|func| {
+            func.read_u16(PCI_VENDOR_ID) == vendor_id && func.read_u16(PCI_DEVICE_ID) == device_id
+        })
+}
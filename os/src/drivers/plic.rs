@@ -0,0 +1,116 @@
+//! RISC-V PLIC(Platform-Level Interrupt Controller) 驱动
+//!
+//! PLIC 将外设的中断路由到各个 hart 的各个特权级(称为 context)
+//! QEMU virt 平台上, hart i 的 M 态 context 为 2i, S 态 context 为 2i + 1
+//!
+//! 寄存器布局:
+//! - priority:        base + 4 * irq
+//! - enable:          base + 0x2000 + 0x80 * context + 4 * (irq / 32)
+//! - threshold:       base + 0x20_0000 + 0x1000 * context
+//! - claim/complete:  base + 0x20_0004 + 0x1000 * context
+
+// PLIC 支持的中断源数量, 0 号中断源保留不用
+pub const MAX_IRQ: usize = 128;
+// 中断优先级的范围为 0~7, 0 表示屏蔽
+pub const MAX_PRIORITY: u32 = 7;
+
+// M 态的 context 由 SBI 管理, 这里只列出内核使用的 S 态
+#[derive(Copy, Clone, Debug)]
+pub enum IntrTargetPriority {
+    Supervisor = 1,
+}
+
+impl IntrTargetPriority {
+    pub fn supported_number() -> usize {
+        2
+    }
+}
+
+pub struct PLIC {
+    base_addr: usize,
+}
+
+impl PLIC {
+    /// # Safety
+    /// base_addr 必须是 PLIC 的 MMIO 基址, 且已经在内核地址空间中映射
+    pub const unsafe fn new(base_addr: usize) -> Self {
+        Self { base_addr }
+    }
+
+    fn context_id(hart_id: usize, target_priority: IntrTargetPriority) -> usize {
+        hart_id * IntrTargetPriority::supported_number() + target_priority as usize
+    }
+
+    fn reg(&self, offset: usize) -> *mut u32 {
+        (self.base_addr + offset) as *mut u32
+    }
+
+    fn priority_ptr(&self, irq: usize) -> *mut u32 {
+        assert!(irq > 0 && irq < MAX_IRQ);
+        self.reg(4 * irq)
+    }
+
+    fn enable_ptr(
+        &self,
+        hart_id: usize,
+        target_priority: IntrTargetPriority,
+        irq: usize,
+    ) -> *mut u32 {
+        let context = Self::context_id(hart_id, target_priority);
+        self.reg(0x2000 + 0x80 * context + 4 * (irq / 32))
+    }
+
+    fn threshold_ptr(&self, hart_id: usize, target_priority: IntrTargetPriority) -> *mut u32 {
+        let context = Self::context_id(hart_id, target_priority);
+        self.reg(0x20_0000 + 0x1000 * context)
+    }
+
+    fn claim_complete_ptr(&self, hart_id: usize, target_priority: IntrTargetPriority) -> *mut u32 {
+        let context = Self::context_id(hart_id, target_priority);
+        self.reg(0x20_0004 + 0x1000 * context)
+    }
+
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        assert!(priority <= MAX_PRIORITY);
+        unsafe {
+            self.priority_ptr(irq).write_volatile(priority);
+        }
+    }
+
+    pub fn enable(&self, hart_id: usize, target_priority: IntrTargetPriority, irq: usize) {
+        let ptr = self.enable_ptr(hart_id, target_priority, irq);
+        unsafe {
+            ptr.write_volatile(ptr.read_volatile() | 1 << (irq % 32));
+        }
+    }
+
+    // 只有优先级严格大于阈值的中断才会被送达该 context
+    pub fn set_threshold(
+        &self,
+        hart_id: usize,
+        target_priority: IntrTargetPriority,
+        threshold: u32,
+    ) {
+        assert!(threshold <= MAX_PRIORITY);
+        unsafe {
+            self.threshold_ptr(hart_id, target_priority)
+                .write_volatile(threshold);
+        }
+    }
+
+    // 领取当前优先级最高的待处理中断, 返回 0 表示没有待处理的中断
+    pub fn claim(&self, hart_id: usize, target_priority: IntrTargetPriority) -> u32 {
+        unsafe {
+            self.claim_complete_ptr(hart_id, target_priority)
+                .read_volatile()
+        }
+    }
+
+    // 通知 PLIC 中断处理完毕, 之后该中断源才能再次触发
+    pub fn complete(&self, hart_id: usize, target_priority: IntrTargetPriority, irq: u32) {
+        unsafe {
+            self.claim_complete_ptr(hart_id, target_priority)
+                .write_volatile(irq);
+        }
+    }
+}
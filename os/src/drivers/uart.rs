@@ -0,0 +1,92 @@
+//! 16550 UART 驱动, 以轮询方式收发
+//!
+//! 控制台仍然通过 SBI 输出, 内核只打开控制台串口的接收中断, 在中断中读取输入
+//! gdbstub 等需要独立串口的模块完全以轮询方式使用
+//! 寄存器间隔为 1 字节, QEMU 的 virt 串口与 pci-serial 都是如此
+
+// 寄存器偏移
+const RBR_THR: usize = 0; // 接收缓冲 / 发送保持
+const IER: usize = 1; // 中断使能
+const LSR: usize = 5; // 线路状态
+
+// IER: 接收缓冲中有数据时产生中断
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+// LSR: 接收缓冲中有数据
+const LSR_DATA_READY: u8 = 1 << 0;
+
+// 初始化与发送只有 gdbstub 使用
+#[cfg(feature = "gdbstub")]
+mod polling {
+    pub const FCR: usize = 2; // FIFO 控制
+    pub const LCR: usize = 3; // 线路控制
+    pub const MCR: usize = 4; // Modem 控制
+
+    // LCR: 8 位数据位, 无校验, 1 位停止位
+    pub const LCR_8N1: u8 = 0b11;
+    // FCR: 使能并清空收发 FIFO
+    pub const FCR_ENABLE_CLEAR: u8 = 0b111;
+    // MCR: DTR | RTS
+    pub const MCR_DTR_RTS: u8 = 0b11;
+    // LSR: 发送保持寄存器为空
+    pub const LSR_THR_EMPTY: u8 = 1 << 5;
+}
+#[cfg(feature = "gdbstub")]
+use polling::*;
+
+pub struct Uart16550 {
+    base_addr: usize,
+}
+
+impl Uart16550 {
+    /// # Safety
+    /// base_addr 必须是 16550 的寄存器基址, 且已经在内核地址空间中映射
+    pub const unsafe fn new(base_addr: usize) -> Self {
+        Self { base_addr }
+    }
+
+    fn read(&self, reg: usize) -> u8 {
+        unsafe { ((self.base_addr + reg) as *const u8).read_volatile() }
+    }
+
+    fn write(&self, reg: usize, value: u8) {
+        unsafe { ((self.base_addr + reg) as *mut u8).write_volatile(value) }
+    }
+
+    // QEMU 不关心波特率, 因此不设置分频
+    #[cfg(feature = "gdbstub")]
+    pub fn init(&self) {
+        self.write(IER, 0);
+        self.write(LCR, LCR_8N1);
+        self.write(FCR, FCR_ENABLE_CLEAR);
+        self.write(MCR, MCR_DTR_RTS);
+    }
+
+    // 打开接收中断, 中断处理函数需要用 getc 读空接收缓冲
+    pub fn enable_rx_irq(&self) {
+        self.write(IER, IER_RX_AVAILABLE);
+    }
+
+    #[cfg(feature = "gdbstub")]
+    pub fn putc(&self, byte: u8) {
+        while self.read(LSR) & LSR_THR_EMPTY == 0 {}
+        self.write(RBR_THR, byte);
+    }
+
+    // 没有数据时立即返回 None
+    pub fn getc(&self) -> Option<u8> {
+        if self.read(LSR) & LSR_DATA_READY != 0 {
+            Some(self.read(RBR_THR))
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "gdbstub")]
+    pub fn getc_blocking(&self) -> u8 {
+        loop {
+            if let Some(byte) = self.getc() {
+                return byte;
+            }
+        }
+    }
+}
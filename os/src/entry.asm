@@ -1,8 +1,19 @@
 .section .text.entry
 .globl   _start
 
+# 所有 hart 都从这里进入内核: a0 = hartid, a1 = opaque
+# 启动 hart 的 opaque 由 SBI 传入, 从 hart 的 opaque 为 hart_start 时传入的内核页表 token
+# 每个 hart 使用各自的启动栈, 栈顶为 boot_stack_top - hartid * BOOT_STACK_SIZE
+# MAX_HARTS 与 BOOT_STACK_SIZE 来自 config, 由 global_asm! 代入
 _start:
+	# 超出 MAX_HARTS 的 hart 没有启动栈, 直接停住
+	li t0, {MAX_HARTS}
+	bgeu a0, t0, 2f
+	mv tp, a0
 	la sp, boot_stack_top
+	li t0, {BOOT_STACK_SIZE}
+	mul t0, t0, a0
+	sub sp, sp, t0
 
 #如果在kernel早期就触发异常的话,sscratch的值为0
 #与sp交换后,sp则为0,后续对sp操作基本不可能成功
@@ -11,11 +22,15 @@ _start:
 #csrw sscratch, sp
 call rust_main
 
+2:
+	wfi
+	j 2b
+
 .section .bss.stack
 .globl   boot_stack_lower_bound
 
 boot_stack_lower_bound:
-	.space 4096 * 16
+	.space {BOOT_STACK_SIZE} * {MAX_HARTS}
 	.globl boot_stack_top
 
 boot_stack_top:
@@ -0,0 +1,459 @@
+//! 内核中的 GDB 远程调试桩(gdbstub)
+//!
+//! QEMU 自带的 gdbstub 只能看到 CPU, 看不到内核中的线程
+//! 这里在内核中实现 GDB 远程串行协议, 通过第二个串口(QEMU 的 pci-serial 设备)与 GDB 通信:
+//! - 把所有线程列为 GDB 的线程, 可以查看/修改每个线程的寄存器, 见 target.rs
+//! - 内存通过所选线程的页表访问
+//! - 软件断点: 在内核代码中写入 ebreak, 由 kernel_trap_handler 交给这里处理
+//! - 不支持硬件单步, GDB 会用软件断点实现单步
+//!
+//! 内核只在时钟中断和断点处检查调试器, 因此 GDB 连接后需要等待下一次时钟中断才能暂停
+//! 暂停期间其余 hart 不会被暂停
+//!
+//! 需要启用 gdbstub feature 编译, 启用后内核代码段是可写的, 见 `make run GDBSTUB=1`
+
+mod packet;
+mod target;
+
+use crate::drivers::{find_device, Uart16550};
+use crate::mm::kernel_token;
+use crate::sync::UPSafeCell;
+use crate::trap::TrapContext;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lazy_static::*;
+use log::*;
+use packet::{
+    parse_hex, parse_hex_bytes, parse_hex_reg, push_hex_bytes, push_hex_reg, Connection, INTERRUPT,
+};
+use target::{write_memory_with, RegView, Target, NUM_REGS, PC};
+
+// QEMU pci-serial 的 PCI ID
+const PCI_VENDOR_REDHAT: u16 = 0x1b36;
+const PCI_DEVICE_SERIAL: u16 = 0x0002;
+// 分配给 pci-serial 的 I/O 端口
+const GDB_UART_PORT: u32 = 0x1000;
+
+// 暂停原因对应的信号
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// ebreak 与 c.ebreak 的编码, GDB 通过 Z0 的 kind 指定断点长度
+const EBREAK: u32 = 0x0010_0073;
+const C_EBREAK: u16 = 0x9002;
+
+const REG_NAMES: [&str; NUM_REGS] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6", "pc",
+];
+
+struct Breakpoint {
+    addr: usize,
+    // 被替换的原始指令
+    orig: Vec<u8>,
+}
+
+struct GdbStub {
+    conn: Connection,
+    // GDB 已发出继续执行的命令, 正在等待暂停报告
+    running: bool,
+    // Hg 选择的线程, 0 表示暂停时的当前线程
+    selected: usize,
+    view: RegView,
+    breakpoints: Vec<Breakpoint>,
+}
+
+// 处理完一条命令后的动作
+enum Action {
+    Reply(Vec<u8>),
+    // 继续执行, 不回复
+    Resume,
+}
+
+lazy_static! {
+    // 只有运行线程的 hart 会进入 gdbstub
+    // 断点设置在 gdbstub 自身时会再次进入, 此时借用失败, 按普通的内核异常处理
+    static ref GDBSTUB: UPSafeCell<Option<GdbStub>> = unsafe { UPSafeCell::new(None) };
+}
+
+pub fn init() {
+    let addr = find_device(PCI_VENDOR_REDHAT, PCI_DEVICE_SERIAL)
+        .and_then(|func| func.map_io_bar(0, GDB_UART_PORT));
+    match addr {
+        Some(addr) => {
+            let uart = unsafe { Uart16550::new(addr) };
+            *GDBSTUB.exclusive_access() = Some(GdbStub {
+                conn: Connection::new(uart),
+                running: false,
+                selected: 0,
+                view: RegView::Kernel,
+                breakpoints: Vec::new(),
+            });
+            info!("[gdbstub] listening on pci-serial at {:#x}", addr);
+        }
+        None => warn!("[gdbstub] pci-serial not found, gdbstub disabled"),
+    }
+}
+
+// 时钟中断时检查 GDB 是否发来了数据, 有则暂停并进入调试
+// frame 为在内核中暂停时的 Trap 上下文, 在用户态暂停时为 None
+pub fn poll(frame: Option<&mut TrapContext>) {
+    let mut stub = match GDBSTUB.try_exclusive_access() {
+        Some(stub) => stub,
+        None => return,
+    };
+    let stub = match stub.as_mut() {
+        Some(stub) => stub,
+        None => return,
+    };
+    match stub.conn.poll() {
+        // 暂停请求, 或者 GDB 刚连接上, 发来的第一个报文
+        Some(INTERRUPT) => stub.session(frame, SIGINT, None),
+        Some(b'$') => stub.session(frame, SIGINT, Some(b'$')),
+        _ => {}
+    }
+}
+
+// 处理内核中的 ebreak, gdbstub 未启用或正忙时返回 false
+pub fn handle_breakpoint(ctx: &mut TrapContext) -> bool {
+    let mut stub = match GDBSTUB.try_exclusive_access() {
+        Some(stub) => stub,
+        None => return false,
+    };
+    let stub = match stub.as_mut() {
+        Some(stub) => stub,
+        None => return false,
+    };
+    let pc = ctx.sepc;
+    let ours = stub.breakpoints.iter().any(|bp| bp.addr == pc);
+    stub.session(Some(ctx), SIGTRAP, None);
+    // 代码中原有的 ebreak 不是 GDB 插入的, 继续执行时跳过它, 否则会反复停在这里
+    if !ours && ctx.sepc == pc {
+        let insn = unsafe { (pc as *const u16).read() };
+        ctx.sepc += if insn & 0b11 == 0b11 { 4 } else { 2 };
+    }
+    true
+}
+
+impl GdbStub {
+    // 暂停执行, 处理 GDB 的命令直到它要求继续执行
+    fn session(&mut self, frame: Option<&mut TrapContext>, signal: u8, first: Option<u8>) {
+        let mut target = Target::new(frame);
+        self.selected = 0;
+        // 继续执行后的暂停需要主动报告, GDB 新连接时则由它通过 '?' 查询
+        if self.running && first.is_none() {
+            let reply = self.stop_reply(&target, signal);
+            self.conn.send(&reply);
+        }
+        self.running = false;
+        let mut first = first;
+        loop {
+            let packet = self.conn.recv(first.take());
+            match self.handle(&mut target, &packet, signal) {
+                Action::Reply(reply) => self.conn.send(&reply),
+                Action::Resume => break,
+            }
+        }
+    }
+
+    fn stop_reply(&self, target: &Target, signal: u8) -> Vec<u8> {
+        let mut reply = Vec::new();
+        reply.push(b'T');
+        packet::push_hex_byte(&mut reply, signal);
+        reply.extend_from_slice(alloc::format!("thread:{:x};", target.current).as_bytes());
+        reply
+    }
+
+    fn thread_of(&self, target: &Target) -> usize {
+        if self.selected == 0 {
+            target.current
+        } else {
+            self.selected
+        }
+    }
+
+    fn handle(&mut self, target: &mut Target, packet: &[u8], signal: u8) -> Action {
+        let (cmd, args) = match packet.split_first() {
+            Some((&cmd, args)) => (cmd, args),
+            None => return Action::Reply(Vec::new()),
+        };
+        let id = self.thread_of(target);
+        let reply = match cmd {
+            b'?' => self.stop_reply(target, signal),
+            b'g' => {
+                let mut reply = Vec::new();
+                for reg in 0..NUM_REGS {
+                    match target.read_reg(id, self.view, reg) {
+                        Some(value) => push_hex_reg(&mut reply, value),
+                        None => reply.extend_from_slice(b"xxxxxxxxxxxxxxxx"),
+                    }
+                }
+                reply
+            }
+            // 线程没有保存的寄存器(如 TaskContext 中的临时寄存器)写入时直接忽略
+            b'G' => {
+                let ok = args.chunks(16).enumerate().all(|(reg, hex)| {
+                    parse_hex_reg(hex)
+                        .map(|value| target.write_reg(id, self.view, reg, value))
+                        .is_some()
+                });
+                ok_or_error(ok)
+            }
+            b'p' => match parse_hex(args).filter(|&reg| reg < NUM_REGS) {
+                Some(reg) => {
+                    let mut reply = Vec::new();
+                    match target.read_reg(id, self.view, reg) {
+                        Some(value) => push_hex_reg(&mut reply, value),
+                        None => reply.extend_from_slice(b"xxxxxxxxxxxxxxxx"),
+                    }
+                    reply
+                }
+                None => b"E01".to_vec(),
+            },
+            b'P' => {
+                let ok = split_once(args, b'=').is_some_and(|(reg, value)| {
+                    match (parse_hex(reg), parse_hex_reg(value)) {
+                        (Some(reg), Some(value)) => target.write_reg(id, self.view, reg, value),
+                        _ => false,
+                    }
+                });
+                ok_or_error(ok)
+            }
+            b'm' => match parse_addr_len(args) {
+                Some((addr, len)) => {
+                    let data = target.read_memory(id, self.view, addr, len);
+                    if data.is_empty() && len > 0 {
+                        b"E14".to_vec()
+                    } else {
+                        let mut reply = Vec::new();
+                        push_hex_bytes(&mut reply, &data);
+                        reply
+                    }
+                }
+                None => b"E01".to_vec(),
+            },
+            b'M' => {
+                let ok = split_once(args, b':').is_some_and(|(range, hex)| {
+                    match (parse_addr_len(range), parse_hex_bytes(hex)) {
+                        (Some((addr, len)), Some(data)) if data.len() == len => {
+                            target.write_memory(id, self.view, addr, &data)
+                        }
+                        _ => false,
+                    }
+                });
+                ok_or_error(ok)
+            }
+            b'Z' | b'z' => self.handle_breakpoint_packet(cmd == b'Z', args),
+            b'H' => match args.split_first() {
+                Some((b'g', thread)) => match parse_thread_id(thread) {
+                    Some(0) => {
+                        self.selected = 0;
+                        b"OK".to_vec()
+                    }
+                    Some(thread) if target.has_thread(thread) => {
+                        self.selected = thread;
+                        b"OK".to_vec()
+                    }
+                    _ => b"E01".to_vec(),
+                },
+                // 继续执行总是针对所有线程
+                _ => b"OK".to_vec(),
+            },
+            b'T' => ok_or_error(parse_thread_id(args).is_some_and(|id| target.has_thread(id))),
+            b'c' | b'C' => {
+                // c[addr] 或 Csig[;addr], 忽略信号
+                let addr = match cmd {
+                    b'c' => Some(args),
+                    _ => split_once(args, b';').map(|(_, addr)| addr),
+                };
+                if let Some(addr) = addr.and_then(parse_hex) {
+                    target.write_reg(target.current, self.view, PC, addr);
+                }
+                return self.resume();
+            }
+            b'v' if args.starts_with(b"Cont?") => b"vCont;c;C".to_vec(),
+            b'v' if args.starts_with(b"Cont;c") || args.starts_with(b"Cont;C") => {
+                return self.resume();
+            }
+            b'D' => {
+                self.remove_all_breakpoints();
+                self.conn.send(b"OK");
+                info!("[gdbstub] detached");
+                return Action::Resume;
+            }
+            b'k' => {
+                self.remove_all_breakpoints();
+                return Action::Resume;
+            }
+            b'q' => self.handle_query(target, args),
+            _ => Vec::new(),
+        };
+        Action::Reply(reply)
+    }
+
+    fn resume(&mut self) -> Action {
+        self.running = true;
+        Action::Resume
+    }
+
+    fn handle_query(&mut self, target: &Target, query: &[u8]) -> Vec<u8> {
+        if query.starts_with(b"Supported") {
+            b"PacketSize=1000;qXfer:features:read+;vContSupported+".to_vec()
+        } else if query == b"Attached" {
+            b"1".to_vec()
+        } else if query == b"C" {
+            alloc::format!("QC{:x}", target.current).into_bytes()
+        } else if query == b"fThreadInfo" {
+            let ids: Vec<String> = target
+                .threads
+                .iter()
+                .map(|thread| alloc::format!("{:x}", thread.id))
+                .collect();
+            alloc::format!("m{}", ids.join(",")).into_bytes()
+        } else if query == b"sThreadInfo" {
+            b"l".to_vec()
+        } else if let Some(thread) = query.strip_prefix(b"ThreadExtraInfo,") {
+            match parse_thread_id(thread).and_then(|id| target.thread_info(id, self.view)) {
+                Some(info) => {
+                    let mut reply = Vec::new();
+                    push_hex_bytes(&mut reply, info.as_bytes());
+                    reply
+                }
+                None => b"E01".to_vec(),
+            }
+        } else if let Some(annex) = query.strip_prefix(b"Xfer:features:read:target.xml:") {
+            match parse_addr_len(annex) {
+                Some((offset, len)) => xfer_reply(target_xml().as_bytes(), offset, len),
+                None => b"E01".to_vec(),
+            }
+        } else if let Some(hex) = query.strip_prefix(b"Rcmd,") {
+            self.handle_monitor(hex)
+        } else {
+            Vec::new()
+        }
+    }
+
+    // monitor 命令, 用于切换非当前线程的寄存器视图
+    fn handle_monitor(&mut self, hex: &[u8]) -> Vec<u8> {
+        let cmd = parse_hex_bytes(hex).unwrap_or_default();
+        let output: &[u8] = match cmd.as_slice() {
+            b"user" => {
+                self.view = RegView::User;
+                b"show user registers of other threads\n"
+            }
+            b"kernel" => {
+                self.view = RegView::Kernel;
+                b"show kernel registers of other threads\n"
+            }
+            _ => b"usage: monitor user|kernel\n",
+        };
+        // 输出通过 O 报文发送, 最后回复 OK
+        let mut reply = b"O".to_vec();
+        push_hex_bytes(&mut reply, output);
+        self.conn.send(&reply);
+        b"OK".to_vec()
+    }
+
+    fn handle_breakpoint_packet(&mut self, insert: bool, args: &[u8]) -> Vec<u8> {
+        // 只支持软件断点 Z0,addr,kind
+        let (addr, kind) = match args.strip_prefix(b"0,").and_then(parse_addr_len) {
+            Some(bp) => bp,
+            None => return Vec::new(),
+        };
+        let token = kernel_token();
+        if !insert {
+            let ok = match self.breakpoints.iter().position(|bp| bp.addr == addr) {
+                Some(idx) => {
+                    let bp = self.breakpoints.remove(idx);
+                    write_memory_with(token, bp.addr, &bp.orig)
+                }
+                None => false,
+            };
+            return ok_or_error(ok);
+        }
+        if self.breakpoints.iter().any(|bp| bp.addr == addr) {
+            return b"OK".to_vec();
+        }
+        let insn: &[u8] = match kind {
+            2 => &C_EBREAK.to_le_bytes(),
+            4 => &EBREAK.to_le_bytes(),
+            _ => return b"E01".to_vec(),
+        };
+        let orig: Vec<u8> = (addr..addr + kind)
+            .map_while(|va| crate::mm::debug_read_byte(token, va))
+            .collect();
+        if orig.len() != kind || !write_memory_with(token, addr, insn) {
+            return b"E14".to_vec();
+        }
+        self.breakpoints.push(Breakpoint { addr, orig });
+        b"OK".to_vec()
+    }
+
+    fn remove_all_breakpoints(&mut self) {
+        let token = kernel_token();
+        for bp in self.breakpoints.drain(..) {
+            write_memory_with(token, bp.addr, &bp.orig);
+        }
+    }
+}
+
+fn ok_or_error(ok: bool) -> Vec<u8> {
+    if ok {
+        b"OK".to_vec()
+    } else {
+        b"E01".to_vec()
+    }
+}
+
+fn split_once(bytes: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let pos = bytes.iter().position(|&c| c == sep)?;
+    Some((&bytes[..pos], &bytes[pos + 1..]))
+}
+
+// "addr,len" 形式的参数
+fn parse_addr_len(args: &[u8]) -> Option<(usize, usize)> {
+    let (addr, len) = split_once(args, b',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+// 线程号为十六进制, -1 表示所有线程, 这里按 0(任意线程)处理
+fn parse_thread_id(thread: &[u8]) -> Option<usize> {
+    if thread == b"-1" {
+        Some(0)
+    } else {
+        parse_hex(thread)
+    }
+}
+
+// qXfer 的回复: 'm' 表示后面还有数据, 'l' 表示这是最后一段
+fn xfer_reply(data: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let start = offset.min(data.len());
+    let end = offset.saturating_add(len).min(data.len());
+    let mut reply = Vec::new();
+    reply.push(if end < data.len() { b'm' } else { b'l' });
+    reply.extend_from_slice(&data[start..end]);
+    reply
+}
+
+// 目标描述, 只包含整数寄存器, 避免 GDB 按 ELF 的浮点 ABI 索要浮点寄存器
+fn target_xml() -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0"><architecture>riscv:rv64</architecture><feature name="org.gnu.gdb.riscv.cpu">"#,
+    );
+    for (regnum, name) in REG_NAMES.iter().enumerate() {
+        let ty = match regnum {
+            PC => "code_ptr",
+            2 => "data_ptr",
+            _ => "int",
+        };
+        xml.push_str(&alloc::format!(
+            r#"<reg name="{}" bitsize="64" type="{}" regnum="{}"/>"#,
+            name,
+            ty,
+            regnum
+        ));
+    }
+    xml.push_str("</feature></target>");
+    xml
+}
@@ -0,0 +1,116 @@
+//! GDB 远程串行协议(RSP)的报文收发
+//!
+//! 报文格式为 `$<数据>#<两位十六进制校验和>`, 校验和为数据各字节之和模 256
+//! 收到报文后回复 '+' 表示正确, '-' 表示需要重传. 目前不支持 no-ack 模式
+//! 运行期间 GDB 发送单独的 0x03 字节请求暂停
+
+use crate::drivers::Uart16550;
+use alloc::vec::Vec;
+
+// GDB 请求暂停(Ctrl-C)
+pub const INTERRUPT: u8 = 0x03;
+
+pub struct Connection {
+    uart: Uart16550,
+}
+
+impl Connection {
+    pub fn new(uart: Uart16550) -> Self {
+        uart.init();
+        Self { uart }
+    }
+
+    // 运行期间轮询 GDB 发来的数据, 没有数据时返回 None
+    pub fn poll(&self) -> Option<u8> {
+        self.uart.getc()
+    }
+
+    // 接收一个完整的报文, 校验失败时要求 GDB 重传
+    // first 为轮询时已经读到的第一个字节
+    pub fn recv(&self, mut first: Option<u8>) -> Vec<u8> {
+        loop {
+            // 跳过 ack 以及暂停期间多余的 0x03
+            let start = first.take().unwrap_or_else(|| self.uart.getc_blocking());
+            if start != b'$' {
+                continue;
+            }
+            let mut data = Vec::new();
+            let mut sum: u8 = 0;
+            loop {
+                match self.uart.getc_blocking() {
+                    b'#' => break,
+                    byte => {
+                        sum = sum.wrapping_add(byte);
+                        data.push(byte);
+                    }
+                }
+            }
+            let hi = self.uart.getc_blocking();
+            let lo = self.uart.getc_blocking();
+            if parse_hex(&[hi, lo]) == Some(sum as usize) {
+                self.uart.putc(b'+');
+                return data;
+            }
+            self.uart.putc(b'-');
+        }
+    }
+
+    // 发送一个报文, 直到 GDB 回复 '+'
+    pub fn send(&self, data: &[u8]) {
+        let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        loop {
+            self.uart.putc(b'$');
+            data.iter().for_each(|&byte| self.uart.putc(byte));
+            self.uart.putc(b'#');
+            self.uart.putc(HEX_DIGITS[(sum >> 4) as usize]);
+            self.uart.putc(HEX_DIGITS[(sum & 0xf) as usize]);
+            match self.uart.getc_blocking() {
+                b'-' => continue,
+                _ => break,
+            }
+        }
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+pub fn parse_hex(hex: &[u8]) -> Option<usize> {
+    if hex.is_empty() || hex.len() > 16 {
+        return None;
+    }
+    hex.iter().try_fold(0usize, |value, &c| {
+        let digit = (c as char).to_digit(16)?;
+        Some(value << 4 | digit as usize)
+    })
+}
+
+pub fn push_hex_byte(out: &mut Vec<u8>, byte: u8) {
+    out.push(HEX_DIGITS[(byte >> 4) as usize]);
+    out.push(HEX_DIGITS[(byte & 0xf) as usize]);
+}
+
+pub fn push_hex_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    bytes.iter().for_each(|&byte| push_hex_byte(out, byte));
+}
+
+// 寄存器按目标的字节序(小端)编码
+pub fn push_hex_reg(out: &mut Vec<u8>, value: usize) {
+    push_hex_bytes(out, &value.to_le_bytes());
+}
+
+pub fn parse_hex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| parse_hex(pair).map(|byte| byte as u8))
+        .collect()
+}
+
+pub fn parse_hex_reg(hex: &[u8]) -> Option<usize> {
+    let bytes = parse_hex_bytes(hex)?;
+    if bytes.len() != core::mem::size_of::<usize>() {
+        return None;
+    }
+    Some(usize::from_le_bytes(bytes.try_into().ok()?))
+}
@@ -0,0 +1,256 @@
+//! 调试器看到的线程、寄存器与内存
+//!
+//! 每个尚未退出的线程都作为 GDB 中的一个线程, 线程号为 (pid + 1) << 16 | tid
+//! 当前 hart 上没有线程在运行(idle 或启动阶段)时, 暂停处作为 1 号线程
+//!
+//! 线程的寄存器有三个来源:
+//! 1. 在内核中暂停的当前线程: 内核 Trap 上下文, 即暂停处的全部寄存器
+//! 2. 线程的 TrapContext: 线程在用户态的寄存器
+//! 3. 线程的 TaskContext: 线程在 __switch 中让出 CPU 时的内核态寄存器, pc 取 ra
+//!
+//! 非当前线程默认显示内核态寄存器, 可以用 `monitor user` 切换为用户态寄存器
+//! 内存通过寄存器所属的地址空间访问: 用户态寄存器对应线程所在进程的页表, 其余对应内核页表
+
+use crate::mm::{debug_read_byte, debug_write_byte, kernel_token};
+use crate::task::{try_current_task, try_list_tasks, TaskControlBlock};
+use crate::trap::TrapContext;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+// GDB 中 RV64 的寄存器: x0 ~ x31 以及 pc
+pub const NUM_REGS: usize = 33;
+pub const PC: usize = 32;
+
+// 在内核中暂停且当前 hart 上没有线程时使用的线程号
+const IDLE_THREAD_ID: usize = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RegView {
+    Kernel,
+    User,
+}
+
+pub struct Thread {
+    pub id: usize,
+    pid: usize,
+    tid: usize,
+    task: Option<Arc<TaskControlBlock>>,
+}
+
+enum RegSource<'a> {
+    Frame(&'a mut TrapContext),
+    Trap(&'static mut TrapContext),
+    Switch(Arc<TaskControlBlock>),
+}
+
+// 一次暂停期间调试器能看到的全部状态
+pub struct Target<'a> {
+    // 在内核中暂停时的 Trap 上下文, 在用户态暂停(时钟中断)时为 None
+    frame: Option<&'a mut TrapContext>,
+    pub threads: Vec<Thread>,
+    // 暂停时当前 hart 上的线程
+    pub current: usize,
+}
+
+fn thread_id(pid: usize, tid: usize) -> usize {
+    (pid + 1) << 16 | tid
+}
+
+impl<'a> Target<'a> {
+    pub fn new(frame: Option<&'a mut TrapContext>) -> Self {
+        let current_task = try_current_task();
+        let mut threads: Vec<Thread> = try_list_tasks()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(pid, tid, task)| Thread {
+                id: thread_id(pid, tid),
+                pid,
+                tid,
+                task: Some(task),
+            })
+            .collect();
+        let current = current_task
+            .and_then(|task| {
+                threads
+                    .iter()
+                    .find(|thread| thread.task.as_ref().is_some_and(|t| Arc::ptr_eq(t, &task)))
+                    .map(|thread| thread.id)
+            })
+            .unwrap_or(IDLE_THREAD_ID);
+        if current == IDLE_THREAD_ID {
+            threads.insert(
+                0,
+                Thread {
+                    id: IDLE_THREAD_ID,
+                    pid: 0,
+                    tid: 0,
+                    task: None,
+                },
+            );
+        }
+        Self {
+            frame,
+            threads,
+            current,
+        }
+    }
+
+    fn thread(&self, id: usize) -> Option<&Thread> {
+        self.threads.iter().find(|thread| thread.id == id)
+    }
+
+    pub fn has_thread(&self, id: usize) -> bool {
+        self.thread(id).is_some()
+    }
+
+    pub fn thread_info(&self, id: usize, view: RegView) -> Option<String> {
+        let thread = self.thread(id)?;
+        let mut info = String::new();
+        match &thread.task {
+            None => info.push_str("idle"),
+            Some(task) => {
+                let _ = write!(info, "pid {} tid {}", thread.pid, thread.tid);
+                if let Some(inner) = task.try_inner_exclusive_access() {
+                    let _ = write!(info, " {:?}", inner.task_info.status);
+                }
+            }
+        }
+        let regs = match self.view_of(id, view) {
+            Some(RegView::User) => " [user]",
+            _ => " [kernel]",
+        };
+        info.push_str(regs);
+        Some(info)
+    }
+
+    // 线程实际显示的寄存器视图, 只有在内核中暂停的当前线程不受 view 影响
+    fn view_of(&self, id: usize, view: RegView) -> Option<RegView> {
+        let thread = self.thread(id)?;
+        if id == self.current {
+            return Some(if self.frame.is_some() {
+                RegView::Kernel
+            } else {
+                RegView::User
+            });
+        }
+        thread.task.as_ref()?;
+        Some(view)
+    }
+
+    fn reg_source(&mut self, id: usize, view: RegView) -> Option<RegSource<'_>> {
+        let view = self.view_of(id, view)?;
+        if id == self.current && view == RegView::Kernel {
+            return self.frame.as_deref_mut().map(RegSource::Frame);
+        }
+        let task = Arc::clone(self.thread(id)?.task.as_ref()?);
+        match view {
+            RegView::User => {
+                let inner = task.try_inner_exclusive_access()?;
+                inner.res.as_ref()?;
+                Some(RegSource::Trap(inner.get_trap_cx()))
+            }
+            RegView::Kernel => Some(RegSource::Switch(task)),
+        }
+    }
+
+    // 读取寄存器, 不可用时返回 None
+    pub fn read_reg(&mut self, id: usize, view: RegView, reg: usize) -> Option<usize> {
+        match self.reg_source(id, view)? {
+            RegSource::Frame(ctx) | RegSource::Trap(ctx) => match reg {
+                0 => Some(0),
+                PC => Some(ctx.sepc),
+                reg if reg < PC => Some(ctx.x[reg]),
+                _ => None,
+            },
+            RegSource::Switch(task) => {
+                let mut inner = task.try_inner_exclusive_access()?;
+                match reg {
+                    0 => Some(0),
+                    // 让出 CPU 的线程会从 ra 处继续执行
+                    PC => inner.task_ctx.reg_mut(1).map(|ra| *ra),
+                    reg => inner.task_ctx.reg_mut(reg).map(|value| *value),
+                }
+            }
+        }
+    }
+
+    pub fn write_reg(&mut self, id: usize, view: RegView, reg: usize, value: usize) -> bool {
+        let source = match self.reg_source(id, view) {
+            Some(source) => source,
+            None => return false,
+        };
+        match source {
+            RegSource::Frame(ctx) | RegSource::Trap(ctx) => match reg {
+                0 => true,
+                PC => {
+                    ctx.sepc = value;
+                    true
+                }
+                reg if reg < PC => {
+                    ctx.x[reg] = value;
+                    true
+                }
+                _ => false,
+            },
+            RegSource::Switch(task) => {
+                let mut inner = match task.try_inner_exclusive_access() {
+                    Some(inner) => inner,
+                    None => return false,
+                };
+                let reg = if reg == PC { 1 } else { reg };
+                match inner.task_ctx.reg_mut(reg) {
+                    Some(slot) => {
+                        *slot = value;
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    // 线程寄存器所在的地址空间
+    fn token(&self, id: usize, view: RegView) -> Option<usize> {
+        match self.view_of(id, view)? {
+            RegView::Kernel => Some(kernel_token()),
+            RegView::User => {
+                let process = self.thread(id)?.task.as_ref()?.process.upgrade()?;
+                let token = process.try_inner_exclusive_access()?.memory_set.token();
+                Some(token)
+            }
+        }
+    }
+
+    // 读取内存, 遇到无法访问的地址时提前结束, 返回已读取的内容
+    pub fn read_memory(&self, id: usize, view: RegView, addr: usize, len: usize) -> Vec<u8> {
+        let token = match self.token(id, view) {
+            Some(token) => token,
+            None => return Vec::new(),
+        };
+        (addr..addr.saturating_add(len))
+            .map_while(|va| debug_read_byte(token, va))
+            .collect()
+    }
+
+    pub fn write_memory(&self, id: usize, view: RegView, addr: usize, data: &[u8]) -> bool {
+        let token = match self.token(id, view) {
+            Some(token) => token,
+            None => return false,
+        };
+        write_memory_with(token, addr, data)
+    }
+}
+
+// 写入后执行 fence.i, 以便写入的断点指令立即生效
+pub fn write_memory_with(token: usize, addr: usize, data: &[u8]) -> bool {
+    let ok = data
+        .iter()
+        .enumerate()
+        .all(|(i, &byte)| debug_write_byte(token, addr + i, byte).is_some());
+    unsafe {
+        core::arch::asm!("fence.i");
+    }
+    ok
+}
@@ -0,0 +1,57 @@
+//! 内核自测框架
+//!
+//! 用 `#[kernel_test]` 标记的函数在 `cargo test` 编译出的内核中由 [`run`] 依次执行
+//! 输出格式与 libtest 相近, 便于 `make test` 与 xtask 解析:
+//!
+//! ```text
+//! running 3 tests
+//! test os::mm::frame_allocator::frame_allocator_test ... ok
+//! test os::mm::heap_allocator::heap_test ... FAILED
+//! test result: FAILED. 1 passed; 1 failed
+//! ```
+//!
+//! 全部通过时以成功状态退出 QEMU; 测试失败即 panic, 此时由 panic 处理函数调用 [`report_failure`]
+//! 输出结果, 再打印 panic 信息并以失败状态退出 QEMU, 因此一次运行最多只有一个失败的测试
+
+use crate::sbi::shutdown;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub struct KernelTest {
+    pub name: &'static str,
+    pub func: fn(),
+}
+
+// 已经通过的测试数量
+static PASSED: AtomicUsize = AtomicUsize::new(0);
+// 是否正在执行某个测试, 测试之外的 panic 不属于任何测试
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub fn run(tests: &[&KernelTest]) -> ! {
+    println!("running {} tests", tests.len());
+    for test in tests {
+        print!("test {} ... ", test.name);
+        RUNNING.store(true, Ordering::Relaxed);
+        (test.func)();
+        RUNNING.store(false, Ordering::Relaxed);
+        println!("ok");
+        PASSED.fetch_add(1, Ordering::Relaxed);
+    }
+    println!(
+        "test result: ok. {} passed; 0 failed",
+        PASSED.load(Ordering::Relaxed)
+    );
+    shutdown(false)
+}
+
+// 当前测试 panic 时由 panic 处理函数调用, 之后由它负责退出
+// 测试开始前或者测试之间的 panic 不输出测试结果, 由解析方当作内核没有给出结果处理
+pub fn report_failure() {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    println!("FAILED");
+    println!(
+        "test result: FAILED. {} passed; 1 failed",
+        PASSED.load(Ordering::Relaxed)
+    );
+}
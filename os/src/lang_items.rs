@@ -7,6 +7,10 @@ use core::panic::PanicInfo;
 */
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // 测试内核中 panic 意味着当前测试失败
+    #[cfg(test)]
+    crate::ktest::report_failure();
+
     if let Some(location) = info.location() {
         println!(
             "panicked at {}:{} {}",
@@ -1,88 +1,3 @@
-use crate::config::*;
-use core::arch::asm;
-use log::*;
-
-use crate::{
-    config::{APP_BASE_ADDRESS, APP_SIZE_LIMIT},
-    trap::TrapContext,
-};
-
-#[repr(align(4096))]
-#[derive(Copy, Clone)]
-struct KernelStack {
-    data: [u8; KERNEL_STACK_SIZE],
-}
-
-#[repr(align(4096))]
-#[derive(Copy, Clone)]
-struct UserStack {
-    data: [u8; USER_STACK_SIZE],
-}
-
-static KERNEL_STACK: [KernelStack; MAX_APP_NUM] = [KernelStack {
-    data: [0; KERNEL_STACK_SIZE],
-}; MAX_APP_NUM];
-
-static USER_STACK: [UserStack; MAX_APP_NUM] = [UserStack {
-    data: [0; USER_STACK_SIZE],
-}; MAX_APP_NUM];
-
-impl KernelStack {
-    fn get_sp(&self) -> usize {
-        self.data.as_ptr() as usize + KERNEL_STACK_SIZE
-    }
-    pub fn push_context(&self, trap_ctx: TrapContext) -> usize {
-        let trap_ctx_ptr =
-            (self.get_sp() - core::mem::size_of::<TrapContext>()) as *mut TrapContext;
-        unsafe {
-            *trap_ctx_ptr = trap_ctx;
-        }
-        trap_ctx_ptr as usize
-    }
-}
-
-impl UserStack {
-    fn get_sp(&self) -> usize {
-        self.data.as_ptr() as usize + USER_STACK_SIZE
-    }
-}
-
-// 这将load所有app到内存中,而不是之前那样轮流,替换式load
-// 因此要特别注意,app之间不能踩踏
-pub fn load_apps() {
-    extern "C" {
-        fn _num_app();
-    }
-    let num_app_ptr = _num_app as *const usize;
-    let num_app = get_num_app();
-    info!("[kernel] get apps num = {}", num_app);
-    let app_start = unsafe { core::slice::from_raw_parts(num_app_ptr.add(1), num_app + 1) };
-
-    for i in 0..num_app {
-        // get start addr for apps[i]
-        let base_i = get_base_i(i);
-        // clear region
-        (base_i..base_i + APP_SIZE_LIMIT).for_each(|addr| unsafe {
-            (addr as *mut u8).write_volatile(0);
-        });
-        // load app from data region to memory
-        let src = unsafe {
-            core::slice::from_raw_parts(app_start[i] as *const u8, app_start[i + 1] - app_start[i])
-        };
-        let dst = unsafe { core::slice::from_raw_parts_mut(base_i as *mut u8, src.len()) };
-        dst.copy_from_slice(src);
-        info!("[kernel] load app {} to {:?}", i, dst.as_ptr());
-    }
-    info!("[kernel] all apps load done");
-    unsafe {
-        asm!("fence.i");
-    }
-}
-
-fn get_base_i(app_id: usize) -> usize {
-    APP_BASE_ADDRESS + app_id * APP_SIZE_LIMIT
-}
-
 /// Get the total number of applications.
 //获取链接到内核内的应用的数目
 pub fn get_num_app() -> usize {
@@ -92,13 +7,6 @@ pub fn get_num_app() -> usize {
     unsafe { (_num_app as *const usize).read_volatile() }
 }
 
-pub fn init_app_ctx(app_id: usize) -> usize {
-    KERNEL_STACK[app_id].push_context(TrapContext::app_init_context(
-        get_base_i(app_id),
-        USER_STACK[app_id].get_sp(),
-    ))
-}
-
 // 根据app id取出对应app的elf格式可执行文件的数据
 pub fn get_app_data(app_id: usize) -> &'static [u8] {
     extern "C" {
@@ -115,3 +23,32 @@ pub fn get_app_data(app_id: usize) -> &'static [u8] {
         )
     }
 }
+
+// 根据app id取出对应app的名称
+pub fn get_app_name(app_id: usize) -> &'static str {
+    extern "C" {
+        fn _app_names();
+    }
+    let num_app = get_num_app();
+    assert!(app_id < num_app);
+    let mut start = _app_names as usize as *const u8;
+    unsafe {
+        for _ in 0..app_id {
+            while start.read_volatile() != b'\0' {
+                start = start.add(1);
+            }
+            start = start.add(1);
+        }
+        let mut end = start;
+        while end.read_volatile() != b'\0' {
+            end = end.add(1);
+        }
+        let slice = core::slice::from_raw_parts(start, end as usize - start as usize);
+        core::str::from_utf8(slice).unwrap()
+    }
+}
+
+// 根据名称查找应用, 返回其 app id
+pub fn find_app(name: &str) -> Option<usize> {
+    (0..get_num_app()).find(|&app_id| get_app_name(app_id) == name)
+}
@@ -1,17 +1,166 @@
+//! 内核日志
+//!
+//! 日志除了输出到控制台外, 还会以文本行的形式保存在一个固定大小的环形缓冲区中
+//! 每行带有时间戳(微秒)与当前线程的 pid:tid, 缓冲区满后覆盖最旧的日志
+//! 应用可以通过 sys_syslog 读取或清空缓冲区, 也可以在运行时修改日志级别
+//!
+//! 日志级别可以按模块设置, 如 "os::task=debug", 模块名按前缀匹配, 最长的匹配优先
+//! 没有匹配的模块使用默认级别, 默认级别在编译时由环境变量 LOG 指定
+
+use crate::sync::SpinNoIrq;
+use crate::task::current_task_ids;
+use crate::timer::get_time_us;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use kernel_test::kernel_test;
 use log::{Level, LevelFilter, Log, Metadata, Record};
 
+// 日志缓冲区的大小
+pub const LOG_BUF_SIZE: usize = 16 * 1024;
+
+struct LogBuffer {
+    buf: [u8; LOG_BUF_SIZE],
+    // 累计写入的字节数, 下一个字节写到 head % LOG_BUF_SIZE 处
+    head: usize,
+    // 缓冲区中有效的字节数
+    len: usize,
+    // SYSLOG_ACTION_READ 读到的位置, 与 head 一样是累计的字节数
+    read_pos: usize,
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; LOG_BUF_SIZE],
+            head: 0,
+            len: 0,
+            read_pos: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.buf[self.head % LOG_BUF_SIZE] = byte;
+        self.head += 1;
+        self.len = (self.len + 1).min(LOG_BUF_SIZE);
+    }
+
+    // 缓冲区被覆盖过时最旧的一行可能不完整, 返回 start 之后第一个完整行的开头
+    fn line_start(&self, mut start: usize) -> usize {
+        let oldest = self.head - self.len;
+        let at_line_start = if start > oldest {
+            self.buf[(start - 1) % LOG_BUF_SIZE] == b'\n'
+        } else {
+            self.head <= LOG_BUF_SIZE || self.len < LOG_BUF_SIZE
+        };
+        if at_line_start {
+            return start;
+        }
+        while start < self.head && self.buf[start % LOG_BUF_SIZE] != b'\n' {
+            start += 1;
+        }
+        (start + 1).min(self.head)
+    }
+
+    // 从 start 开始拷贝至多 out.len() 个字节, 返回拷贝的字节数
+    fn copy_from(&self, start: usize, out: &mut [u8]) -> usize {
+        let count = (self.head - start).min(out.len());
+        for (i, byte) in out.iter_mut().take(count).enumerate() {
+            *byte = self.buf[(start + i) % LOG_BUF_SIZE];
+        }
+        count
+    }
+
+    // 按从旧到新的顺序拷贝最新的至多 out.len() 个字节, 返回拷贝的字节数
+    // 总是从某一行的开头开始拷贝
+    fn read(&self, out: &mut [u8]) -> usize {
+        let oldest = self.head - self.len;
+        let start = self.line_start(oldest.max(self.head.saturating_sub(out.len())));
+        self.copy_from(start, out)
+    }
+
+    // 第一个还没有被读走的字节, 未读的部分已经被覆盖或清空时从最旧的完整一行开始
+    fn unread_start(&self) -> usize {
+        let oldest = self.head - self.len;
+        if self.read_pos >= oldest {
+            self.read_pos
+        } else {
+            self.line_start(oldest)
+        }
+    }
+
+    fn unread_len(&self) -> usize {
+        self.head - self.unread_start()
+    }
+
+    // 从上次读到的位置开始拷贝至多 out.len() 个字节, 不移动读位置
+    // 返回 (起始位置, 拷贝的字节数), 数据交给读者之后再用 commit_read 移动读位置
+    fn peek_unread(&self, out: &mut [u8]) -> (usize, usize) {
+        let start = self.unread_start();
+        (start, self.copy_from(start, out))
+    }
+
+    // [start, start + count) 已经交给了读者, 读位置只会前进
+    fn commit_read(&mut self, start: usize, count: usize) {
+        self.read_pos = self.read_pos.max(start + count);
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Write for LogBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|byte| self.push(byte));
+        Ok(())
+    }
+}
+
+// 日志级别过滤规则
+struct LevelFilters {
+    default: LevelFilter,
+    // (模块名前缀, 级别)
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LevelFilters {
+    fn level_of(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target.starts_with(module.as_str())
+                    && (target.len() == module.len() || target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+static LOG_BUFFER: SpinNoIrq<LogBuffer> = SpinNoIrq::new(LogBuffer::new());
+static LEVEL_FILTERS: SpinNoIrq<LevelFilters> = SpinNoIrq::new(LevelFilters {
+    default: LevelFilter::Trace,
+    modules: Vec::new(),
+});
+
 struct SimpleLogger;
 
 impl Log for SimpleLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        //metadata.level() <= Level::Info
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LEVEL_FILTERS.lock().level_of(metadata.target())
     }
 
     fn log(&self, record: &Record) {
-        // if self.enabled(record.metadata()) {
-        //     println!("{} - {}", record.level(), record.args());
-        // }
+        if !self.enabled(record.metadata()) {
+            return;
+        }
         let color = match record.level() {
             Level::Trace => 90, // BrightBlack
             Level::Debug => 32, // Green
@@ -20,6 +169,28 @@ impl Log for SimpleLogger {
             Level::Error => 31, //Red
         };
 
+        let us = get_time_us();
+        let _ = match current_task_ids() {
+            Some((pid, tid)) => writeln!(
+                LOG_BUFFER.lock(),
+                "[{:>5}.{:06}] [{:>5}] [{}:{}] {}",
+                us / 1_000_000,
+                us % 1_000_000,
+                record.level(),
+                pid,
+                tid,
+                record.args()
+            ),
+            None => writeln!(
+                LOG_BUFFER.lock(),
+                "[{:>5}.{:06}] [{:>5}] [-] {}",
+                us / 1_000_000,
+                us % 1_000_000,
+                record.level(),
+                record.args()
+            ),
+        };
+
         println!(
             "\u{1B}[{}m[{:>5}] {}\u{1B}[0m",
             color,
@@ -33,6 +204,18 @@ impl Log for SimpleLogger {
 
 static LOGGER: SimpleLogger = SimpleLogger;
 
+fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level {
+        "trace" => Some(LevelFilter::Trace),
+        "debug" => Some(LevelFilter::Debug),
+        "info" => Some(LevelFilter::Info),
+        "warn" => Some(LevelFilter::Warn),
+        "error" => Some(LevelFilter::Error),
+        "off" => Some(LevelFilter::Off),
+        _ => None,
+    }
+}
+
 pub fn init() {
     if let Err(e) = log::set_logger(&LOGGER) {
         println!("set logger failed: {} ?", e);
@@ -40,24 +223,84 @@ pub fn init() {
     }
 
     let level = option_env!("LOG").unwrap_or("trace");
-    // let level = option_env!("LOG").map(|inner| {
-    //     inner.chars().map(|c| {
-    //         if c >= 'A' && c <= 'Z' {
-    //             (c as u8 + 32) as char
-    //         } else {
-    //             c
-    //         }
-    //     }).collect::<Vec<char>>()
-    // }).unwrap_or("trace");
-
-    let max_level = match level {
-        "trace" => LevelFilter::Trace,
-        "debug" => LevelFilter::Debug,
-        "info" => LevelFilter::Info,
-        "warn" => LevelFilter::Warn,
-        "error" => LevelFilter::Error,
-        _ => LevelFilter::Off,
-    };
-
-    log::set_max_level(max_level);
+    let default = parse_level(level).unwrap_or(LevelFilter::Off);
+    LEVEL_FILTERS.lock().default = default;
+    log::set_max_level(default);
+}
+
+// 修改日志级别, spec 为 "级别" 或 "模块=级别"
+// 模块的级别为 "default" 时删除该模块的规则, 改用默认级别
+pub fn set_level(spec: &str) -> bool {
+    let mut filters = LEVEL_FILTERS.lock();
+    match spec.split_once('=') {
+        None => match parse_level(spec) {
+            Some(level) => filters.default = level,
+            None => return false,
+        },
+        Some((module, level)) => {
+            let level = match level {
+                "default" => None,
+                level => Some(parse_level(level)),
+            };
+            if module.is_empty() || level == Some(None) {
+                return false;
+            }
+            filters.modules.retain(|(m, _)| m != module);
+            if let Some(Some(level)) = level {
+                filters.modules.push((String::from(module), level));
+            }
+        }
+    }
+    // log 宏会先与全局的最大级别比较, 因此它需要是所有规则中最宽松的那个
+    log::set_max_level(filters.max_level());
+    true
+}
+
+// 读取缓冲区中最新的 out.len() 个字节, 返回读取的字节数
+pub fn read_log(out: &mut [u8]) -> usize {
+    LOG_BUFFER.lock().read(out)
+}
+
+// 拷贝上次读到的位置之后的日志, 返回 (起始位置, 拷贝的字节数)
+// 读位置不变, 日志成功交给读者后要调用 commit_unread_log, 之后这些字节不会再被读到
+pub fn peek_unread_log(out: &mut [u8]) -> (usize, usize) {
+    LOG_BUFFER.lock().peek_unread(out)
+}
+
+pub fn commit_unread_log(start: usize, count: usize) {
+    LOG_BUFFER.lock().commit_read(start, count);
+}
+
+pub fn clear_log() {
+    LOG_BUFFER.lock().clear();
+}
+
+// 还没有被读走(commit_unread_log)的字节数
+pub fn unread_log_len() -> usize {
+    LOG_BUFFER.lock().unread_len()
+}
+
+#[kernel_test]
+fn log_buffer_unread_test() {
+    let mut log = LogBuffer::new();
+    let mut out = [0u8; 8];
+    log.write_str("abc\n").unwrap();
+    assert_eq!(log.unread_len(), 4);
+    // 只拷贝不确认时读位置不变
+    assert_eq!(log.peek_unread(&mut out[..2]), (0, 2));
+    assert_eq!(log.unread_len(), 4);
+    log.commit_read(0, 2);
+    assert_eq!(log.unread_len(), 2);
+    log.write_str("de\n").unwrap();
+    assert_eq!(log.peek_unread(&mut out), (2, 5));
+    log.commit_read(2, 5);
+    assert_eq!(&out[..5], b"c\nde\n");
+    assert_eq!(log.unread_len(), 0);
+    // 读取最新的日志不影响读位置
+    assert_eq!(log.read(&mut out), 7);
+    assert_eq!(log.unread_len(), 0);
+    log.write_str("f\n").unwrap();
+    log.clear();
+    assert_eq!(log.unread_len(), 0);
+    assert_eq!(log.peek_unread(&mut out).1, 0);
 }
@@ -1,7 +1,9 @@
 #![no_std]
 #![no_main]
-#![feature(variant_count)]
 #![feature(alloc_error_handler)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::ktest::run)]
+#![reexport_test_harness_main = "test_main"]
 #[macro_use]
 mod console;
 extern crate bitflags;
@@ -9,11 +11,18 @@ extern crate bitflags;
 #[path = "board/qemu.rs"]
 mod board;
 
-pub mod batch;
 mod config;
+mod drivers;
+#[cfg(feature = "gdbstub")]
+mod gdbstub;
+mod ksyms;
+#[cfg(test)]
+mod ktest;
 mod lang_items;
 mod loader;
 mod logging;
+#[cfg(feature = "profile")]
+mod profile;
 mod sbi;
 mod stack_trace;
 mod sync;
@@ -22,22 +31,42 @@ mod task;
 mod timer;
 pub mod trap;
 
-use core::arch::global_asm;
+use config::MAX_HARTS;
+use core::arch::{asm, global_asm};
+use core::sync::atomic::{AtomicBool, Ordering};
 use log::*;
-use riscv::register::sstatus;
+use riscv::register::{satp, sstatus};
 
 mod mm;
 extern crate alloc;
 
 // include_str! 宏, 可以将指令路径下的文件转化为字符串
 // 再通过global_asm!宏嵌入到代码中
-global_asm!(include_str!("entry.asm"));
+global_asm!(
+    include_str!("entry.asm"),
+    MAX_HARTS = const MAX_HARTS,
+    BOOT_STACK_SIZE = const config::BOOT_STACK_SIZE,
+);
 
 global_asm!(include_str!("link_app.S"));
 
+// 是否已经有 hart 进入内核完成初始化
+// 放在 .data 段中, 以免被启动 hart 的 clear_bss 清零
+#[link_section = ".data"]
+static BOOT_HART_ENTERED: AtomicBool = AtomicBool::new(false);
+
 // 避免编译器对函数名称进行混淆, 否则链接时, entry.asm将找不到该函数
+// SBI 只会让一个 hart 进入内核, 其余 hart 由它通过 HSM 扩展启动
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hartid: usize, opaque: usize) -> ! {
+    if BOOT_HART_ENTERED.swap(true, Ordering::AcqRel) {
+        secondary_main(hartid, opaque)
+    }
+    primary_main(hartid, opaque)
+}
+
+// 启动 hart 的 opaque 为 SBI 传入的设备树(FDT)物理地址
+fn primary_main(hartid: usize, dtb: usize) -> ! {
     extern "C" {
         fn stext(); // begin addr of text segment
         fn etext(); // end addr of text segment
@@ -56,6 +85,7 @@ pub fn rust_main() -> ! {
     println!("[kernel] hello rCore!");
 
     logging::init();
+    info!("[kernel] boot hart {}, dtb {:#x}", hartid, dtb);
 
     trace!(
         "[kernel] .text [{:#x}, {:#x})",
@@ -77,31 +107,35 @@ pub fn rust_main() -> ! {
     error!("[kernel] .bss [{:#x}, {:#x})", sbss as usize, ebss as usize);
 
     mm::init();
-    mm::frame_allocator::frame_allocator_test();
-
     trap::init();
-    //batch::init();
-    //batch::run_next_app();
-    loader::load_apps();
+
+    // 测试内核(make test)在这里运行全部 #[kernel_test] 并退出 QEMU
+    #[cfg(test)]
+    test_main();
+
+    task::add_apps();
+    drivers::init();
+    #[cfg(feature = "gdbstub")]
+    gdbstub::init();
     trap::enable_timer_interrupt();
+    trap::enable_external_interrupt();
     timer::set_next_trigger();
 
     unsafe {
         // 打开内核态中断
         sstatus::set_sie();
     }
-    loop {
-        if trap::check_kernel_interrupt() {
-            info!("kernel interrupt returned");
-            break;
-        }
-    }
+    // 等待第一次内核态时钟中断, 以验证内核 trap 处理流程
+    while trap::kernel_ticks() == 0 {}
+    info!("kernel interrupt returned");
     unsafe {
         // 关闭内核态中断
         sstatus::clear_sie();
     }
 
-    task::run_first_task();
+    start_secondary_harts(hartid);
+
+    task::run_tasks();
 
     // 如果以panic等非正常途径的方式进入发散
     // make 检查返回值会报错, 属于正常现象
@@ -109,6 +143,40 @@ pub fn rust_main() -> ! {
     //sbi::shutdown(false)
 }
 
+// 通过 SBI HSM 扩展启动其余 hart, 它们同样从 _start 开始执行
+// opaque 传入内核页表的 token, 从 hart 不再访问 KERNEL_SPACE
+// SBI 在进入内核前已经等待所有 hart 就绪, 因此 SBI_ERR_INVALID_PARAM 只说明该 hart 不存在
+fn start_secondary_harts(boot_hartid: usize) {
+    extern "C" {
+        fn _start();
+    }
+    let token = mm::kernel_token();
+    for hartid in (0..MAX_HARTS).filter(|&id| id != boot_hartid) {
+        match sbi::hart_start(hartid, _start as usize, token) {
+            Ok(()) => debug!("[kernel] starting hart {}", hartid),
+            Err(sbi::SBI_ERR_INVALID_PARAM) => {}
+            Err(error) => warn!("[kernel] failed to start hart {}: error {}", hartid, error),
+        }
+    }
+}
+
+// 从 hart 的入口, 此时启动 hart 已经完成了全局的初始化
+fn secondary_main(hartid: usize, kernel_token: usize) -> ! {
+    unsafe {
+        satp::write(kernel_token);
+        asm!("sfence.vma");
+    }
+    trap::init();
+    info!("[kernel] hart {} started", hartid);
+    // 已知限制: 任务、进程等数据结构由 UPSafeCell 保护, 只能在单核上安全访问,
+    // 因此从 hart 只完成初始化后停在这里, 不参与调度, 所有任务都运行在启动 hart 上
+    loop {
+        unsafe {
+            asm!("wfi");
+        }
+    }
+}
+
 fn clear_bss() {
     extern "C" {
         fn sbss();
@@ -1,85 +1,23 @@
-use super::address::{PhysAddr, PhysPageNum};
-use crate::{config::MEMORY_END, sync::UPSafeCell};
-use alloc::vec::Vec;
-use core::panic;
+use super::address::{PhysAddr, PhysPageAccess, PhysPageNum};
+use crate::{config::MEMORY_END, sync::SpinNoIrq};
+use kernel_test::kernel_test;
 use lazy_static::lazy_static;
-use log::*;
+use mm_core::{FrameAllocator, StackFrameAllocator};
 
-// 描述一个物理页帧管理器需要提供哪些功能
-trait FrameAllocator {
-    fn new() -> Self;
-    // 分配页面
-    fn alloc(&mut self) -> Option<PhysPageNum>;
-    // 回收页面
-    fn dealloc(&mut self, ppn: PhysPageNum);
-}
-
-/*
-    最简单的栈式物理页帧管理策略
-    物理页号区间 [current, end) 此前均未被分配出去过
-    recycled向量则以LIFO的方式保存了被回收的物理页号
-*/
-pub struct StackFrameAllocator {
-    current: usize, //空闲内存的起始物理页号
-    end: usize,     // 空闲内存的结束物理页号
-    recycled: Vec<usize>,
-}
-
-impl StackFrameAllocator {
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
-        self.end = r.0;
-    }
-}
-
-impl FrameAllocator for StackFrameAllocator {
-    fn new() -> Self {
-        Self {
-            current: 0,
-            end: 0,
-            recycled: Vec::new(),
-        }
-    }
+// 页帧分配器的实现见 mm_core::frame_allocator
 
-    fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            trace!("page {} recycled", ppn);
-            Some(ppn.into())
-        } else if self.current == self.end {
-            error!("overflow!!!");
-            None
-        } else {
-            trace!("alloc new page");
-            self.current += 1;
-            Some((self.current - 1).into())
-        }
-    }
-
-    fn dealloc(&mut self, ppn: PhysPageNum) {
-        let ppn = ppn.0;
-        // validity check
-        // 1. 该页面之前一定被分配出去过,因此它的物理页号一定 < current
-        // 2. 该页面没有正处于回收状态,因此它不能在recycled找到
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
-            panic!("Frame ppn={:#x} as not been allocated!", ppn);
-        }
-        //recycle
-        self.recycled.push(ppn);
-    }
-}
-
-// 使用UPSafeCell封装,确保安全访问
+// 使用SpinNoIrq封装,确保多核下的安全访问
 type FrameAllocatorImpl = StackFrameAllocator;
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinNoIrq<FrameAllocatorImpl> =
+        SpinNoIrq::new(FrameAllocatorImpl::new());
 }
 
 pub fn init_frame_allocator() {
     extern "C" {
         fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
@@ -111,34 +49,32 @@ impl Drop for FrameTracker {
 // 当它的生命周期结束被回收时, 通过实现Drop让编译器自动处理
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
-        .exclusive_access()
+        .lock()
         .alloc()
         //.map(FrameTracker::new)
         .map(|ppn| FrameTracker::new(ppn))
 }
 
 fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
 
-#[allow(unused)]
-pub fn frame_allocator_test() {
-    init_frame_allocator();
-    info!("new pages test :)");
+#[kernel_test]
+fn frame_allocator_test() {
+    use alloc::vec::Vec;
+    // 不要在这里重新 init_frame_allocator
+    // 否则会把内核页表已经占用的页帧再次分配出去
     let mut v = Vec::<FrameTracker>::new();
-    for i in 0..5 {
-        let frame = frame_alloc().unwrap();
-        println!("{:?}", frame);
-        v.push(frame);
+    for _ in 0..5 {
+        v.push(frame_alloc().unwrap());
     }
+    let ppns: Vec<PhysPageNum> = v.iter().map(|frame| frame.ppn).collect();
     v.clear();
 
-    info!("reuse pages test :)");
-    for i in 0..5 {
+    // 回收的页帧会被优先分配出去
+    for _ in 0..5 {
         let frame = frame_alloc().unwrap();
-        println!("{:?}", frame);
+        assert!(ppns.contains(&frame.ppn));
         v.push(frame);
     }
-    drop(v);
-    println!("frame_allocator_test passed!");
 }
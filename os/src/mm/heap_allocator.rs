@@ -3,6 +3,7 @@ use core::{alloc::Layout, ops::RangeBounds};
 use buddy_system_allocator::LockedHeap;
 
 use crate::config::KERNEL_HEAP_SIZE;
+use kernel_test::kernel_test;
 
 // LockedHeap 是被Mutex保护的类型
 // 因此操作它前必须先获取锁
@@ -26,8 +27,8 @@ pub fn init_heap() {
     }
 }
 
-#[allow(unused)]
-pub fn heap_test() {
+#[kernel_test]
+fn heap_test() {
     use alloc::boxed::Box;
     use alloc::vec::Vec;
     extern "C" {
@@ -55,5 +56,4 @@ pub fn heap_test() {
     // 同理, 该Vec也是如此
     assert!(bss_range.contains(&(v.as_ptr() as usize)));
     drop(v);
-    println!("heap_test passed!");
 }
@@ -3,12 +3,12 @@ use super::{
     frame_allocator::FrameTracker,
     page_table::PageTable,
 };
-use crate::mm::address::{PhysAddr, PhysPageNum, StepByOne};
+use crate::mm::address::{PhysAddr, PhysPageAccess, PhysPageNum, StepByOne};
 use crate::mm::frame_allocator::frame_alloc;
-use crate::mm::page_table::PTEFlags;
+use crate::mm::page_table::{PTEFlags, PageTableEntry};
 use crate::{
-    config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE},
-    sync::UPSafeCell,
+    config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE},
+    sync::SpinNoIrq,
 };
 use alloc::vec::Vec;
 use alloc::{collections::btree_map::BTreeMap, sync::Arc};
@@ -230,12 +230,17 @@ impl MemorySet {
             ebss as usize
         );
         trace!("[kernel] mapping .text section");
+        let mut text_perm = MapPermission::R | MapPermission::X;
+        // gdbstub 需要往代码段中写入断点指令
+        if cfg!(feature = "gdbstub") {
+            text_perm |= MapPermission::W;
+        }
         memory_set.push(
             MapArea::new(
                 (stext as usize).into(),
                 (etext as usize).into(),
                 MapType::Identical,
-                MapPermission::R | MapPermission::X,
+                text_perm,
             ),
             None,
         );
@@ -280,6 +285,18 @@ impl MemorySet {
             ),
             None,
         );
+        trace!("[kernel] mapping memory-mapped registers");
+        for &(start, len) in MMIO {
+            memory_set.push(
+                MapArea::new(
+                    start.into(),
+                    (start + len).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
         memory_set
     }
     pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
@@ -320,39 +337,48 @@ impl MemorySet {
             }
         }
 
-        //map user stack with U flags
+        // 用户栈与 Trap 上下文不在这里映射
+        // 它们属于每个线程各自的资源, 在创建线程时才分配, 见 TaskUserRes
+        // 这里只返回用户栈区域的基址: 在 ELF 最高的段之上再留出一个保护页
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
+        let mut user_stack_base: usize = max_end_va.into();
         // guard page
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        memory_set.push(
-            MapArea::new(
-                user_stack_bottom.into(),
-                user_stack_top.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W | MapPermission::U,
-            ),
-            None,
-        );
-        // map TrapContext
-        memory_set.push(
-            MapArea::new(
-                TRAP_CONTEXT.into(),
-                TRAMPOLINE.into(),
-                MapType::Framed,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        user_stack_base += PAGE_SIZE;
 
         (
             memory_set,
-            user_stack_top,
+            user_stack_base,
             elf.header.pt2.entry_point() as usize,
         )
     }
 
+    // 移除以 start_vpn 开头的逻辑段, 并回收其占用的物理页帧
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            self.areas.remove(idx);
+        }
+    }
+
+    // 回收应用地址空间中所有逻辑段占用的物理页帧
+    // 多级页表本身占用的页帧要等到 MemorySet 被 drop 时才回收
+    pub fn recycle_data_pages(&mut self) {
+        self.areas.clear();
+    }
+
+    pub fn token(&self) -> usize {
+        self.page_table.token()
+    }
+
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        self.page_table.translate(vpn)
+    }
+
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -372,21 +398,29 @@ impl MemorySet {
 
 // 创建内核地址空间的全局实例
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinNoIrq<MemorySet>> =
+        Arc::new(SpinNoIrq::new(MemorySet::new_kernel()));
+}
+
+pub fn kernel_token() -> usize {
+    KERNEL_SPACE.lock().token()
 }
 
+// 检查内核各段的映射权限, 每次启动(包括测试内核)时在 mm::init 中执行
 pub fn remap_test() {
-    let kernel_space = KERNEL_SPACE.exclusive_access();
+    let kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
-    // text不允许写
-    assert!(!kernel_space
-        .page_table
-        .translate(mid_text.floor())
-        .unwrap()
-        .writable());
+    // text不允许写, 启用 gdbstub 时除外
+    assert_eq!(
+        kernel_space
+            .page_table
+            .translate(mid_text.floor())
+            .unwrap()
+            .writable(),
+        cfg!(feature = "gdbstub")
+    );
     // rodata不允许写
     assert!(!kernel_space
         .page_table
@@ -1,21 +1,26 @@
-use memory_set::KERNEL_SPACE;
-
 mod address;
 pub mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod uaccess;
+
+pub use address::{PhysPageAccess, PhysPageNum, VirtAddr};
+pub use memory_set::{kernel_token, MapPermission, MemorySet, KERNEL_SPACE};
+#[cfg(feature = "gdbstub")]
+pub use page_table::{debug_read_byte, debug_write_byte};
+pub use page_table::{user_read_byte, user_write_byte};
+pub use uaccess::{copy_from_user, copy_to_user, read_from_user, write_to_user};
 
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
-    //heap_allocator::heap_test();
     frame_allocator::init_frame_allocator();
 
     // 通过new_kernel创建一个内核地址空间,并用Arc<UPSafeCell<T>>封装起来
     // 然后通过exclusive_access获取一个&mut MemorySet
     // 然后通过activate将satp CSR进行设置,激活SV39分页模式
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
 
     memory_set::remap_test();
 }
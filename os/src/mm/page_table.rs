@@ -1,83 +1,13 @@
 use super::{
-    address::{PhysPageNum, VirtPageNum},
+    address::{KernelMemory, PhysPageAccess, PhysPageNum, VirtAddr, VirtPageNum},
     frame_allocator::{self, frame_alloc, FrameTracker},
 };
 use alloc::vec;
 use alloc::vec::Vec;
-use bitflags::*;
+use mm_core::page_table;
 
-/*
-Page Table Entry, 每个页表项为64b, 即8字节
-
-Reserved: [63:54]
-PPN[2]:   [53:28]
-PPN[1]:   [27:19]
-PPN[0]:   [18:10]
-RSW:      [9:8]
-DAGUXWRV: [7:0]
-
-三级页表:
-假设有虚拟地址 VA(由VPN_0, VPN_1, VPN_2, offset等组成)
-- 首先会记录装载「当前所用的一级页表的物理页」的页号到 satp 寄存器中
-- 把 VPN_0 作为偏移在一级页表的物理页中找到二级页表的物理页号
-- 把 VPN_1 作为偏移在二级页表的物理页中找到三级页表的物理页号
-- 把 VPN_2 作为偏移在三级页表的物理页中找到要访问位置的物理页号
-物理页号对应的物理页基址（即物理页号左移12位）加上offset 就是VA对应的PA
-
-*/
-
-#[derive(Copy, Clone, Debug)]
-#[repr(C)]
-pub struct PageTableEntry {
-    pub bits: usize,
-}
-
-bitflags! {
-    #[derive(Debug, PartialEq)]
-    pub struct PTEFlags:u8 {
-        const V = 1 << 0;// 仅当V为1时, 页表项才是合法的
-        const R = 1 << 1;//对应的虚拟页面是否可读
-        const W = 1 << 2;//对应的虚拟页面是否可写
-        const X = 1 << 3;//对应的虚拟页面是否可执行
-        const U = 1 << 4;//对应的虚拟页面,在CPU处于U时是否可访问
-        const G = 1 << 5;//reserve
-        const A = 1 << 6;//Accessed,处理器动态地 记录自从页表项上的这一位被清零之后,页表项对应的虚拟页面是否被访问过
-        const D = 1 << 7;//Dirty,处理器动态地 记录自从页表项上的这一位被清零之后,页表项对应的虚拟页面是否被修改过
-    }
-}
-
-impl PageTableEntry {
-    pub fn new(ppn: PhysPageNum, flags: PTEFlags) -> Self {
-        PageTableEntry {
-            bits: ppn.0 << 10 | flags.bits() as usize,
-        }
-    }
-
-    pub fn empty() -> Self {
-        Self { bits: 0 }
-    }
-
-    pub fn ppn(&self) -> PhysPageNum {
-        (self.bits >> 10 & ((1usize << 44) - 1)).into()
-    }
-    pub fn flags(&self) -> PTEFlags {
-        PTEFlags::from_bits(self.bits as u8).unwrap()
-    }
-
-    pub fn is_valid(&self) -> bool {
-        (self.flags() & PTEFlags::V) != PTEFlags::empty()
-    }
-
-    pub fn readable(&self) -> bool {
-        (self.flags() & PTEFlags::R) != PTEFlags::empty()
-    }
-    pub fn writable(&self) -> bool {
-        (self.flags() & PTEFlags::W) != PTEFlags::empty()
-    }
-    pub fn executable(&self) -> bool {
-        (self.flags() & PTEFlags::X) != PTEFlags::empty()
-    }
-}
+// 页表项的编码与 Sv39 页表的遍历见 mm_core::page_table
+pub use mm_core::page_table::{PTEFlags, PageTableEntry};
 
 pub struct PageTable {
     root_ppn: PhysPageNum,
@@ -98,45 +28,18 @@ impl PageTable {
     // 在多级页表找到一个虚拟页号对应的页表项的可变引用
     // 如果在遍历的过程中发现有节点尚未创建则会新建一个节点
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
-        let idxs = vpn.indexes();
-        let mut ppn = self.root_ppn;
-        let mut ret: Option<&mut PageTableEntry> = None;
-        //for (i, item) in idxs.iter_mut().enumerate() {
-        //let pte = &mut ppn.get_pte_array()[*item];
-        for i in 0..3 {
-            let pte = &mut ppn.get_pte_array()[idxs[i]];
-            if i == 2 {
-                ret = Some(pte);
-                break;
-            }
-
-            if !pte.is_valid() {
-                let frame = frame_alloc().unwrap();
-                *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
-                self.frames.push(frame);
-            }
-            ppn = pte.ppn();
-        }
-        ret
+        let frames = &mut self.frames;
+        page_table::find_pte_create(&KernelMemory, self.root_ppn, vpn, || {
+            let frame = frame_alloc()?;
+            let ppn = frame.ppn;
+            frames.push(frame);
+            Some(ppn)
+        })
     }
 
     //当找不到合法叶子节点的时候不会新建叶子节点而是直接返回 None 即查找失败
     fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
-        let idxs = vpn.indexes();
-        let mut ppn = self.root_ppn;
-        let mut ret: Option<&mut PageTableEntry> = None;
-        for i in 0..3 {
-            let pte = &mut ppn.get_pte_array()[idxs[i]];
-            if i == 2 {
-                ret = Some(pte);
-                break;
-            }
-            if !pte.is_valid() {
-                return None;
-            }
-            ppn = pte.ppn();
-        }
-        ret
+        page_table::find_pte(&KernelMemory, self.root_ppn, vpn)
     }
 
     // 在多级页表中插入一个键值对:建立va pa的映射关系
@@ -148,7 +51,7 @@ impl PageTable {
     // 来删除一个键值对:拆除va pa的映射关系
     pub fn unmap(&mut self, vpn: VirtPageNum) {
         let pte = self.find_pte(vpn).unwrap();
-        assert!(!pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
 
@@ -157,7 +60,7 @@ impl PageTable {
     // frames 字段为空, 即实际不控制任何资源
     pub fn from_token(satp: usize) -> Self {
         Self {
-            root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
+            root_ppn: page_table::root_ppn_from_token(satp),
             frames: Vec::new(),
         }
     }
@@ -167,6 +70,46 @@ impl PageTable {
     }
 
     pub fn token(&self) -> usize {
-        8usize << 60 | self.root_ppn.0
+        page_table::token(self.root_ppn)
     }
 }
+
+// 查找应用地址空间中 va 所在的页, 要求该页合法且用户态可访问
+fn user_pte(token: usize, va: usize) -> Option<PageTableEntry> {
+    let page_table = PageTable::from_token(token);
+    page_table
+        .translate(VirtAddr::from(va).floor())
+        .filter(|pte| pte.is_valid() && pte.is_user())
+}
+
+// 逐字节读写应用地址空间, 会检查页表项的权限, 不可访问时返回 None
+// 用于模拟执行应用的访存指令, 因此不能像 translated_ref 那样直接 unwrap
+pub fn user_read_byte(token: usize, va: usize) -> Option<u8> {
+    let pte = user_pte(token, va).filter(|pte| pte.readable())?;
+    Some(pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()])
+}
+
+pub fn user_write_byte(token: usize, va: usize, value: u8) -> Option<()> {
+    let pte = user_pte(token, va).filter(|pte| pte.writable())?;
+    pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()] = value;
+    Some(())
+}
+
+// 供调试器逐字节读写任意地址空间, 只要求页表项合法, 不检查权限
+// 物理内存在内核地址空间中是恒等映射的, 因此可以直接通过物理地址访问
+#[cfg(feature = "gdbstub")]
+pub fn debug_read_byte(token: usize, va: usize) -> Option<u8> {
+    let pte = PageTable::from_token(token)
+        .translate(VirtAddr::from(va).floor())
+        .filter(|pte| pte.is_valid())?;
+    Some(pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()])
+}
+
+#[cfg(feature = "gdbstub")]
+pub fn debug_write_byte(token: usize, va: usize, value: u8) -> Option<()> {
+    let pte = PageTable::from_token(token)
+        .translate(VirtAddr::from(va).floor())
+        .filter(|pte| pte.is_valid())?;
+    pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()] = value;
+    Some(())
+}
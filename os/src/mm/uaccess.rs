@@ -0,0 +1,124 @@
+//! 内核与应用地址空间之间的数据拷贝
+//!
+//! 内核与应用处于不同的地址空间, 内核不能直接解引用用户指针
+//! 这里逐页查询应用页表, 检查 U 位与读写权限, 再通过物理地址完成拷贝
+//! 用户指针不合法时返回 Err, 由系统调用转换为 -EFAULT, 而不是让内核 panic
+//!
+//! 真正的拷贝由 __copy_user 完成, 它的每条访存指令都登记在 __ex_table 中
+//! 若页表项指向了无法访问的物理地址, kernel_trap_handler 会跳转到修复代码, 同样返回 Err
+
+use super::address::{PhysPageAccess, StepByOne, VirtAddr};
+use super::page_table::{PageTable, PageTableEntry};
+use core::arch::global_asm;
+use kernel_test::kernel_test;
+
+global_asm!(include_str!("uaccess.S"));
+
+extern "C" {
+    // 返回未能拷贝的字节数, 正常时为 0
+    fn __copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+}
+
+// 用户指针不合法
+#[derive(Debug)]
+pub struct BadAddress;
+
+// 将应用地址空间中 [ptr, ptr + len) 拆分为若干个不跨页的物理地址区间
+// 对每个区间调用 f(物理地址, 在缓冲区中的偏移, 长度)
+fn for_each_user_chunk(
+    token: usize,
+    ptr: usize,
+    len: usize,
+    writable: bool,
+    mut f: impl FnMut(usize, usize, usize) -> Result<(), BadAddress>,
+) -> Result<(), BadAddress> {
+    let page_table = PageTable::from_token(token);
+    let end = ptr.checked_add(len).ok_or(BadAddress)?;
+    let mut start = ptr;
+    while start < end {
+        let start_va = VirtAddr::from(start);
+        let mut vpn = start_va.floor();
+        let pte: PageTableEntry = page_table
+            .translate(vpn)
+            .filter(|pte| pte.is_valid() && pte.is_user())
+            .filter(|pte| {
+                if writable {
+                    pte.writable()
+                } else {
+                    pte.readable()
+                }
+            })
+            .ok_or(BadAddress)?;
+        vpn.step();
+        let page_end: VirtAddr = vpn.into();
+        let chunk_end = end.min(page_end.into());
+        let pa = pte.ppn().get_bytes_array().as_ptr() as usize + start_va.page_offset();
+        f(pa, start - ptr, chunk_end - start)?;
+        start = chunk_end;
+    }
+    Ok(())
+}
+
+// 从应用地址空间 src 处拷贝 dst.len() 个字节
+pub fn copy_from_user(token: usize, dst: &mut [u8], src: *const u8) -> Result<(), BadAddress> {
+    for_each_user_chunk(token, src as usize, dst.len(), false, |pa, offset, len| {
+        let left = unsafe { __copy_user(dst[offset..].as_mut_ptr(), pa as *const u8, len) };
+        if left == 0 {
+            Ok(())
+        } else {
+            Err(BadAddress)
+        }
+    })
+}
+
+// 将 src 拷贝到应用地址空间的 dst 处
+pub fn copy_to_user(token: usize, dst: *mut u8, src: &[u8]) -> Result<(), BadAddress> {
+    for_each_user_chunk(token, dst as usize, src.len(), true, |pa, offset, len| {
+        let left = unsafe { __copy_user(pa as *mut u8, src[offset..].as_ptr(), len) };
+        if left == 0 {
+            Ok(())
+        } else {
+            Err(BadAddress)
+        }
+    })
+}
+
+// 按值读取应用地址空间中的一个 T, T 的布局需要与用户态保持一致
+pub fn read_from_user<T: Copy>(token: usize, src: *const T) -> Result<T, BadAddress> {
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+    copy_from_user(token, dst, src as *const u8)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+pub fn write_to_user<T>(token: usize, dst: *mut T, value: &T) -> Result<(), BadAddress> {
+    let src = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    copy_to_user(token, dst as *mut u8, src)
+}
+
+// 直接以内核中未映射的地址调用 __copy_user, 经由 __ex_table 的修复代码返回
+// 系统调用的路径会先在软件中检查页表项, 因此只有页表项本身有误时才会走到这里
+#[kernel_test]
+fn copy_user_fixup_test() {
+    let mut buf = [0u8; 8];
+    let src = [1u8; 8];
+    assert_eq!(
+        unsafe { __copy_user(buf.as_mut_ptr(), src.as_ptr(), buf.len()) },
+        0
+    );
+    assert_eq!(buf, src);
+    // 读出错: 地址 0 不在内核地址空间中
+    assert_eq!(
+        unsafe { __copy_user(buf.as_mut_ptr(), core::ptr::null(), buf.len()) },
+        buf.len()
+    );
+    // 写出错
+    assert_eq!(
+        unsafe { __copy_user(core::ptr::null_mut(), src.as_ptr(), src.len()) },
+        src.len()
+    );
+}
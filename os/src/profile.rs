@@ -0,0 +1,108 @@
+//! 基于时钟中断的采样分析器
+//!
+//! 打开 profile feature(`make run PROFILE=1`)后, 每次时钟中断都记录被打断处的 sepc 与当前线程,
+//! 所有应用结束后在关机前把全部样本打印出来, 每个样本一行:
+//!
+//! ```text
+//! [profile] ---------------- samples ----------------
+//! [profile] 2 samples, 0 dropped, 10ms period
+//! [profile] 0x0000000000010234 2 1 00hello_world
+//! [profile] 0x0000000080201a3c - - [kernel]
+//! ```
+//!
+//! 依次为 pc、pid、tid 与应用名, 内核中的样本应用名为 `[kernel]`, 不属于任何线程时 pid/tid 为 `-`
+//! 由 `cargo xtask profile` 用内核与应用的 ELF 把 pc 转换为函数, 输出平坦的统计或者 flamegraph 使用的折叠栈
+//! 目前内核只在启动阶段打开内核态中断, 因此样本几乎都来自用户态
+
+use crate::sync::SpinNoIrq;
+use crate::task::{current_task, current_task_ids};
+use crate::timer::TICKS_PER_SEC;
+use alloc::string::{String, ToString};
+
+// 样本缓冲区的容量, 每个 hart 每秒 TICKS_PER_SEC 个样本
+const MAX_SAMPLES: usize = 8192;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    pc: usize,
+    // (pid, tid)
+    ids: Option<(usize, usize)>,
+    // 用户态样本所属的应用, 内核样本为 None
+    app: Option<&'static str>,
+}
+
+struct Profile {
+    samples: [Sample; MAX_SAMPLES],
+    len: usize,
+    // 缓冲区满后丢弃的样本数
+    dropped: usize,
+}
+
+impl Profile {
+    fn push(&mut self, sample: Sample) {
+        if self.len == MAX_SAMPLES {
+            self.dropped += 1;
+            return;
+        }
+        self.samples[self.len] = sample;
+        self.len += 1;
+    }
+}
+
+// 时钟中断中会访问, 因此使用 SpinNoIrq 而不是 UPSafeCell, 样本缓冲区也不能在中断中分配
+static PROFILE: SpinNoIrq<Profile> = SpinNoIrq::new(Profile {
+    samples: [Sample {
+        pc: 0,
+        ids: None,
+        app: None,
+    }; MAX_SAMPLES],
+    len: 0,
+    dropped: 0,
+});
+
+// 应用在用户态被时钟中断打断时调用
+pub fn sample_user(pc: usize) {
+    let app = current_task()
+        .and_then(|task| task.process.upgrade())
+        .map(|process| process.name);
+    PROFILE.lock().push(Sample {
+        pc,
+        ids: current_task_ids(),
+        app,
+    });
+}
+
+// 内核被时钟中断打断时调用, 此时相关结构体可能正被借用, 只记录能取到的信息
+pub fn sample_kernel(pc: usize) {
+    PROFILE.lock().push(Sample {
+        pc,
+        ids: current_task_ids(),
+        app: None,
+    });
+}
+
+// 关机前打印全部样本, 与 app exit summary 一样是报告, 不受 LOG 等级的影响
+pub fn dump() {
+    let profile = PROFILE.lock();
+    println!("[profile] ---------------- samples ----------------");
+    println!(
+        "[profile] {} samples, {} dropped, {}ms period",
+        profile.len,
+        profile.dropped,
+        1000 / TICKS_PER_SEC
+    );
+    for sample in &profile.samples[..profile.len] {
+        let (pid, tid) = sample
+            .ids
+            .map_or((String::from("-"), String::from("-")), |(pid, tid)| {
+                (pid.to_string(), tid.to_string())
+            });
+        println!(
+            "[profile] {:#018x} {} {} {}",
+            sample.pc,
+            pid,
+            tid,
+            sample.app.unwrap_or("[kernel]")
+        );
+    }
+}
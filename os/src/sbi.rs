@@ -3,6 +3,12 @@ pub fn console_putchar(c: usize) {
     sbi_rt::legacy::console_putchar(c);
 }
 
+// 没有输入时返回 usize::MAX
+pub fn console_getchar() -> usize {
+    #[allow(deprecated)]
+    sbi_rt::legacy::console_getchar()
+}
+
 pub fn shutdown(failure: bool) -> ! {
     use sbi_rt::{system_reset, NoReason, Shutdown, SystemFailure};
     if !failure {
@@ -19,3 +25,15 @@ pub fn set_timer(timer: usize) {
     //sbi_rt::sbi_call(SBI_SET_TIMER, timer, 0, 0);
     sbi_rt::set_timer(timer as _);
 }
+
+// hartid 不存在
+pub const SBI_ERR_INVALID_PARAM: isize = -3;
+
+// 通过 SBI HSM 扩展启动 hart, 它会以 S 态从 start_addr 开始执行, a0 = hartid, a1 = opaque
+// 失败时返回 SBI 的错误码
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> Result<(), isize> {
+    match sbi_rt::hart_start(hartid, start_addr, opaque).error as isize {
+        0 => Ok(()),
+        error => Err(error),
+    }
+}
@@ -1,17 +1,91 @@
+use crate::config::{BOOT_STACK_SIZE, KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
+use crate::ksyms;
+use crate::task::{checked_kernel_stack_position, hart_id};
 use core::arch::asm;
 
+// 回溯的最大深度, 防止栈被破坏时陷入死循环
+const MAX_DEPTH: usize = 32;
+
+// fp 所在的内核栈的范围 [bottom, top]
+// 内核只会运行在当前 hart 的启动栈或某个线程的内核栈上, 它们的位置都是固定的
+// 这里直接根据地址计算, 不访问任何需要加锁的数据结构, 以便在 panic 时也能使用
+fn stack_bounds(fp: usize) -> Option<(usize, usize)> {
+    extern "C" {
+        fn boot_stack_top();
+    }
+    let boot_top = boot_stack_top as usize - hart_id() * BOOT_STACK_SIZE;
+    let boot_bottom = boot_top - BOOT_STACK_SIZE;
+    if fp > boot_bottom && fp <= boot_top {
+        return Some((boot_bottom, boot_top));
+    }
+    // 内核栈从 TRAMPOLINE 往下依次排布, 相邻的内核栈之间有一个保护页
+    // fp 可能是任意值, 计算时不能溢出
+    let kstack_id = TRAMPOLINE.checked_sub(fp)? / (KERNEL_STACK_SIZE + PAGE_SIZE);
+    let (bottom, top) = checked_kernel_stack_position(kstack_id)?;
+    if fp > bottom && fp <= top {
+        return Some((bottom, top));
+    }
+    None
+}
+
+// is_ra 表示 pc 是栈帧中保存的返回地址
+fn print_frame(depth: usize, pc: usize, is_ra: bool) {
+    // ra 指向 call 的下一条指令, 用 ra - 1 查找, 避免 call 位于函数末尾时找到下一个函数
+    let lookup_pc = if is_ra { pc.saturating_sub(1) } else { pc };
+    match ksyms::lookup(lookup_pc) {
+        Some((name, offset)) => {
+            let offset = offset + pc - lookup_pc;
+            println!("#{:<2} 0x{:016x} {}+{:#x}", depth, pc, name, offset);
+        }
+        None => {
+            println!("#{:<2} 0x{:016x} ???", depth, pc);
+        }
+    }
+}
+
 pub unsafe fn print_stack_trace() {
-    let mut fp: *const usize = core::ptr::null_mut();
+    let mut fp: usize;
     asm!("mv {}, fp", out(reg) fp);
+    print_stack_trace_from(None, fp);
+}
 
+// 从给定的 fp 开始沿栈帧链回溯, 用于打印 trap 发生处的调用栈
+// pc 为出错指令的地址, 会作为第一帧打印
+// 只在 fp 所在的内核栈范围内回溯, 栈帧链被破坏时也不会访问到栈以外的内存
+pub unsafe fn print_stack_trace_from(pc: Option<usize>, mut fp: usize) {
     println!("=========BACKTRACE START=========");
-    while !fp.is_null() {
-        let saved_ra = *fp.sub(1);
-        let saved_fp = *fp.sub(2);
-
-        println!("ra = 0x{:016x}, fp = 0x{:016x}", saved_ra, saved_fp);
+    let mut depth = 0;
+    if let Some(pc) = pc {
+        print_frame(depth, pc, false);
+        depth += 1;
+    }
+    let (bottom, top) = match stack_bounds(fp) {
+        Some(bounds) => bounds,
+        None => {
+            println!("fp 0x{:016x} is not on a kernel stack", fp);
+            println!("=========BACKTRACE END=========");
+            return;
+        }
+    };
+    while depth < MAX_DEPTH {
+        // fp 必须按 8 字节对齐, 且 ra/fp 两项都位于栈内
+        if fp % 8 != 0 || fp < bottom + 16 || fp > top {
+            break;
+        }
+        let frame = fp as *const usize;
+        let saved_ra = *frame.sub(1);
+        let saved_fp = *frame.sub(2);
+        if saved_ra == 0 {
+            break;
+        }
+        print_frame(depth, saved_ra, true);
+        depth += 1;
 
-        fp = saved_fp as *const usize;
+        // 栈向低地址增长, 上一帧的 fp 一定更大
+        if saved_fp <= fp {
+            break;
+        }
+        fp = saved_fp;
     }
     println!("=========BACKTRACE END=========");
 }
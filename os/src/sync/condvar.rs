@@ -0,0 +1,50 @@
+use crate::sync::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::{collections::VecDeque, sync::Arc};
+
+// 条件变量, 需要与互斥锁配合使用
+pub struct Condvar {
+    pub inner: UPSafeCell<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(CondvarInner {
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    // 唤醒一个等待在该条件变量上的线程, 没有等待者时什么也不做
+    pub fn signal(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    // 阻塞当前线程, 直到被 signal 唤醒, 被终止信号打断时返回 false
+    // 与之配合的互斥锁由调用者在等待前释放、被唤醒后重新获取, 以便记入死锁检测
+    pub fn wait(&self) -> bool {
+        let task = current_task().unwrap();
+        let mut inner = self.inner.exclusive_access();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        drop(inner);
+        block_current_and_run_next();
+        let mut inner = self.inner.exclusive_access();
+        match inner.wait_queue.iter().position(|t| Arc::ptr_eq(t, &task)) {
+            Some(pos) => {
+                inner.wait_queue.remove(pos);
+                false
+            }
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use log::error;
+
+// 基于银行家算法的死锁检测
+// 每个进程为互斥锁和信号量各维护一份, 资源的下标即对应同步原语的 id
+// 线程以 tid 为下标
+pub struct DeadlockDetector {
+    // available[j]: 资源 j 当前剩余可用的数量
+    available: Vec<usize>,
+    // allocation[i][j]: 线程 i 已经持有的资源 j 的数量
+    allocation: Vec<Vec<usize>>,
+    // need[i][j]: 线程 i 还需要(正在请求)的资源 j 的数量
+    need: Vec<Vec<usize>>,
+}
+
+impl DeadlockDetector {
+    pub fn new() -> Self {
+        Self {
+            available: Vec::new(),
+            allocation: Vec::new(),
+            need: Vec::new(),
+        }
+    }
+
+    // 新建(或复用) id 对应的资源, 初始可用数量为 count
+    pub fn add_resource(&mut self, id: usize, count: usize) {
+        if id >= self.available.len() {
+            self.available.resize(id + 1, 0);
+            for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+                row.resize(id + 1, 0);
+            }
+        }
+        self.available[id] = count;
+        for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+            row[id] = 0;
+        }
+    }
+
+    fn ensure_thread(&mut self, tid: usize) {
+        while self.allocation.len() <= tid {
+            self.allocation.push(vec![0; self.available.len()]);
+            self.need.push(vec![0; self.available.len()]);
+        }
+    }
+
+    // 线程 tid 开始请求一个资源 id
+    pub fn request(&mut self, tid: usize, id: usize) {
+        self.ensure_thread(tid);
+        self.need[tid][id] += 1;
+    }
+
+    // 撤销 request, 用于检测到不安全状态后拒绝本次请求
+    pub fn cancel(&mut self, tid: usize, id: usize) {
+        self.ensure_thread(tid);
+        if !decrement(&mut self.need[tid][id]) {
+            error!(
+                "[kernel] deadlock detector: tid {} cancels resource {} it never requested",
+                tid, id
+            );
+        }
+    }
+
+    // 请求得到满足, 线程 tid 真正持有了资源 id
+    pub fn acquire(&mut self, tid: usize, id: usize) {
+        self.ensure_thread(tid);
+        if !decrement(&mut self.available[id]) {
+            error!(
+                "[kernel] deadlock detector: resource {} is not available for tid {}",
+                id, tid
+            );
+        }
+        decrement(&mut self.need[tid][id]);
+        self.allocation[tid][id] += 1;
+    }
+
+    // 线程 tid 归还资源 id, tid 并没有持有资源 id 时返回 false, 不做任何修改
+    pub fn release(&mut self, tid: usize, id: usize) -> bool {
+        self.ensure_thread(tid);
+        if !decrement(&mut self.allocation[tid][id]) {
+            return false;
+        }
+        self.available[id] += 1;
+        true
+    }
+
+    // 资源 id 的可用数量加一, 用于信号量: up 的线程不一定曾经 down 过
+    pub fn produce(&mut self, id: usize) {
+        self.available[id] += 1;
+    }
+
+    // 安全性检查:
+    // 1. work = available, 所有线程的 finish = false
+    // 2. 找到一个 finish == false 且 need <= work 的线程
+    //    假设它能运行结束并归还所有资源: work += allocation, finish = true
+    // 3. 重复第 2 步, 直到找不到这样的线程
+    // 若最终所有线程的 finish 都为 true, 则系统处于安全状态
+    pub fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut finish = vec![false; self.need.len()];
+        loop {
+            let next = (0..self.need.len()).find(|&i| {
+                !finish[i] && self.need[i].iter().zip(work.iter()).all(|(n, w)| n <= w)
+            });
+            match next {
+                Some(i) => {
+                    for (w, a) in work.iter_mut().zip(self.allocation[i].iter()) {
+                        *w += a;
+                    }
+                    finish[i] = true;
+                }
+                None => break,
+            }
+        }
+        finish.iter().all(|f| *f)
+    }
+}
+
+// 减一并返回 true, 已经为 0 时保持不变并返回 false
+fn decrement(value: &mut usize) -> bool {
+    match value.checked_sub(1) {
+        Some(v) => {
+            *value = v;
+            true
+        }
+        None => false,
+    }
+}
@@ -1,3 +1,13 @@
+mod condvar;
+mod deadlock;
+mod mutex;
+mod semaphore;
+mod spin;
 mod up;
 
+pub use condvar::Condvar;
+pub use deadlock::DeadlockDetector;
+pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use semaphore::Semaphore;
+pub use spin::SpinNoIrq;
 pub use up::UPSafeCell;
@@ -0,0 +1,123 @@
+use super::UPSafeCell;
+use crate::task::{
+    block_current_and_run_next, current_fatal_signal_pending, current_task,
+    suspend_current_and_run_next, wakeup_task, TaskControlBlock,
+};
+use alloc::{collections::VecDeque, sync::Arc};
+
+// 提供给用户态的互斥锁
+// 锁记录持有者的 tid, 只有持有者才能 unlock
+pub trait Mutex: Sync + Send {
+    // 等待被终止信号打断时返回 false, 此时没有获得锁
+    fn lock(&self, tid: usize) -> bool;
+    // 锁没有被 tid 持有时返回 false, 锁的状态保持不变
+    fn unlock(&self, tid: usize) -> bool;
+}
+
+// 自旋(让权)锁: 拿不到锁时主动 yield, 下次被调度时再尝试
+pub struct MutexSpin {
+    // 持有者的 tid, 未上锁时为 None
+    owner: UPSafeCell<Option<usize>>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            owner: unsafe { UPSafeCell::new(None) },
+        }
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self, tid: usize) -> bool {
+        loop {
+            let mut owner = self.owner.exclusive_access();
+            if owner.is_some() {
+                drop(owner);
+                suspend_current_and_run_next();
+                if current_fatal_signal_pending() {
+                    return false;
+                }
+                continue;
+            } else {
+                *owner = Some(tid);
+                return true;
+            }
+        }
+    }
+
+    fn unlock(&self, tid: usize) -> bool {
+        let mut owner = self.owner.exclusive_access();
+        if *owner != Some(tid) {
+            return false;
+        }
+        *owner = None;
+        true
+    }
+}
+
+// 阻塞锁: 拿不到锁时线程进入等待队列并被阻塞, 不再参与调度
+// 直到持有者 unlock 时将它唤醒
+pub struct MutexBlocking {
+    inner: UPSafeCell<MutexBlockingInner>,
+}
+
+pub struct MutexBlockingInner {
+    // 持有者的 tid, 未上锁时为 None
+    owner: Option<usize>,
+    // 等待者的 tid 与线程
+    wait_queue: VecDeque<(usize, Arc<TaskControlBlock>)>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(MutexBlockingInner {
+                    owner: None,
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self, tid: usize) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.owner.is_none() {
+            mutex_inner.owner = Some(tid);
+            return true;
+        }
+        mutex_inner
+            .wait_queue
+            .push_back((tid, current_task().unwrap()));
+        drop(mutex_inner);
+        block_current_and_run_next();
+        // 被 unlock 唤醒时锁已经移交过来, 否则是被终止信号唤醒的, 要离开等待队列
+        let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.owner == Some(tid) {
+            return true;
+        }
+        mutex_inner
+            .wait_queue
+            .retain(|(waiting_tid, _)| *waiting_tid != tid);
+        false
+    }
+
+    fn unlock(&self, tid: usize) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.owner != Some(tid) {
+            return false;
+        }
+        // 锁直接移交给等待队列中的第一个线程
+        mutex_inner.owner = match mutex_inner.wait_queue.pop_front() {
+            Some((waking_tid, waking_task)) => {
+                wakeup_task(waking_task);
+                Some(waking_tid)
+            }
+            None => None,
+        };
+        true
+    }
+}
@@ -0,0 +1,62 @@
+use crate::sync::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::{collections::VecDeque, sync::Arc};
+
+// 信号量
+// count >= 0 时表示可用资源的数量
+// count < 0 时其绝对值表示等待队列中线程的数量
+pub struct Semaphore {
+    pub inner: UPSafeCell<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    pub count: isize,
+    pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize) -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(SemaphoreInner {
+                    count: res_count as isize,
+                    wait_queue: VecDeque::new(),
+                })
+            },
+        }
+    }
+
+    // V 操作
+    pub fn up(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.count += 1;
+        if inner.count <= 0 {
+            if let Some(task) = inner.wait_queue.pop_front() {
+                wakeup_task(task);
+            }
+        }
+    }
+
+    // P 操作, 等待被终止信号打断时返回 false, 此时没有获得资源
+    pub fn down(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.count -= 1;
+        if inner.count >= 0 {
+            return true;
+        }
+        let task = current_task().unwrap();
+        inner.wait_queue.push_back(Arc::clone(&task));
+        drop(inner);
+        block_current_and_run_next();
+        // 被 up 唤醒时已经离开等待队列, 仍在队列中说明是被终止信号唤醒的
+        let mut inner = self.inner.exclusive_access();
+        match inner.wait_queue.iter().position(|t| Arc::ptr_eq(t, &task)) {
+            Some(pos) => {
+                inner.wait_queue.remove(pos);
+                inner.count += 1;
+                false
+            }
+            None => true,
+        }
+    }
+}
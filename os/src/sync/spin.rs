@@ -0,0 +1,365 @@
+//! 多核下的自旋锁
+//!
+//! SpinLock 是一个排号自旋锁(ticket lock): 每个 hart 先取一个号, 再等待叫到自己的号
+//! 因此锁会按请求的先后顺序交给各个 hart, 不会有 hart 一直抢不到锁
+//!
+//! SpinNoIrq 在加锁前关闭当前 hart 的中断, 解锁后再恢复
+//! 被中断处理程序访问的数据需要使用它, 否则中断处理程序可能在同一个 hart 上再次加锁, 造成死锁
+//!
+//! debug 模式下会记录每把锁的持有者、每个 hart 当前持有的锁, 以及出现过的加锁顺序
+//! 同一个 hart 重复加锁时直接 panic; 持有 A 获取 B 之前若出现过持有 B 获取 A, 打印加锁顺序颠倒的警告;
+//! 自旋过久时打印持有者信息, 便于排查加锁顺序引起的死锁
+
+use crate::config::MAX_HARTS;
+use crate::task::hart_id;
+use core::cell::{Cell, UnsafeCell};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+pub struct SpinLock<T> {
+    // 下一个可以领取的号
+    next_ticket: AtomicUsize,
+    // 当前被叫到的号
+    now_serving: AtomicUsize,
+    // 持有者的 hartid + 1, 0 表示没有被持有
+    #[cfg(debug_assertions)]
+    holder: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            #[cfg(debug_assertions)]
+            holder: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        debug::before_lock(self.addr(), &self.holder);
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        #[cfg(debug_assertions)]
+        let mut spins = 0usize;
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            #[cfg(debug_assertions)]
+            {
+                spins += 1;
+                if spins % debug::SPIN_REPORT_INTERVAL == 0 {
+                    debug::report_long_spin(self.addr(), &self.holder);
+                }
+            }
+            core::hint::spin_loop();
+        }
+        #[cfg(debug_assertions)]
+        debug::after_lock(self.addr(), &self.holder);
+        SpinLockGuard { lock: self }
+    }
+
+    fn unlock(&self) {
+        #[cfg(debug_assertions)]
+        debug::before_unlock(self.addr(), &self.holder);
+        // 只有持有者会修改 now_serving, 因此不需要 fetch_add
+        let next = self.now_serving.load(Ordering::Relaxed) + 1;
+        self.now_serving.store(next, Ordering::Release);
+    }
+
+    #[cfg(debug_assertions)]
+    fn addr(&self) -> usize {
+        self as *const _ as usize
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+// 加锁期间关闭中断的自旋锁
+pub struct SpinNoIrq<T> {
+    inner: SpinLock<T>,
+}
+
+pub struct SpinNoIrqGuard<'a, T> {
+    // 字段按声明顺序 drop: 先解锁, 再恢复中断
+    guard: SpinLockGuard<'a, T>,
+    _irq: IrqGuard,
+}
+
+impl<T> SpinNoIrq<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: SpinLock::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> SpinNoIrqGuard<'_, T> {
+        // 必须先关中断再加锁, 否则持有锁期间可能被中断打断
+        let irq = IrqGuard::new();
+        SpinNoIrqGuard {
+            guard: self.inner.lock(),
+            _irq: irq,
+        }
+    }
+}
+
+impl<T> Deref for SpinNoIrqGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for SpinNoIrqGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+// 每个 hart 关中断的嵌套层数, 以及最外层关中断之前中断是否打开
+// 只会被所属的 hart 访问, 且访问时中断已经关闭
+struct IrqState {
+    depth: Cell<usize>,
+    enabled: Cell<bool>,
+}
+
+unsafe impl Sync for IrqState {}
+
+static IRQ_STATES: [IrqState; MAX_HARTS] = [const {
+    IrqState {
+        depth: Cell::new(0),
+        enabled: Cell::new(false),
+    }
+}; MAX_HARTS];
+
+// 关中断, drop 时恢复, 可以嵌套
+// 只有最外层的 IrqGuard 被 drop 时, 才会恢复到最初的中断状态
+struct IrqGuard;
+
+impl IrqGuard {
+    fn new() -> Self {
+        let enabled = sstatus::read().sie();
+        unsafe {
+            sstatus::clear_sie();
+        }
+        let state = &IRQ_STATES[hart_id()];
+        if state.depth.get() == 0 {
+            state.enabled.set(enabled);
+        }
+        state.depth.set(state.depth.get() + 1);
+        Self
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        assert!(
+            !sstatus::read().sie(),
+            "interrupts enabled inside SpinNoIrq"
+        );
+        let state = &IRQ_STATES[hart_id()];
+        let depth = state.depth.get();
+        assert!(depth > 0, "unbalanced IrqGuard");
+        state.depth.set(depth - 1);
+        if depth == 1 && state.enabled.get() {
+            unsafe {
+                sstatus::set_sie();
+            }
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+mod debug {
+    use super::{IrqGuard, MAX_HARTS};
+    use crate::task::hart_id;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // 诊断信息都直接输出到控制台, 不经过 log:
+    // 日志系统内部使用 SpinNoIrq, 经过它输出会再次获取正被诊断的锁
+
+    // 每个 hart 最多同时持有的锁的数量
+    const MAX_HELD: usize = 16;
+    // 最多记录的加锁顺序的数量, 记满后不再记录新的顺序
+    const MAX_ORDERS: usize = 256;
+    // 自旋多少次后打印一次持有者信息
+    pub const SPIN_REPORT_INTERVAL: usize = 1 << 24;
+
+    // 每个 hart 当前持有的锁的地址, 0 表示空位
+    // 只会被所属的 hart 修改, 其他 hart 仅在打印诊断信息时读取
+    static HELD: [[AtomicUsize; MAX_HELD]; MAX_HARTS] =
+        [const { [const { AtomicUsize::new(0) }; MAX_HELD] }; MAX_HARTS];
+
+    // 一条加锁顺序: 持有 first 时获取了 second
+    struct LockOrder {
+        first: AtomicUsize,
+        second: AtomicUsize,
+        // 已经报告过与它相反的顺序
+        reported: AtomicBool,
+    }
+
+    // ORDERS[..ORDERS_LEN] 为已经记录的顺序, 只追加不删除
+    // 锁的地址可能被复用(如栈上或堆上的锁), 此时的警告可能是误报
+    static ORDERS: [LockOrder; MAX_ORDERS] = [const {
+        LockOrder {
+            first: AtomicUsize::new(0),
+            second: AtomicUsize::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }; MAX_ORDERS];
+    static ORDERS_LEN: AtomicUsize = AtomicUsize::new(0);
+    // 串行化 ORDERS 的追加, 这里不能再使用 SpinLock
+    static ORDERS_WRITING: AtomicBool = AtomicBool::new(false);
+
+    pub fn before_lock(lock: usize, holder: &AtomicUsize) {
+        let hart = hart_id();
+        if holder.load(Ordering::Relaxed) == hart + 1 {
+            panic!("hart {} locks {:#x} recursively", hart, lock);
+        }
+        check_order(hart, lock);
+    }
+
+    fn find_order(first: usize, second: usize) -> Option<&'static LockOrder> {
+        ORDERS[..ORDERS_LEN.load(Ordering::Acquire)]
+            .iter()
+            .find(|order| {
+                order.first.load(Ordering::Relaxed) == first
+                    && order.second.load(Ordering::Relaxed) == second
+            })
+    }
+
+    fn record_order(first: usize, second: usize) {
+        while ORDERS_WRITING
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let len = ORDERS_LEN.load(Ordering::Relaxed);
+        // 等待期间其他 hart 可能已经记录了同样的顺序
+        if len < MAX_ORDERS && find_order(first, second).is_none() {
+            ORDERS[len].first.store(first, Ordering::Relaxed);
+            ORDERS[len].second.store(second, Ordering::Relaxed);
+            ORDERS_LEN.store(len + 1, Ordering::Release);
+        }
+        ORDERS_WRITING.store(false, Ordering::Release);
+    }
+
+    // 对当前 hart 持有的每把锁 held, 检查之前是否出现过持有 lock 时获取 held, 再记录 held -> lock
+    // 每对相反的顺序只报告一次
+    fn check_order(hart: usize, lock: usize) {
+        let _irq = IrqGuard::new();
+        for held in HELD[hart]
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .filter(|&held| held != 0)
+        {
+            match find_order(lock, held) {
+                Some(order) => {
+                    if !order.reported.swap(true, Ordering::Relaxed) {
+                        println!(
+                            "[spin] lock order inversion: hart {} acquires {:#x} while holding {:#x}, \
+                             but {:#x} was held while acquiring {:#x} before",
+                            hart, lock, held, lock, held
+                        );
+                        print_held(hart);
+                    }
+                }
+                None => {
+                    if find_order(held, lock).is_none() {
+                        record_order(held, lock);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn after_lock(lock: usize, holder: &AtomicUsize) {
+        let hart = hart_id();
+        holder.store(hart + 1, Ordering::Relaxed);
+        // 修改 HELD 期间不能被中断处理程序打断
+        let _irq = IrqGuard::new();
+        match HELD[hart]
+            .iter()
+            .find(|slot| slot.load(Ordering::Relaxed) == 0)
+        {
+            Some(slot) => slot.store(lock, Ordering::Relaxed),
+            None => panic!("hart {} holds too many locks", hart),
+        }
+    }
+
+    pub fn before_unlock(lock: usize, holder: &AtomicUsize) {
+        let hart = hart_id();
+        let owner = holder.load(Ordering::Relaxed);
+        if owner != hart + 1 {
+            panic!(
+                "hart {} unlocks {:#x} held by {}",
+                hart,
+                lock,
+                owner.wrapping_sub(1) as isize
+            );
+        }
+        holder.store(0, Ordering::Relaxed);
+        let _irq = IrqGuard::new();
+        if let Some(slot) = HELD[hart]
+            .iter()
+            .rev()
+            .find(|slot| slot.load(Ordering::Relaxed) == lock)
+        {
+            slot.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // 等待过久时打印锁的持有者以及双方各自持有的锁, 通常是加锁顺序不一致导致的死锁
+    pub fn report_long_spin(lock: usize, holder: &AtomicUsize) {
+        let hart = hart_id();
+        let owner = holder.load(Ordering::Relaxed);
+        println!(
+            "[spin] hart {} still waiting for {:#x}, held by hart {}",
+            hart,
+            lock,
+            owner.wrapping_sub(1) as isize
+        );
+        print_held(hart);
+        if owner != 0 && owner - 1 < MAX_HARTS {
+            print_held(owner - 1);
+        }
+    }
+
+    fn print_held(hart: usize) {
+        print!("[spin] hart {} holds:", hart);
+        HELD[hart]
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .filter(|&lock| lock != 0)
+            .for_each(|lock| {
+                print!(" {:#x}", lock);
+            });
+        println!();
+    }
+}
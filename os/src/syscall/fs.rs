@@ -1,16 +1,58 @@
 //! File and filesystem-related syscalls
 
+use super::EFAULT;
+use crate::console::{stdin_pop, write_bytes};
+use crate::mm::{copy_from_user, copy_to_user};
+use crate::task::current_user_token;
+
+const FD_STDIN: usize = 0;
 const FD_STDOUT: usize = 1;
 
+// 从标准输入读取至多 len 个字节, 不会阻塞, 返回读到的字节数, 没有输入时返回 0
+// 与 waitpid 一样由用户库循环 yield 等待
+pub fn sys_read(fd: usize, buffer: *mut u8, len: usize) -> isize {
+    if fd != FD_STDIN {
+        return -1;
+    }
+    let mut kbuf = [0u8; 64];
+    let mut count = 0;
+    while count < len.min(kbuf.len()) {
+        match stdin_pop() {
+            Some(c) => {
+                kbuf[count] = c;
+                count += 1;
+            }
+            None => break,
+        }
+    }
+    if copy_to_user(current_user_token(), buffer, &kbuf[..count]).is_err() {
+        return -EFAULT;
+    }
+    count as isize
+}
+
+// sys_write 的中转缓冲区大小, 应用的缓冲区分段拷贝到这里再输出
+const WRITE_CHUNK: usize = 256;
+
 pub fn sys_write(fd: usize, buffer: *const u8, len: usize) -> isize {
     match fd {
         FD_STDOUT => {
-            //let str = unsafe { core::str::from_raw_parts(buffer, len) };
-            let slice = unsafe { core::slice::from_raw_parts(buffer, len) };
-            let str = core::str::from_utf8(slice).unwrap();
-            // DO NOT append '\n' or use println
-            // JUST print raw would be better
-            print!("{}", str);
+            // buffer 是用户地址空间中的虚拟地址, 分段拷贝到内核中再输出, 以免按 len 在内核堆上分配
+            // 指针不可访问时返回 -EFAULT, 而不是让内核 panic, 此前的分段已经输出
+            let token = current_user_token();
+            let mut kbuf = [0u8; WRITE_CHUNK];
+            let mut written = 0;
+            while written < len {
+                let chunk = (len - written).min(WRITE_CHUNK);
+                let src = buffer.wrapping_add(written);
+                if copy_from_user(token, &mut kbuf[..chunk], src).is_err() {
+                    return -EFAULT;
+                }
+                // DO NOT append '\n' or use println
+                // JUST print raw would be better
+                write_bytes(&kbuf[..chunk]);
+                written += chunk;
+            }
             len as isize
         }
         _ => {
@@ -4,21 +4,64 @@ use fs::*;
 mod process;
 use process::*;
 
+mod sync;
+use sync::*;
+
+mod syslog;
+use syslog::*;
+
+mod thread;
+use thread::*;
+
+mod trace;
+use trace::sys_strace;
+pub use trace::{strace_enter, strace_exit, STRACE_DEFAULT};
+
+use crate::task::{ExitInfo, SignalAction};
+
+// Bad address, 用户传入的指针不可访问
+const EFAULT: isize = 14;
+
 // const SYSCALL_WRITE: usize = 64;
 // const SYSCALL_EXIT: usize = 93;
 // const SYSCALL_TS: usize = 169;
 // const SYSCALL_YIELD: usize = 124;
 // const SYSCALL_TASK_INFO: usize = 410;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+// 派生 Ord 以便作为每个线程系统调用计数表的键
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 #[non_exhaustive]
 pub enum SyscallID {
     Invalid = -1,
+    Read = 63,
     Write = 64,
     Exit = 93,
     Ts = 169,
+    WaitPid = 260,
     Yield = 124,
+    Syslog = 116,
+    Kill = 129,
+    SigAction = 134,
+    SigProcMask = 135,
+    SigReturn = 139,
+    GetPid = 172,
+    Spawn = 400,
     TaskInfo = 410,
+    EnableDeadlockDetect = 469,
+    ThreadCreate = 1000,
+    GetTid = 1001,
+    WaitTid = 1002,
+    MutexCreate = 1010,
+    MutexLock = 1011,
+    MutexUnlock = 1012,
+    SemaphoreCreate = 1020,
+    SemaphoreUp = 1021,
+    SemaphoreDown = 1022,
+    CondvarCreate = 1030,
+    CondvarSignal = 1031,
+    CondvarWait = 1032,
+    DebugBacktrace = 1100,
+    Strace = 1101,
 }
 
 impl From<SyscallID> for usize {
@@ -30,11 +73,35 @@ impl From<SyscallID> for usize {
 impl From<usize> for SyscallID {
     fn from(val: usize) -> Self {
         match val {
+            63 => Self::Read,
             64 => Self::Write,
             93 => Self::Exit,
             169 => Self::Ts,
+            260 => Self::WaitPid,
             124 => Self::Yield,
+            116 => Self::Syslog,
+            129 => Self::Kill,
+            134 => Self::SigAction,
+            135 => Self::SigProcMask,
+            139 => Self::SigReturn,
+            172 => Self::GetPid,
+            400 => Self::Spawn,
             410 => Self::TaskInfo,
+            469 => Self::EnableDeadlockDetect,
+            1000 => Self::ThreadCreate,
+            1001 => Self::GetTid,
+            1002 => Self::WaitTid,
+            1010 => Self::MutexCreate,
+            1011 => Self::MutexLock,
+            1012 => Self::MutexUnlock,
+            1020 => Self::SemaphoreCreate,
+            1021 => Self::SemaphoreUp,
+            1022 => Self::SemaphoreDown,
+            1030 => Self::CondvarCreate,
+            1031 => Self::CondvarSignal,
+            1032 => Self::CondvarWait,
+            1100 => Self::DebugBacktrace,
+            1101 => Self::Strace,
             _ => Self::Invalid,
         }
     }
@@ -42,10 +109,38 @@ impl From<usize> for SyscallID {
 
 pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
     match syscall_id.into() {
+        SyscallID::Read => sys_read(args[0], args[1] as *mut u8, args[2]),
         SyscallID::Write => sys_write(args[0], args[1] as *const u8, args[2]),
         SyscallID::Exit => sys_exit(args[0] as i32),
         SyscallID::Ts => sys_get_time(args[0] as *mut TimeVal, 0),
         SyscallID::Yield => sys_yield(),
+        SyscallID::Syslog => sys_syslog(args[0], args[1] as *mut u8, args[2]),
+        SyscallID::Kill => sys_kill(args[0], args[1]),
+        SyscallID::SigAction => sys_sigaction(
+            args[0],
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        ),
+        SyscallID::SigProcMask => sys_sigprocmask(args[0] as u32),
+        SyscallID::SigReturn => sys_sigreturn(),
+        SyscallID::GetPid => sys_getpid(),
+        SyscallID::Spawn => sys_spawn(args[0] as *const u8, args[1]),
+        SyscallID::WaitPid => sys_waitpid(args[0], args[1] as *mut ExitInfo),
+        SyscallID::ThreadCreate => sys_thread_create(args[0], args[1]),
+        SyscallID::GetTid => sys_gettid(),
+        SyscallID::WaitTid => sys_waittid(args[0]) as isize,
+        SyscallID::EnableDeadlockDetect => sys_enable_deadlock_detect(args[0]),
+        SyscallID::MutexCreate => sys_mutex_create(args[0] == 1),
+        SyscallID::MutexLock => sys_mutex_lock(args[0]),
+        SyscallID::MutexUnlock => sys_mutex_unlock(args[0]),
+        SyscallID::SemaphoreCreate => sys_semaphore_create(args[0]),
+        SyscallID::SemaphoreUp => sys_semaphore_up(args[0]),
+        SyscallID::SemaphoreDown => sys_semaphore_down(args[0]),
+        SyscallID::CondvarCreate => sys_condvar_create(),
+        SyscallID::CondvarSignal => sys_condvar_signal(args[0]),
+        SyscallID::CondvarWait => sys_condvar_wait(args[0], args[1]),
+        SyscallID::DebugBacktrace => sys_debug_backtrace(),
+        SyscallID::Strace => sys_strace(args[0]),
         //SYSCALL_TASK_INFO => sys_task_info(args[0], args[1] as *mut TaskInfo),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
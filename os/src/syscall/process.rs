@@ -2,14 +2,22 @@ use log::*;
 
 // App management syscalls
 //use crate::batch::run_next_app;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
+use super::EFAULT;
+use crate::loader::{find_app, get_app_data, get_app_name};
+use crate::mm::{copy_from_user, read_from_user, write_to_user};
+use crate::stack_trace::print_stack_trace;
+use crate::task::{
+    current_process, current_task, current_user_token, exit_current_and_run_next, pid2process,
+    remove_from_pid2process, send_signal, suspend_current_and_run_next, ExitInfo, ExitReason,
+    SignalAction, SignalFlags, MAX_SIG,
+};
 use crate::timer::get_time_us;
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
-    info!("[kernel] Application exited with code {}", exit_code);
+    debug!("[kernel] Application exited with code {}", exit_code);
     //run_next_app()
-    exit_current_and_run_next();
+    exit_current_and_run_next(ExitReason::Exited(exit_code));
     panic!("unreachable in sys_exit!")
 }
 
@@ -18,6 +26,10 @@ pub fn sys_yield() -> isize {
     0
 }
 
+pub fn sys_getpid() -> isize {
+    current_process().getpid() as isize
+}
+
 pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     let timestamp = get_time_us();
     timestamp as isize
@@ -29,6 +41,152 @@ pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     // 0
 }
 
+// 向进程 pid 发送信号 signum, 信号会投递给该进程的主线程
+// 终止信号会唤醒阻塞在同步原语上的主线程
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    let process = match pid2process(pid) {
+        Some(process) => process,
+        None => return -1,
+    };
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) if signum != 0 => signal,
+        _ => return -1,
+    };
+    let process_inner = process.inner_exclusive_access();
+    if process_inner.is_zombie {
+        return -1;
+    }
+    let main_task = process_inner.tasks[0].as_ref().unwrap();
+    send_signal(&process_inner, main_task, signal);
+    0
+}
+
+// 设置信号 signum 的处理函数
+// action 为 0 时只查询, old_action 非 0 时写回原来的处理函数
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    if signum == 0 || signum > MAX_SIG {
+        return -1;
+    }
+    if SignalFlags::from_signum(signum).unwrap().uncatchable() {
+        return -1;
+    }
+    let token = current_user_token();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let prev_action = process_inner.signal_actions.table[signum];
+    if !old_action.is_null() && write_to_user(token, old_action, &prev_action).is_err() {
+        return -EFAULT;
+    }
+    if !action.is_null() {
+        match read_from_user(token, action) {
+            Ok(action) => process_inner.signal_actions.table[signum] = action,
+            Err(_) => return -EFAULT,
+        }
+    }
+    0
+}
+
+// 设置当前线程的信号屏蔽字, 返回原来的屏蔽字
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    match SignalFlags::from_bits(mask) {
+        Some(flags) => {
+            // SIGKILL 不能被屏蔽
+            inner.signal_mask = flags - SignalFlags::SIGKILL;
+            old_mask.bits() as isize
+        }
+        None => -1,
+    }
+}
+
+// 从用户的信号处理函数返回, 恢复进入处理函数前的 Trap 上下文
+pub fn sys_sigreturn() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let backup = match inner.trap_ctx_backup.take() {
+        Some(backup) => backup,
+        None => return -1,
+    };
+    inner.handling_sig = -1;
+    let trap_ctx = inner.get_trap_cx();
+    *trap_ctx = backup;
+    // trap_handler 会把返回值写入 a0, 这里返回原来的 a0 以免被覆盖
+    trap_ctx.x[10] as isize
+}
+
+// 查询子进程 pid 的退出信息, 子进程已退出时将其回收
+// 回收后子进程从父进程的 children 与 PID2PCB 中移除, 再次等待同一 pid 返回 -1
+// 不会阻塞: 由用户库循环 yield 等待, 因此等待期间每次都会回到用户态, 信号能够及时递送
+// 返回值: -1 进程不存在或者不是当前进程的子进程, -2 进程尚未退出, -EFAULT info 不可写 (此时不回收),
+// 否则返回 pid 并在 info 非 0 时写入退出信息
+pub fn sys_waitpid(pid: usize, info: *mut ExitInfo) -> isize {
+    let process = current_process();
+    let child = process
+        .inner_exclusive_access()
+        .children
+        .iter()
+        .find(|child| child.getpid() == pid)
+        .cloned();
+    let child = match child {
+        Some(child) => child,
+        None => return -1,
+    };
+    let child_inner = child.inner_exclusive_access();
+    let reason = match child_inner.exit_reason.as_ref() {
+        Some(reason) => reason,
+        None => return -2,
+    };
+    if !info.is_null() {
+        let exit_info = ExitInfo::new(reason, child_inner.user_time, child_inner.kernel_time);
+        if write_to_user(current_user_token(), info, &exit_info).is_err() {
+            return -EFAULT;
+        }
+    }
+    drop(child_inner);
+    // 退出信息已经记录在退出报告中, 这里释放最后的引用, 子进程的 pid 随 PCB 一起回收
+    process
+        .inner_exclusive_access()
+        .children
+        .retain(|child| child.getpid() != pid);
+    remove_from_pid2process(pid);
+    pid as isize
+}
+
+// 应用名的最大长度
+const MAX_APP_NAME: usize = 64;
+
+// 创建子进程运行链接进内核的应用, 应用名由 name 与 len 给出
+// 返回值: 子进程的 pid, 应用不存在时返回 -1, name 不可读时返回 -EFAULT
+pub fn sys_spawn(name: *const u8, len: usize) -> isize {
+    if len > MAX_APP_NAME {
+        return -1;
+    }
+    let mut buf = [0u8; MAX_APP_NAME];
+    if copy_from_user(current_user_token(), &mut buf[..len], name).is_err() {
+        return -EFAULT;
+    }
+    let app_id = match core::str::from_utf8(&buf[..len]).ok().and_then(find_app) {
+        Some(app_id) => app_id,
+        None => return -1,
+    };
+    let child = current_process().spawn(get_app_name(app_id), get_app_data(app_id));
+    child.getpid() as isize
+}
+
+// 打印当前线程在内核中的调用栈, 用于调试
+pub fn sys_debug_backtrace() -> isize {
+    unsafe {
+        print_stack_trace();
+    }
+    0
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct TimeVal {
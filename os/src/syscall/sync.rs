@@ -0,0 +1,256 @@
+//! Synchronization syscalls
+
+use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
+use crate::task::{current_process, current_task};
+use alloc::sync::Arc;
+
+// Interrupted system call, 等待被终止信号打断, 线程会在返回用户态时退出
+const EINTR: isize = 4;
+// Resource deadlock would occur
+const EDEADLK: isize = 35;
+
+fn current_tid() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .tid
+}
+
+// 创建互斥锁, blocking 为 true 时创建阻塞锁, 否则为让权自旋锁
+// 返回值为锁在进程内的 id
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    let process = current_process();
+    let mutex: Option<Arc<dyn Mutex>> = if !blocking {
+        Some(Arc::new(MutexSpin::new()))
+    } else {
+        Some(Arc::new(MutexBlocking::new()))
+    };
+    let mut process_inner = process.inner_exclusive_access();
+    // 优先复用空出来的位置
+    let id = if let Some(id) = process_inner
+        .mutex_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.mutex_list[id] = mutex;
+        id
+    } else {
+        process_inner.mutex_list.push(mutex);
+        process_inner.mutex_list.len() - 1
+    };
+    process_inner.mutex_detector.add_resource(id, 1);
+    id as isize
+}
+
+// 开启死锁检测时, 若本次请求会使系统进入不安全状态, 则不阻塞而是返回 -EDEADLK
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Arc::clone(mutex),
+        _ => return -1,
+    };
+    process_inner.mutex_detector.request(tid, mutex_id);
+    if process_inner.deadlock_detect && !process_inner.mutex_detector.is_safe() {
+        process_inner.mutex_detector.cancel(tid, mutex_id);
+        return -EDEADLK;
+    }
+    // lock 可能阻塞, 必须先释放进程控制块的借用
+    drop(process_inner);
+    if !mutex.lock(tid) {
+        process
+            .inner_exclusive_access()
+            .mutex_detector
+            .cancel(tid, mutex_id);
+        return -EINTR;
+    }
+    process
+        .inner_exclusive_access()
+        .mutex_detector
+        .acquire(tid, mutex_id);
+    0
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let tid = current_tid();
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Arc::clone(mutex),
+        _ => return -1,
+    };
+    drop(process_inner);
+    // 锁没有上锁或者由其他线程持有
+    if !mutex.unlock(tid) {
+        return -1;
+    }
+    process
+        .inner_exclusive_access()
+        .mutex_detector
+        .release(tid, mutex_id);
+    0
+}
+
+// 创建信号量, 初始资源数为 res_count
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = if let Some(id) = process_inner
+        .semaphore_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(res_count)));
+        id
+    } else {
+        process_inner
+            .semaphore_list
+            .push(Some(Arc::new(Semaphore::new(res_count))));
+        process_inner.semaphore_list.len() - 1
+    };
+    process_inner.semaphore_detector.add_resource(id, res_count);
+    id as isize
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(sem)) => Arc::clone(sem),
+        _ => return -1,
+    };
+    // up 的线程没有 down 过时, 相当于新增了一个资源
+    if !process_inner.semaphore_detector.release(tid, sem_id) {
+        process_inner.semaphore_detector.produce(sem_id);
+    }
+    drop(process_inner);
+    sem.up();
+    0
+}
+
+// 与 sys_mutex_lock 相同, 开启死锁检测时可能返回 -EDEADLK
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let tid = current_tid();
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let sem = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(sem)) => Arc::clone(sem),
+        _ => return -1,
+    };
+    process_inner.semaphore_detector.request(tid, sem_id);
+    if process_inner.deadlock_detect && !process_inner.semaphore_detector.is_safe() {
+        process_inner.semaphore_detector.cancel(tid, sem_id);
+        return -EDEADLK;
+    }
+    drop(process_inner);
+    if !sem.down() {
+        process
+            .inner_exclusive_access()
+            .semaphore_detector
+            .cancel(tid, sem_id);
+        return -EINTR;
+    }
+    process
+        .inner_exclusive_access()
+        .semaphore_detector
+        .acquire(tid, sem_id);
+    0
+}
+
+pub fn sys_condvar_create() -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let id = if let Some(id) = process_inner
+        .condvar_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.condvar_list[id] = Some(Arc::new(Condvar::new()));
+        id
+    } else {
+        process_inner
+            .condvar_list
+            .push(Some(Arc::new(Condvar::new())));
+        process_inner.condvar_list.len() - 1
+    };
+    id as isize
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let condvar = match process_inner.condvar_list.get(condvar_id) {
+        Some(Some(condvar)) => Arc::clone(condvar),
+        _ => return -1,
+    };
+    drop(process_inner);
+    condvar.signal();
+    0
+}
+
+// 释放 mutex 并等待条件变量, 被唤醒后重新获取 mutex
+// 释放与重新获取都与 sys_mutex_unlock/sys_mutex_lock 一样记入死锁检测, 当前线程没有持有 mutex 时返回 -1
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let tid = current_tid();
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let (condvar, mutex) = match (
+        process_inner.condvar_list.get(condvar_id),
+        process_inner.mutex_list.get(mutex_id),
+    ) {
+        (Some(Some(condvar)), Some(Some(mutex))) => (Arc::clone(condvar), Arc::clone(mutex)),
+        _ => return -1,
+    };
+    drop(process_inner);
+    if !mutex.unlock(tid) {
+        return -1;
+    }
+    process
+        .inner_exclusive_access()
+        .mutex_detector
+        .release(tid, mutex_id);
+    // 被终止信号打断时不再重新获取 mutex, 线程随后就会退出
+    if !condvar.wait() {
+        return -EINTR;
+    }
+    // 条件变量的语义要求返回时持有 mutex, 因此重新获取时即使开启了死锁检测也不能拒绝
+    process
+        .inner_exclusive_access()
+        .mutex_detector
+        .request(tid, mutex_id);
+    if !mutex.lock(tid) {
+        process
+            .inner_exclusive_access()
+            .mutex_detector
+            .cancel(tid, mutex_id);
+        return -EINTR;
+    }
+    process
+        .inner_exclusive_access()
+        .mutex_detector
+        .acquire(tid, mutex_id);
+    0
+}
+
+// 为当前进程开启(enabled = 1)或关闭(enabled = 0)死锁检测
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    match enabled {
+        0 => process_inner.deadlock_detect = false,
+        1 => process_inner.deadlock_detect = true,
+        _ => return -1,
+    }
+    0
+}
@@ -0,0 +1,95 @@
+//! 内核日志相关的系统调用, action 的取值参考 Linux 的 syslog(2)
+
+use super::EFAULT;
+use crate::logging::{
+    clear_log, commit_unread_log, peek_unread_log, read_log, set_level, unread_log_len,
+    LOG_BUF_SIZE,
+};
+use crate::mm::{copy_from_user, copy_to_user};
+use crate::task::current_user_token;
+use alloc::vec;
+use kernel_test::kernel_test;
+
+// 读取上次 READ 之后的新日志, 没有新日志时返回 0, 不会阻塞
+const SYSLOG_ACTION_READ: usize = 2;
+// 读取缓冲区中最新的 len 个字节, 不清空缓冲区, 也不影响 READ 读到的位置
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+// 读取后清空缓冲区
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+// 还没有被 READ 读走的字节数
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+// 缓冲区的总大小
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+// 修改日志级别, buf 中为 "级别" 或 "模块=级别", 如 "os::task=debug"
+const SYSLOG_ACTION_SET_LEVEL: usize = 11;
+
+// 日志级别字符串的最大长度
+const MAX_LEVEL_SPEC_LEN: usize = 128;
+
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    match action {
+        SYSLOG_ACTION_READ => syslog_read(token, buf, len),
+        SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR => {
+            let mut kbuf = vec![0u8; len.min(LOG_BUF_SIZE)];
+            let count = read_log(&mut kbuf);
+            if copy_to_user(token, buf, &kbuf[..count]).is_err() {
+                return -EFAULT;
+            }
+            if action == SYSLOG_ACTION_READ_CLEAR {
+                clear_log();
+            }
+            count as isize
+        }
+        SYSLOG_ACTION_CLEAR => {
+            clear_log();
+            0
+        }
+        SYSLOG_ACTION_SIZE_UNREAD => unread_log_len() as isize,
+        SYSLOG_ACTION_SIZE_BUFFER => LOG_BUF_SIZE as isize,
+        SYSLOG_ACTION_SET_LEVEL => {
+            if len > MAX_LEVEL_SPEC_LEN {
+                return -1;
+            }
+            let mut spec = vec![0u8; len];
+            if copy_from_user(token, &mut spec, buf).is_err() {
+                return -EFAULT;
+            }
+            match core::str::from_utf8(&spec) {
+                Ok(spec) if set_level(spec) => 0,
+                _ => -1,
+            }
+        }
+        _ => -1,
+    }
+}
+
+// 拷贝给用户成功之后才移动读位置, 用户缓冲区不可写时这些日志仍然是未读的
+fn syslog_read(token: usize, buf: *mut u8, len: usize) -> isize {
+    let mut kbuf = vec![0u8; len.min(LOG_BUF_SIZE)];
+    let (start, count) = peek_unread_log(&mut kbuf);
+    if copy_to_user(token, buf, &kbuf[..count]).is_err() {
+        return -EFAULT;
+    }
+    commit_unread_log(start, count);
+    count as isize
+}
+
+#[kernel_test]
+fn syslog_read_fault_test() {
+    use crate::mm::kernel_token;
+    use log::info;
+    // 保证缓冲区中有未读的日志, 不受编译时日志级别的影响
+    assert!(set_level("os::syscall::syslog=info"));
+    info!("syslog_read_fault_test");
+    assert!(set_level("os::syscall::syslog=default"));
+    let unread = unread_log_len();
+    assert!(unread > 0);
+    // 地址 0 在任何地址空间中都没有映射
+    assert_eq!(
+        syslog_read(kernel_token(), core::ptr::null_mut(), LOG_BUF_SIZE),
+        -EFAULT
+    );
+    assert_eq!(unread_log_len(), unread);
+}
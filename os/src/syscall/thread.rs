@@ -0,0 +1,90 @@
+//! Thread management syscalls
+
+use crate::mm::kernel_token;
+use crate::task::{add_task, current_task, TaskControlBlock};
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::Arc;
+
+// 在当前进程中创建一个新线程, 从 entry 开始执行, 参数 arg 通过 a0 传入
+// 返回新线程的 tid
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    // create a new thread
+    let new_task = Arc::new(TaskControlBlock::new(
+        Arc::clone(&process),
+        task.inner_exclusive_access()
+            .res
+            .as_ref()
+            .unwrap()
+            .ustack_base,
+    ));
+    // 新线程继承创建者的 strace 设置
+    new_task.inner_exclusive_access().strace = task.inner_exclusive_access().strace;
+    // add new task to scheduler
+    add_task(Arc::clone(&new_task));
+    let new_task_inner = new_task.inner_exclusive_access();
+    let new_task_res = new_task_inner.res.as_ref().unwrap();
+    let new_task_tid = new_task_res.tid;
+    let mut process_inner = process.inner_exclusive_access();
+    // add new thread to current process
+    let tasks = &mut process_inner.tasks;
+    while tasks.len() < new_task_tid + 1 {
+        tasks.push(None);
+    }
+    tasks[new_task_tid] = Some(Arc::clone(&new_task));
+    let new_task_trap_cx = new_task_inner.get_trap_cx();
+    *new_task_trap_cx = TrapContext::app_init_context(
+        entry,
+        new_task_res.ustack_top(),
+        kernel_token(),
+        new_task.kstack.get_top(),
+        trap_handler as usize,
+    );
+    (*new_task_trap_cx).x[10] = arg;
+    new_task_tid as isize
+}
+
+pub fn sys_gettid() -> isize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .tid as isize
+}
+
+// 等待同进程内的线程 tid 退出, 并回收它剩余的资源(内核栈)
+// 返回值:
+// -1: 线程不存在, 或者等待的是自己
+// -2: 线程尚未退出
+// 其他: 线程的退出码
+pub fn sys_waittid(tid: usize) -> i32 {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    let mut process_inner = process.inner_exclusive_access();
+    // a thread cannot wait for itself
+    if task_inner.res.as_ref().unwrap().tid == tid {
+        return -1;
+    }
+    let mut exit_code: Option<i32> = None;
+    let waited_task = process_inner.tasks.get(tid).and_then(|t| t.as_ref());
+    if let Some(waited_task) = waited_task {
+        if let Some(reason) = waited_task.inner_exclusive_access().exit_reason {
+            exit_code = Some(reason.exit_code());
+        }
+    } else {
+        // waited thread does not exist
+        return -1;
+    }
+    if let Some(exit_code) = exit_code {
+        // dealloc the exited thread
+        process_inner.tasks[tid] = None;
+        exit_code
+    } else {
+        // waited thread has not exited
+        -2
+    }
+}
@@ -0,0 +1,174 @@
+//! 系统调用跟踪(strace)
+//!
+//! 线程打开 strace 后, 它的每次系统调用都会以 info 级别输出一行日志, 格式类似于 strace:
+//! `write(fd=1, buf="hello\n", len=6) = 6 <12us>`
+//! 耗时为系统调用从进入到返回的时间, 期间如果发生了调度, 也包含在其他线程上运行的时间
+//!
+//! strace 可以由线程自己通过 sys_strace 打开, 也可以在编译时设置环境变量 STRACE,
+//! 让所有线程默认打开. 日志的模块名为 os::syscall::trace, 可以用 sys_syslog 单独调整它的级别
+
+use super::SyscallID;
+use crate::mm::copy_from_user;
+use crate::task::{current_task, current_user_token};
+use crate::timer::get_time_us;
+use alloc::string::String;
+use core::fmt::Write;
+use log::info;
+
+// 编译时设置了 STRACE 时, 所有线程默认打开 strace
+pub const STRACE_DEFAULT: bool = option_env!("STRACE").is_some();
+
+// 打印用户缓冲区内容时最多读取的字节数
+const MAX_BUF_PREVIEW: usize = 32;
+
+// 参数的解码方式
+#[derive(Clone, Copy)]
+enum Arg {
+    // 有符号整数
+    Int,
+    // 指针或按位解释的值
+    Hex,
+    // 用户缓冲区, 内容按字符串打印, 长度为第 n 个参数
+    Buf(usize),
+}
+
+use Arg::*;
+
+// 系统调用的名称与参数, 没有列出的系统调用按 SyscallID 的名称和 3 个十六进制参数打印
+fn signature(id: SyscallID) -> Option<(&'static str, &'static [(&'static str, Arg)])> {
+    let sig: (&str, &[(&str, Arg)]) = match id {
+        SyscallID::Read => ("read", &[("fd", Int), ("buf", Hex), ("len", Int)]),
+        SyscallID::Write => ("write", &[("fd", Int), ("buf", Buf(2)), ("len", Int)]),
+        SyscallID::Exit => ("exit", &[("code", Int)]),
+        SyscallID::Ts => ("get_time", &[("ts", Hex)]),
+        SyscallID::WaitPid => ("waitpid", &[("pid", Int), ("exit_info", Hex)]),
+        SyscallID::Yield => ("yield", &[]),
+        SyscallID::Syslog => ("syslog", &[("action", Int), ("buf", Hex), ("len", Int)]),
+        SyscallID::Kill => ("kill", &[("pid", Int), ("signum", Int)]),
+        SyscallID::SigAction => (
+            "sigaction",
+            &[("signum", Int), ("action", Hex), ("old_action", Hex)],
+        ),
+        SyscallID::SigProcMask => ("sigprocmask", &[("mask", Hex)]),
+        SyscallID::SigReturn => ("sigreturn", &[]),
+        SyscallID::GetPid => ("getpid", &[]),
+        SyscallID::Spawn => ("spawn", &[("name", Buf(1)), ("len", Int)]),
+        SyscallID::EnableDeadlockDetect => ("enable_deadlock_detect", &[("enabled", Int)]),
+        SyscallID::ThreadCreate => ("thread_create", &[("entry", Hex), ("arg", Hex)]),
+        SyscallID::GetTid => ("gettid", &[]),
+        SyscallID::WaitTid => ("waittid", &[("tid", Int)]),
+        SyscallID::MutexCreate => ("mutex_create", &[("blocking", Int)]),
+        SyscallID::MutexLock => ("mutex_lock", &[("mutex_id", Int)]),
+        SyscallID::MutexUnlock => ("mutex_unlock", &[("mutex_id", Int)]),
+        SyscallID::SemaphoreCreate => ("semaphore_create", &[("res_count", Int)]),
+        SyscallID::SemaphoreUp => ("semaphore_up", &[("sem_id", Int)]),
+        SyscallID::SemaphoreDown => ("semaphore_down", &[("sem_id", Int)]),
+        SyscallID::CondvarCreate => ("condvar_create", &[]),
+        SyscallID::CondvarSignal => ("condvar_signal", &[("condvar_id", Int)]),
+        SyscallID::CondvarWait => ("condvar_wait", &[("condvar_id", Int), ("mutex_id", Int)]),
+        SyscallID::DebugBacktrace => ("debug_backtrace", &[]),
+        SyscallID::Strace => ("strace", &[("enable", Int)]),
+        _ => return None,
+    };
+    Some(sig)
+}
+
+// 不会返回的系统调用, 进入时就要打印
+fn no_return(id: SyscallID) -> bool {
+    id == SyscallID::Exit
+}
+
+// value 为参数的值, args 为全部参数, 用于读取 Buf 的长度
+fn write_arg(out: &mut String, arg: Arg, value: usize, args: &[usize; 3]) -> core::fmt::Result {
+    match arg {
+        Int => write!(out, "{}", value as isize),
+        Hex => write!(out, "{:#x}", value),
+        Buf(len_idx) => {
+            let ptr = value as *const u8;
+            let len = args[len_idx];
+            let mut preview = [0u8; MAX_BUF_PREVIEW];
+            let preview = &mut preview[..len.min(MAX_BUF_PREVIEW)];
+            if copy_from_user(current_user_token(), preview, ptr).is_err() {
+                // 地址不可访问时只打印地址, 系统调用本身会返回 -EFAULT
+                return write!(out, "{:#x}", ptr as usize);
+            }
+            write!(out, "\"{}\"", preview.escape_ascii())?;
+            if len > MAX_BUF_PREVIEW {
+                out.push_str("...");
+            }
+            Ok(())
+        }
+    }
+}
+
+// 把系统调用格式化为 "name(arg=value, ...)"
+fn format_call(syscall_id: usize, args: [usize; 3]) -> String {
+    let id = SyscallID::from(syscall_id);
+    let mut out = String::new();
+    let _ = match signature(id) {
+        Some((name, params)) => {
+            out.push_str(name);
+            out.push('(');
+            params
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, &(param, arg))| {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    write!(out, "{}=", param)?;
+                    write_arg(&mut out, arg, args[i], &args)
+                })
+                .and_then(|_| write!(out, ")"))
+        }
+        None => write!(
+            out,
+            "{:?}#{}({:#x}, {:#x}, {:#x})",
+            id, syscall_id, args[0], args[1], args[2]
+        ),
+    };
+    out
+}
+
+// 一次正在进行的系统调用, 在返回时打印
+pub struct StraceEntry {
+    call: String,
+    start_us: usize,
+}
+
+// 系统调用开始前调用, 当前线程没有打开 strace 时返回 None
+pub fn strace_enter(syscall_id: usize, args: [usize; 3], enabled: bool) -> Option<StraceEntry> {
+    if !enabled {
+        return None;
+    }
+    let call = format_call(syscall_id, args);
+    if no_return(syscall_id.into()) {
+        info!("{} = ?", call);
+        return None;
+    }
+    Some(StraceEntry {
+        call,
+        start_us: get_time_us(),
+    })
+}
+
+// 系统调用返回后调用, 打印返回值与耗时
+pub fn strace_exit(entry: Option<StraceEntry>, result: isize) {
+    if let Some(entry) = entry {
+        info!(
+            "{} = {} <{}us>",
+            entry.call,
+            result,
+            get_time_us() - entry.start_us
+        );
+    }
+}
+
+// 打开或关闭当前线程的 strace, 返回之前的状态
+pub fn sys_strace(enable: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old = inner.strace;
+    inner.strace = enable != 0;
+    old as isize
+}
@@ -1,3 +1,5 @@
+use crate::trap::trap_return;
+
 #[derive(Copy, Clone)]
 #[repr(C)] //要与C接口交互,务必要用该属性!
 pub struct TaskContext {
@@ -16,14 +18,25 @@ impl TaskContext {
         }
     }
 
-    pub fn goto_restore(kstack_ptr: usize) -> Self {
-        extern "C" {
-            fn __restore();
-        }
+    // 线程第一次被调度时, __switch 返回到 trap_return, 从而进入用户态
+    pub fn goto_trap_return(kstack_ptr: usize) -> Self {
         Self {
-            ra: __restore as _,
+            ra: trap_return as usize,
             sp: kstack_ptr,
             s: [0; 12],
         }
     }
+
+    // 按寄存器编号访问 __switch 保存的寄存器, 供调试器使用
+    // 只有 ra(x1), sp(x2), s0~s1(x8~x9), s2~s11(x18~x27) 被保存, 其余返回 None
+    #[cfg(feature = "gdbstub")]
+    pub fn reg_mut(&mut self, idx: usize) -> Option<&mut usize> {
+        match idx {
+            1 => Some(&mut self.ra),
+            2 => Some(&mut self.sp),
+            8..=9 => Some(&mut self.s[idx - 8]),
+            18..=27 => Some(&mut self.s[idx - 16]),
+            _ => None,
+        }
+    }
 }
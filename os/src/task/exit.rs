@@ -0,0 +1,198 @@
+use super::manager::PID2PCB;
+use super::SignalFlags;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use lazy_static::*;
+
+// 会导致应用被终止的异常, 取值需要与用户库保持一致
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FaultKind {
+    StoreFault = 1,
+    StorePageFault = 2,
+    IllegalInstruction = 3,
+    LoadFault = 4,
+    LoadPageFault = 5,
+    InstructionFault = 6,
+    InstructionPageFault = 7,
+    InstructionMisaligned = 8,
+    // 无法模拟的非对齐访存, 如浮点访存指令
+    LoadMisaligned = 9,
+    StoreMisaligned = 10,
+    Breakpoint = 11,
+    Unknown = 12,
+}
+
+impl FaultKind {
+    // 异常对应的信号
+    pub fn signal(&self) -> SignalFlags {
+        match self {
+            Self::StoreFault
+            | Self::StorePageFault
+            | Self::LoadFault
+            | Self::LoadPageFault
+            | Self::InstructionFault
+            | Self::InstructionPageFault => SignalFlags::SIGSEGV,
+            Self::InstructionMisaligned | Self::LoadMisaligned | Self::StoreMisaligned => {
+                SignalFlags::SIGBUS
+            }
+            Self::Breakpoint => SignalFlags::SIGTRAP,
+            Self::IllegalInstruction | Self::Unknown => SignalFlags::SIGILL,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FaultInfo {
+    pub kind: FaultKind,
+    // 出错的地址, 即 stval
+    pub addr: usize,
+    // 出错的指令地址, 即 sepc
+    pub pc: usize,
+}
+
+// 线程/进程的退出原因
+#[derive(Clone, Copy, Debug)]
+pub enum ExitReason {
+    // 通过 sys_exit 正常退出
+    Exited(i32),
+    // 因异常产生的信号未被处理而终止
+    Fault(FaultInfo),
+    // 因其他信号而终止
+    Signaled(SignalFlags),
+}
+
+impl ExitReason {
+    // 被信号终止时, 退出码为 -signum
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Exited(code) => *code,
+            Self::Fault(fault) => -(fault.kind.signal().signum() as i32),
+            Self::Signaled(signal) => -(signal.signum() as i32),
+        }
+    }
+}
+
+impl Display for ExitReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited({})", code),
+            Self::Fault(fault) => write!(
+                f,
+                "{:?}(addr={:#x}, pc={:#x})",
+                fault.kind, fault.addr, fault.pc
+            ),
+            Self::Signaled(signal) => write!(f, "killed by {:?}", signal),
+        }
+    }
+}
+
+// 提供给用户态的退出信息, 布局需要与用户库保持一致
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct ExitInfo {
+    // 0: 正常退出, 1: 异常, 2: 信号
+    pub reason: usize,
+    pub code: isize,
+    // 以下三项仅在 reason 为 1 时有意义
+    pub fault_kind: usize,
+    pub fault_addr: usize,
+    pub fault_pc: usize,
+    pub user_time: usize,
+    pub kernel_time: usize,
+}
+
+impl ExitInfo {
+    pub fn new(reason: &ExitReason, user_time: usize, kernel_time: usize) -> Self {
+        let mut info = Self {
+            code: reason.exit_code() as isize,
+            user_time,
+            kernel_time,
+            ..Default::default()
+        };
+        match reason {
+            ExitReason::Exited(_) => info.reason = 0,
+            ExitReason::Fault(fault) => {
+                info.reason = 1;
+                info.fault_kind = fault.kind as usize;
+                info.fault_addr = fault.addr;
+                info.fault_pc = fault.pc;
+            }
+            ExitReason::Signaled(_) => info.reason = 2,
+        }
+        info
+    }
+}
+
+// 进程退出时留下的记录, 进程被 waitpid 回收后仍保留, 供退出报告使用
+#[derive(Clone, Copy)]
+struct ExitRecord {
+    pid: usize,
+    name: &'static str,
+    reason: Option<ExitReason>,
+    user_time: usize,
+    kernel_time: usize,
+}
+
+lazy_static! {
+    static ref EXIT_RECORDS: UPSafeCell<Vec<ExitRecord>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+// 由 exit_current_and_run_next 在进程的主线程退出, 进程运行时间统计完毕后调用
+pub fn record_exit(
+    pid: usize,
+    name: &'static str,
+    reason: ExitReason,
+    user_time: usize,
+    kernel_time: usize,
+) {
+    EXIT_RECORDS.exclusive_access().push(ExitRecord {
+        pid,
+        name,
+        reason: Some(reason),
+        user_time,
+        kernel_time,
+    });
+}
+
+// 所有应用运行结束后, 打印每个应用的退出原因与运行时间
+// 这是一份报告而不是日志, 因此不受 LOG 等级的影响
+// 已退出的进程来自退出记录 (其 PCB 可能已被回收), 尚未退出的进程来自 PID2PCB
+pub fn print_exit_summary() {
+    let mut records = EXIT_RECORDS.exclusive_access().clone();
+    for (&pid, process) in PID2PCB.exclusive_access().iter() {
+        let inner = process.inner_exclusive_access();
+        if inner.exit_reason.is_none() {
+            records.push(ExitRecord {
+                pid,
+                name: process.name,
+                reason: None,
+                user_time: inner.user_time,
+                kernel_time: inner.kernel_time,
+            });
+        }
+    }
+    // pid 回收后会被复用, 稳定排序保证同一 pid 的记录仍按退出先后排列
+    records.sort_by_key(|record| record.pid);
+    println!("[kernel] ---------------- app exit summary ----------------");
+    println!(
+        "[kernel] {:>4} {:<20} {:>10} {:>10}  reason",
+        "pid", "name", "user(us)", "kernel(us)"
+    );
+    for record in records.iter() {
+        match record.reason.as_ref() {
+            Some(reason) => {
+                println!(
+                    "[kernel] {:>4} {:<20} {:>10} {:>10}  {}",
+                    record.pid, record.name, record.user_time, record.kernel_time, reason
+                );
+            }
+            None => {
+                println!(
+                    "[kernel] {:>4} {:<20} {:>10} {:>10}  not exited",
+                    record.pid, record.name, record.user_time, record.kernel_time
+                );
+            }
+        }
+    }
+}
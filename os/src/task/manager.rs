@@ -0,0 +1,93 @@
+use super::{ProcessControlBlock, TaskControlBlock};
+use crate::sync::{SpinNoIrq, UPSafeCell};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+#[cfg(feature = "gdbstub")]
+use alloc::vec::Vec;
+use lazy_static::*;
+
+// 任务管理器只负责管理所有处于就绪态的线程
+// 当前正在运行的线程由 Processor 管理
+pub struct TaskManager {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+/// A simple FIFO scheduler.
+impl TaskManager {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    pub fn remove(&mut self, task: Arc<TaskControlBlock>) {
+        if let Some((id, _)) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .find(|(_, t)| Arc::as_ptr(t) == Arc::as_ptr(&task))
+        {
+            self.ready_queue.remove(id);
+        }
+    }
+}
+
+lazy_static! {
+    // 所有 hart 共享同一个就绪队列
+    pub static ref TASK_MANAGER: SpinNoIrq<TaskManager> = SpinNoIrq::new(TaskManager::new());
+    // 进程没有父进程来持有它, 因此由这张表持有所有进程的所有权
+    // 进程退出后成为僵尸进程, 但仍保留在表中, 直到被父进程 waitpid 回收
+    pub static ref PID2PCB: UPSafeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.lock().add(task);
+}
+
+pub fn remove_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.lock().remove(task);
+}
+
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.lock().fetch()
+}
+
+pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
+    PID2PCB.exclusive_access().insert(pid, process);
+}
+
+pub fn remove_from_pid2process(pid: usize) {
+    PID2PCB.exclusive_access().remove(&pid);
+}
+
+pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    PID2PCB.exclusive_access().get(&pid).map(Arc::clone)
+}
+
+// 所有尚未退出的线程 (pid, tid, 线程), 供调试器列出线程
+// 调试器可能在任意位置暂停内核, 相关结构体正被借用时返回 None 而不是 panic
+#[cfg(feature = "gdbstub")]
+pub fn try_list_tasks() -> Option<Vec<(usize, usize, Arc<TaskControlBlock>)>> {
+    let pid2pcb = PID2PCB.try_exclusive_access()?;
+    let mut tasks = Vec::new();
+    for (&pid, process) in pid2pcb.iter() {
+        let process_inner = process.try_inner_exclusive_access()?;
+        for task in process_inner.tasks.iter().flatten() {
+            if let Some(tid) = task
+                .try_inner_exclusive_access()?
+                .res
+                .as_ref()
+                .map(|res| res.tid)
+            {
+                tasks.push((pid, tid, Arc::clone(task)));
+            }
+        }
+    }
+    Some(tasks)
+}
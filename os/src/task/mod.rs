@@ -1,211 +1,327 @@
 mod context;
-use crate::{config::MAX_APP_NUM, sbi::shutdown, syscall::SyscallID, timer::get_time_us};
-use context::TaskContext;
-use core::panic;
-use lazy_static::lazy_static;
-use switch::__switch;
-use task::{TaskControlBlock, TaskInfo, TaskStatus};
-
-use crate::{
-    loader::{get_num_app, init_app_ctx},
-    sync::UPSafeCell,
-};
-use log::{info, trace};
+mod exit;
+mod id;
+mod manager;
+mod process;
+mod processor;
+mod signal;
 mod switch;
 
 // 该属性可以避免clippy的warning
 #[allow(clippy::module_inception)]
 mod task;
 
-pub struct TaskManager {
-    num_app: usize,
-    inner: UPSafeCell<TaskManagerInner>,
-}
+use crate::loader::{get_app_data, get_app_name, get_num_app};
+use crate::syscall::SyscallID;
+use alloc::{sync::Arc, vec::Vec};
+use context::TaskContext;
+use id::TaskUserRes;
+use log::{info, trace};
+use manager::remove_task;
+use switch::__switch;
+use task::TaskStatus;
 
-struct TaskManagerInner {
-    tasks: [TaskControlBlock; MAX_APP_NUM],
-    current_task: usize,
-    last_ts: usize,
-}
+pub use exit::{print_exit_summary, ExitInfo, ExitReason, FaultInfo, FaultKind};
+pub use id::checked_kernel_stack_position;
+use manager::fetch_task;
+pub use manager::{add_task, pid2process, remove_from_pid2process};
+pub use process::{ProcessControlBlock, ProcessControlBlockInner};
+// 供 gdbstub 使用
+#[cfg(feature = "gdbstub")]
+pub use manager::try_list_tasks;
+#[cfg(feature = "gdbstub")]
+pub use processor::try_current_task;
+pub use processor::{
+    current_process, current_task, current_task_ids, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, hart_id, run_tasks, schedule, take_current_task,
+};
+pub use signal::{SignalAction, SignalActions, SignalFlags, MAX_SIG};
+pub use task::TaskControlBlock;
 
-impl TaskManagerInner {
-    // 每次会返回当前到上一次暂停的时间间隔
-    // 然后刷新为当前时间
-    fn update_duration(&mut self) -> usize {
-        let tmp_ts = self.last_ts;
-        self.last_ts = get_time_us();
-        self.last_ts - tmp_ts
+// 为每个链接进内核的应用创建一个进程
+// 各进程的主线程会被加入任务管理器, 等待 run_tasks 调度
+pub fn add_apps() {
+    let num_app = get_num_app();
+    info!("[kernel] get apps num = {}", num_app);
+    for i in 0..num_app {
+        let process = ProcessControlBlock::new(get_app_name(i), get_app_data(i));
+        info!(
+            "[kernel] app {} ({}) loaded as pid {}",
+            i,
+            process.name,
+            process.getpid()
+        );
     }
 }
 
-lazy_static! {
-    pub static ref TASK_MANAGER: TaskManager = {
-        let num_app = get_num_app();
-        let mut tasks = [TaskControlBlock {
-            task_ctx: TaskContext::zero_init(),
-            task_info: TaskInfo::init()
-        }; MAX_APP_NUM];
-        // app第一次被运行之前, 在这里构造任务上下文
-        // 即通过app_init_ctx 将app的入口地址, sp 压入栈顶
-        // 然后通过goto_restore构造TaskControlBlock要用到的TaskContext
-        // for i in 0..num_app {
-        //     tasks[i].task_ctx = TaskContext::goto_restore(init_app_ctx(i));
-        //     tasks[i].task_info.status = TaskStatus::Ready;
-        // }
-        for (i, task) in tasks.iter_mut().enumerate() {
-            task.task_ctx = TaskContext::goto_restore(init_app_ctx(i));
-            task.task_info.status = TaskStatus::Ready;
-        }
-        TaskManager {
-            num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                    last_ts:0
-                })
-            },
-        }
-    };
+pub fn suspend_current_and_run_next() {
+    // There must be an application running.
+    let task = take_current_task().unwrap();
+
+    // ---- access current TCB exclusively
+    let mut task_inner = task.inner_exclusive_access();
+    let task_ctx_ptr = &mut task_inner.task_ctx as *mut TaskContext;
+    // 当被标记为suspend,即Ready时, 意味着该app不再占用kernel time了
+    task_inner.task_info.kernel_time += processor::current_processor()
+        .exclusive_access()
+        .update_duration();
+    task_inner.task_info.status = TaskStatus::Ready;
+    drop(task_inner);
+    // ---- release current TCB
+
+    // push back to ready queue.
+    add_task(task);
+    // jump to scheduling cycle
+    schedule(task_ctx_ptr);
 }
 
-impl TaskManager {
-    fn trace_syscall_info(&self, syscall_id: usize) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        let syscall_id: SyscallID = syscall_id.into();
-
-        let idx = match syscall_id {
-            SyscallID::Write => 0,
-            SyscallID::Exit => 1,
-            SyscallID::Yield => 2,
-            SyscallID::Ts => 3,
-            //SYSCALL_TASK_INFO => sys_task_info(args[0], args[1] as *mut TaskInfo),
-            _ => 5,
-        };
-        inner.tasks[current].task_info.syscall[idx].times += 1;
-        inner.tasks[current].task_info.syscall[idx].id = syscall_id;
-    }
-    fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        // 当被标记为suspend,即Ready时, 意味着该app不再占用kernel time了
-        inner.tasks[current].task_info.kernel_time += inner.update_duration();
-        trace!("task {} suspended", current);
-        inner.tasks[current].task_info.status = TaskStatus::Ready;
-    }
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        // 当被标记为exit时, 意味着该app不再占用kernel time了
-        inner.tasks[current].task_info.kernel_time += inner.update_duration();
-
-        trace!(
-            "task {} syscall trace {:?}",
-            current,
-            inner.tasks[current].task_info
-        );
-        inner.tasks[current].task_info.status = TaskStatus::Exited;
+// 阻塞当前线程, 与 suspend 不同的是它不会被放回就绪队列
+// 调用者需要事先把它放进某个等待队列, 之后由 wakeup_task 唤醒
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_ctx_ptr = &mut task_inner.task_ctx as *mut TaskContext;
+    task_inner.task_info.kernel_time += processor::current_processor()
+        .exclusive_access()
+        .update_duration();
+    task_inner.task_info.status = TaskStatus::Blocked;
+    drop(task_inner);
+    schedule(task_ctx_ptr);
+}
+
+// 唤醒一个被阻塞的线程, 将其重新放回就绪队列
+// 线程可能已经被终止信号提前唤醒, 或者已随进程退出, 此时什么也不做
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_info.status != TaskStatus::Blocked {
+        return;
     }
+    task_inner.task_info.status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
 
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_info.status == TaskStatus::Ready)
+// 当前线程退出
+// 如果退出的是主线程, 则整个进程随之退出: 其余线程被移出调度队列, 地址空间中的数据页被回收
+// 进程本身作为僵尸进程保留在 PID2PCB 中, 因为当前仍运行在主线程的内核栈上, 直到被父进程 waitpid 回收
+pub fn exit_current_and_run_next(reason: ExitReason) {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let process = task.process.upgrade().unwrap();
+    let tid = task_inner.res.as_ref().unwrap().tid;
+    // 当被标记为exit时, 意味着该app不再占用kernel time了
+    task_inner.task_info.kernel_time += processor::current_processor()
+        .exclusive_access()
+        .update_duration();
+    task_inner.task_info.status = TaskStatus::Exited;
+    trace!(
+        "task pid={} tid={} syscall trace {:?}",
+        process.getpid(),
+        tid,
+        task_inner.task_info
+    );
+    if task_inner.strace {
+        info!(
+            "task pid={} tid={} syscall counts {:?}",
+            process.getpid(),
+            tid,
+            task_inner.task_info.syscall
+        );
     }
+    // record exit reason
+    task_inner.exit_reason = Some(reason);
+    // 回收用户栈与 Trap 上下文, 内核栈要等到 waittid 时才能回收
+    task_inner.res = None;
+    let mut process_inner = process.inner_exclusive_access();
+    process_inner.user_time += task_inner.task_info.user_time;
+    process_inner.kernel_time += task_inner.task_info.kernel_time;
+    drop(process_inner);
+    drop(task_inner);
+    drop(task);
+    // however, if this is the main thread of current process
+    // the process should terminate at once
+    if tid == 0 {
+        info!("[kernel] process {} {}", process.getpid(), reason);
+        let mut process_inner = process.inner_exclusive_access();
+        // mark this process as a zombie process
+        process_inner.is_zombie = true;
+        // record exit reason of main thread
+        process_inner.exit_reason = Some(reason);
 
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            trace!("task {} running", current);
-            inner.tasks[next].task_info.status = TaskStatus::Running;
-            inner.current_task = next;
-            let current_task_ctx_ptr = &mut inner.tasks[current].task_ctx as *mut TaskContext;
-            let next_task_ctx_ptr = &inner.tasks[next].task_ctx as *const TaskContext;
-            drop(inner);
-            // 必须在该代码块之前手动drop,因为一时半会回不来了
-            // 直到下次切换到该app时,才算"回来"
-            // 在此期间,TaskManager的exclusive_access永远成功不了
-            unsafe {
-                __switch(current_task_ctx_ptr, next_task_ctx_ptr);
+        // deallocate user res (including tid/trap_cx/ustack) of all threads
+        // it has to be done before we dealloc the whole memory_set
+        // otherwise they will be deallocated twice
+        let mut recycle_res = Vec::<TaskUserRes>::new();
+        let (mut user_time, mut kernel_time) = (0, 0);
+        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
+            let task = task.as_ref().unwrap();
+            // 其余线程可能还在就绪队列中, 要将它们移除
+            remove_task(Arc::clone(task));
+            let mut task_inner = task.inner_exclusive_access();
+            if let Some(res) = task_inner.res.take() {
+                recycle_res.push(res);
+            }
+            // 尚未退出的线程随进程一起结束, 也要统计它们的运行时间
+            if task_inner.exit_reason.is_none() {
+                task_inner.exit_reason = Some(reason);
+                task_inner.task_info.status = TaskStatus::Exited;
+                user_time += task_inner.task_info.user_time;
+                kernel_time += task_inner.task_info.kernel_time;
             }
-            // go back to user mode
-        } else {
-            //panic!("[kernel] all apps completed!");
-            info!("[kernel] all apps completed!");
-            //use crate::board::QEMUExit;
-            //crate::board::QEMU_EXIT_HANDLE.exit_success();
-            shutdown(true);
         }
-    }
+        process_inner.user_time += user_time;
+        process_inner.kernel_time += kernel_time;
+        exit::record_exit(
+            process.getpid(),
+            process.name,
+            reason,
+            process_inner.user_time,
+            process_inner.kernel_time,
+        );
+        // dealloc_tid and dealloc_user_res require access to PCB inner, so we
+        // need to collect those user res first, then release process_inner
+        // for now to avoid deadlock/double borrow problem.
+        drop(process_inner);
+        recycle_res.clear();
 
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_info.status = TaskStatus::Running;
-        let next_task_ctx_ptr = &task0.task_ctx as *const TaskContext;
-        // 开始记录时间
-        inner.update_duration();
-        drop(inner);
-        let mut _unused = TaskContext::zero_init();
-        unsafe {
-            __switch(&mut _unused as *mut TaskContext, next_task_ctx_ptr);
+        let mut process_inner = process.inner_exclusive_access();
+        // deallocate other data in user space i.e. program code/data section
+        process_inner.memory_set.recycle_data_pages();
+        // Remove all tasks except for the main thread itself.
+        // This is because we are still using the kstack under the TCB,
+        // so we should not release the TCB itself.
+        while process_inner.tasks.len() > 1 {
+            process_inner.tasks.pop();
         }
-
-        panic!("unreachable in run_first_task!");
-    }
-
-    fn kernel_end_and_user_time_start(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_info.kernel_time += inner.update_duration();
-    }
-
-    fn user_end_and_kernel_time_start(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_info.user_time += inner.update_duration();
     }
+    drop(process);
+    // we do not have to save task context
+    let mut _unused = TaskContext::zero_init();
+    schedule(&mut _unused as *mut _);
 }
 
-fn mark_current_suspended() {
-    TASK_MANAGER.mark_current_suspended();
+// 向当前线程发送信号, 用于由内核产生的信号, 如访存异常
+pub fn current_add_signal(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().signals |= signal;
 }
 
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+// 向线程 task 发送信号, process_inner 为其所属进程
+// 信号只在返回用户态时递送, 因此终止信号还要唤醒阻塞在同步原语上的线程,
+// 它从等待中返回后发现被打断, 系统调用以 -EINTR 返回, 随后在返回用户态的路上退出
+pub fn send_signal(
+    process_inner: &ProcessControlBlockInner,
+    task: &Arc<TaskControlBlock>,
+    signal: SignalFlags,
+) {
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.signals |= signal;
+    let fatal = signal.fatal(&process_inner.signal_actions, task_inner.signal_mask);
+    drop(task_inner);
+    if fatal {
+        wakeup_task(Arc::clone(task));
+    }
 }
 
-fn run_next_task() {
-    TASK_MANAGER.run_next_task();
+// 当前线程是否有待递送的终止信号
+// 阻塞在同步原语上的线程被唤醒后以此判断等待是否被信号打断
+pub fn current_fatal_signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let process_inner = process.inner_exclusive_access();
+    let task_inner = task.inner_exclusive_access();
+    (1..=MAX_SIG)
+        .filter_map(SignalFlags::from_signum)
+        .filter(|signal| task_inner.signals.contains(*signal))
+        .any(|signal| signal.fatal(&process_inner.signal_actions, task_inner.signal_mask))
 }
 
-pub fn suspend_current_and_run_next() {
-    mark_current_suspended();
-    run_next_task();
+// 每次返回用户态之前调用, 处理当前线程可以递送的信号
+// 1. 有用户处理函数的信号: 备份 Trap 上下文, 并令其返回到处理函数, 一次只递送一个
+// 2. 默认动作为忽略的信号: 直接清除
+// 3. 其余信号的默认动作为终止: 记录在 killed_by 中, 由 trap_handler 完成退出
+pub fn handle_signals() {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let process_inner = process.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    for signum in 1..=MAX_SIG {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        if !task_inner.signals.contains(signal) {
+            continue;
+        }
+        let action = process_inner.signal_actions.table[signum];
+        if !signal.uncatchable() {
+            if task_inner.signal_mask.contains(signal) {
+                continue;
+            }
+            if task_inner.handling_sig != -1 {
+                // 正在执行处理函数时不会嵌套递送, 被其 mask 屏蔽的信号也要等待
+                let handling = process_inner.signal_actions.table[task_inner.handling_sig as usize];
+                if action.handler != 0 || handling.mask.contains(signal) {
+                    continue;
+                }
+            }
+            if action.handler != 0 {
+                task_inner.signals.remove(signal);
+                task_inner.handling_sig = signum as isize;
+                let trap_ctx = task_inner.get_trap_cx();
+                task_inner.trap_ctx_backup = Some(*trap_ctx);
+                trap_ctx.sepc = action.handler;
+                trap_ctx.x[10] = signum;
+                break;
+            }
+            if signal.default_ignored() {
+                task_inner.signals.remove(signal);
+                continue;
+            }
+        }
+        task_inner.signals.remove(signal);
+        task_inner.killed_by = Some(signal);
+        // 终止信号作用于整个进程, 由子线程收到时还要转发给主线程
+        if task_inner.res.as_ref().unwrap().tid != 0 {
+            if let Some(main_task) = process_inner.tasks[0].as_ref() {
+                send_signal(&process_inner, main_task, signal);
+            }
+        }
+        break;
+    }
 }
 
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
-    run_next_task();
+// 向当前线程发送异常对应的信号, 并记录异常信息
+pub fn current_add_fault(fault: FaultInfo) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.signals |= fault.kind.signal();
+    inner.last_fault = Some(fault);
 }
 
-pub fn run_first_task() -> ! {
-    TASK_MANAGER.run_first_task()
+// 若当前线程需要因信号终止, 返回对应的退出原因
+// 信号由异常产生时, 退出原因中包含异常的详细信息
+pub fn check_signals_error_of_current() -> Option<ExitReason> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let signal = inner.killed_by?;
+    match inner.last_fault {
+        Some(fault) if fault.kind.signal() == signal => Some(ExitReason::Fault(fault)),
+        _ => Some(ExitReason::Signaled(signal)),
+    }
 }
 
-pub fn trace_syscall_info(syscall_id: usize) {
-    TASK_MANAGER.trace_syscall_info(syscall_id);
+// 统计当前线程的系统调用次数, 返回当前线程是否打开了 strace
+pub fn trace_syscall_info(syscall_id: usize) -> bool {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let syscall_id: SyscallID = syscall_id.into();
+    *inner.task_info.syscall.entry(syscall_id).or_insert(0) += 1;
+    inner.strace
 }
 
 /*
  * 时间统计规则
- * 1. 当 first_task 执行后,第一个app肯定是运行在user, 记为 t0
+ * 1. 当线程被调度执行后, 它首先经由 trap_return 进入 user, 记为 t0
  * 2. 之后, app与kernel切换的时机,一个是异常,一个是系统调用
- *   2.1 当 switch 执行时, cpu/app 便从kernel转为user
+ *   2.1 当 trap_return 执行时, cpu/app 便从kernel转为user
  *   2.2 当异常或系统调用触发时,cpu/app 便从user转为kernel
  * 因此, 当第一个异常或系统调用触发时, 运行到 trap_handler 时, 可以第一时间记录为 t1
  * t1 - t0 即为第一个app运行 user 的持续时间
@@ -214,9 +330,17 @@ pub fn trace_syscall_info(syscall_id: usize) {
  */
 
 pub fn kernel_end_and_user_time_start() {
-    TASK_MANAGER.kernel_end_and_user_time_start();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.task_info.kernel_time += processor::current_processor()
+        .exclusive_access()
+        .update_duration();
 }
 
 pub fn user_end_and_kernel_time_start() {
-    TASK_MANAGER.user_end_and_kernel_time_start();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.task_info.user_time += processor::current_processor()
+        .exclusive_access()
+        .update_duration();
 }
@@ -0,0 +1,135 @@
+use super::id::{pid_alloc, PidHandle, RecycleAllocator};
+use super::manager::{add_task, insert_into_pid2process};
+use super::{ExitReason, SignalActions, TaskControlBlock};
+use crate::mm::{kernel_token, MemorySet};
+use crate::sync::{Condvar, DeadlockDetector, Mutex, Semaphore, UPSafeCell};
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::RefMut;
+
+// 进程控制块
+// 进程是资源的容器: 地址空间以及其中运行的所有线程
+// 真正被调度的是线程, 即 TaskControlBlock
+pub struct ProcessControlBlock {
+    // immutable
+    pub pid: PidHandle,
+    pub name: &'static str,
+    // mutable
+    inner: UPSafeCell<ProcessControlBlockInner>,
+}
+
+pub struct ProcessControlBlockInner {
+    pub is_zombie: bool,
+    pub memory_set: MemorySet,
+    // 主线程的退出原因, 进程运行期间为 None
+    pub exit_reason: Option<ExitReason>,
+    // 由 spawn 创建的子进程, 只有父进程才能 waitpid 它们
+    // 启动时加载的应用没有父进程
+    pub children: Vec<Arc<ProcessControlBlock>>,
+    // 所有线程的运行时间之和, 在线程退出时累加
+    pub user_time: usize,
+    pub kernel_time: usize,
+    // 信号处理函数由进程内所有线程共享
+    pub signal_actions: SignalActions,
+    // 以 tid 为下标, 线程被 waittid 回收后对应位置为 None
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    pub task_res_allocator: RecycleAllocator,
+    // 进程内的同步原语, 以下标作为 id 返回给用户态
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    // 是否开启死锁检测, 开启后 lock/down 在会导致不安全状态时直接返回错误
+    pub deadlock_detect: bool,
+    // 资源的分配情况无论是否开启检测都会记录, 以便随时开启
+    pub mutex_detector: DeadlockDetector,
+    pub semaphore_detector: DeadlockDetector,
+}
+
+impl ProcessControlBlockInner {
+    pub fn alloc_tid(&mut self) -> usize {
+        self.task_res_allocator.alloc()
+    }
+
+    pub fn dealloc_tid(&mut self, tid: usize) {
+        self.task_res_allocator.dealloc(tid)
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    // 供 gdbstub 列出线程时使用
+    #[cfg(feature = "gdbstub")]
+    pub fn try_inner_exclusive_access(&self) -> Option<RefMut<'_, ProcessControlBlockInner>> {
+        self.inner.try_exclusive_access()
+    }
+
+    // 解析 ELF 创建进程地址空间, 并创建它的主线程(tid = 0)加入调度队列
+    pub fn new(name: &'static str, elf_data: &[u8]) -> Arc<Self> {
+        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let pid_handle = pid_alloc();
+        let process = Arc::new(Self {
+            pid: pid_handle,
+            name,
+            inner: unsafe {
+                UPSafeCell::new(ProcessControlBlockInner {
+                    is_zombie: false,
+                    memory_set,
+                    exit_reason: None,
+                    children: Vec::new(),
+                    user_time: 0,
+                    kernel_time: 0,
+                    signal_actions: SignalActions::default(),
+                    tasks: Vec::new(),
+                    task_res_allocator: RecycleAllocator::new(),
+                    mutex_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    condvar_list: Vec::new(),
+                    deadlock_detect: false,
+                    mutex_detector: DeadlockDetector::new(),
+                    semaphore_detector: DeadlockDetector::new(),
+                })
+            },
+        });
+        // create a main thread, we should allocate ustack and trap_cx here
+        let task = Arc::new(TaskControlBlock::new(Arc::clone(&process), ustack_base));
+        // prepare trap_cx of main thread
+        let task_inner = task.inner_exclusive_access();
+        let trap_cx = task_inner.get_trap_cx();
+        let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
+        let kstack_top = task.kstack.get_top();
+        drop(task_inner);
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            ustack_top,
+            kernel_token(),
+            kstack_top,
+            trap_handler as usize,
+        );
+        // add main thread to the process
+        process
+            .inner_exclusive_access()
+            .tasks
+            .push(Some(Arc::clone(&task)));
+        insert_into_pid2process(process.getpid(), Arc::clone(&process));
+        // add main thread to scheduler
+        add_task(task);
+        process
+    }
+
+    // 创建子进程运行应用 name, 它与启动时加载的进程一样立即加入调度队列
+    pub fn spawn(&self, name: &'static str, elf_data: &[u8]) -> Arc<Self> {
+        let child = Self::new(name, elf_data);
+        self.inner_exclusive_access()
+            .children
+            .push(Arc::clone(&child));
+        child
+    }
+
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+}
@@ -0,0 +1,170 @@
+use super::__switch;
+use super::{fetch_task, print_exit_summary, TaskStatus};
+use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
+use crate::config::MAX_HARTS;
+use crate::sbi::shutdown;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_us;
+use crate::trap::TrapContext;
+use alloc::sync::Arc;
+use core::arch::asm;
+use lazy_static::*;
+use log::{info, trace};
+
+// 处理器管理结构, 描述 CPU 执行状态
+pub struct Processor {
+    // 当前处理器上正在执行的线程
+    current: Option<Arc<TaskControlBlock>>,
+    // 每个处理器都有一个 idle 控制流, 运行在启动栈上
+    // 它负责从任务管理器中选出一个线程在当前处理器上执行
+    idle_task_ctx: TaskContext,
+    // 上一次统计时间的时刻, 用于计算线程的 user/kernel time
+    last_ts: usize,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_ctx: TaskContext::zero_init(),
+            last_ts: 0,
+        }
+    }
+    fn get_idle_task_ctx_ptr(&mut self) -> *mut TaskContext {
+        &mut self.idle_task_ctx as *mut _
+    }
+    pub fn take_current(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.current.take()
+    }
+    pub fn current(&self) -> Option<Arc<TaskControlBlock>> {
+        self.current.as_ref().map(Arc::clone)
+    }
+    // 每次会返回当前到上一次暂停的时间间隔
+    // 然后刷新为当前时间
+    pub fn update_duration(&mut self) -> usize {
+        let tmp_ts = self.last_ts;
+        self.last_ts = get_time_us();
+        self.last_ts - tmp_ts
+    }
+}
+
+lazy_static! {
+    // 每个 hart 各有一个 Processor, 以 hartid 为下标
+    // 一个 Processor 只会被它所属的 hart 访问, 因此仍可以使用 UPSafeCell
+    static ref PROCESSORS: [UPSafeCell<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| unsafe { UPSafeCell::new(Processor::new()) });
+}
+
+// 当前 hart 的 id, 在 entry.asm 中保存到 tp 寄存器
+// 用户态可能修改 tp, 因此 __alltraps 会从 TrapContext 中重新加载它
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+// 当前 hart 的处理器管理结构
+pub fn current_processor() -> &'static UPSafeCell<Processor> {
+    &PROCESSORS[hart_id()]
+}
+
+// idle 控制流: 不断从任务管理器中取出线程并切换过去
+// 线程让出 CPU 后, 会通过 schedule 切换回这里
+pub fn run_tasks() -> ! {
+    loop {
+        let mut processor = current_processor().exclusive_access();
+        if let Some(task) = fetch_task() {
+            let idle_task_ctx_ptr = processor.get_idle_task_ctx_ptr();
+            // access coming task TCB exclusively
+            let mut task_inner = task.inner_exclusive_access();
+            let next_task_ctx_ptr = &task_inner.task_ctx as *const TaskContext;
+            task_inner.task_info.status = TaskStatus::Running;
+            trace!(
+                "task pid={} tid={} running",
+                task.process.upgrade().unwrap().getpid(),
+                task_inner.res.as_ref().unwrap().tid
+            );
+            drop(task_inner);
+            // release coming task TCB manually
+            processor.current = Some(task);
+            // 开始记录时间
+            processor.update_duration();
+            // 必须在该代码块之前手动drop,因为一时半会回不来了
+            // 直到下次切换回 idle 控制流时,才算"回来"
+            // 在此期间,当前 Processor 的exclusive_access永远成功不了
+            drop(processor);
+            unsafe {
+                __switch(idle_task_ctx_ptr, next_task_ctx_ptr);
+            }
+        } else {
+            //panic!("[kernel] all apps completed!");
+            info!("[kernel] all apps completed!");
+            print_exit_summary();
+            #[cfg(feature = "profile")]
+            crate::profile::dump();
+            //use crate::board::QEMUExit;
+            //crate::board::QEMU_EXIT_HANDLE.exit_success();
+            shutdown(true);
+        }
+    }
+}
+
+pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
+    current_processor().exclusive_access().take_current()
+}
+
+pub fn current_task() -> Option<Arc<TaskControlBlock>> {
+    current_processor().exclusive_access().current()
+}
+
+// 与 current_task 相同, 但 Processor 正被借用时返回 None
+pub fn try_current_task() -> Option<Arc<TaskControlBlock>> {
+    current_processor().try_exclusive_access()?.current()
+}
+
+// 当前 hart 上正在运行的线程的 (pid, tid)
+// 日志可能在任意上下文中输出, 相关结构体正被借用时返回 None 而不是 panic
+pub fn current_task_ids() -> Option<(usize, usize)> {
+    let task = try_current_task()?;
+    let pid = task.process.upgrade()?.getpid();
+    let tid = task.try_inner_exclusive_access()?.res.as_ref()?.tid;
+    Some((pid, tid))
+}
+
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process.upgrade().unwrap()
+}
+
+pub fn current_user_token() -> usize {
+    let task = current_task().unwrap();
+    task.get_user_token()
+}
+
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .get_trap_cx()
+}
+
+pub fn current_trap_cx_user_va() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .trap_cx_user_va()
+}
+
+// 当前线程让出 CPU, 切换回 idle 控制流
+pub fn schedule(switched_task_ctx_ptr: *mut TaskContext) {
+    let mut processor = current_processor().exclusive_access();
+    let idle_task_ctx_ptr = processor.get_idle_task_ctx_ptr();
+    drop(processor);
+    unsafe {
+        __switch(switched_task_ctx_ptr, idle_task_ctx_ptr);
+    }
+}
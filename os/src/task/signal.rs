@@ -0,0 +1,108 @@
+use bitflags::*;
+
+pub const MAX_SIG: usize = 31;
+
+bitflags! {
+    // 第 i 位对应编号为 i 的信号, 编号与 Linux 保持一致
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SignalFlags: u32 {
+        const SIGDEF = 1; // Default signal handling
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum > MAX_SIG {
+            None
+        } else {
+            Self::from_bits(1 << signum)
+        }
+    }
+
+    // 仅对单个信号有意义
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+
+    // 默认动作为忽略的信号
+    pub fn default_ignored(&self) -> bool {
+        self.intersects(Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH | Self::SIGCONT)
+    }
+
+    // 无法被捕获, 也无法被屏蔽的信号
+    // 暂不支持 SIGSTOP/SIGCONT 的暂停语义
+    pub fn uncatchable(&self) -> bool {
+        self.contains(Self::SIGKILL)
+    }
+
+    // 递送后是否会终止线程: 无法捕获, 或者没有被屏蔽、没有处理函数且默认动作不是忽略
+    pub fn fatal(&self, actions: &SignalActions, mask: SignalFlags) -> bool {
+        self.uncatchable()
+            || (!mask.contains(*self)
+                && actions.table[self.signum()].handler == 0
+                && !self.default_ignored())
+    }
+}
+
+// 用户态的信号处理函数, 布局需要与用户库保持一致
+// handler 为 0 表示使用默认动作
+// mask 为执行该处理函数期间额外屏蔽的信号
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
@@ -1,49 +1,128 @@
-use super::TaskContext;
-use crate::syscall::SyscallID;
+use super::id::{kstack_alloc, KernelStack, TaskUserRes};
+use super::{ExitReason, FaultInfo, ProcessControlBlock, SignalFlags, TaskContext};
+use crate::mm::{PhysPageAccess, PhysPageNum};
+use crate::sync::UPSafeCell;
+use crate::syscall::{SyscallID, STRACE_DEFAULT};
+use crate::trap::TrapContext;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use core::cell::RefMut;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     Uninit,
     Ready,
     Running,
+    // 等待某个同步原语, 不在就绪队列中
+    Blocked,
     Exited,
 }
 
-#[derive(Copy, Clone)]
+// 线程控制块
+// 一个进程可以有多个线程, 它们共享进程的地址空间
+// 但各自拥有自己的内核栈, 用户栈和 Trap 上下文
 pub struct TaskControlBlock {
-    //pub task_status: TaskStatus,
+    // immutable
+    pub process: Weak<ProcessControlBlock>,
+    pub kstack: KernelStack,
+    // mutable
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+pub struct TaskControlBlockInner {
+    // 线程退出后 res 会被置为 None, 从而回收其用户栈和 Trap 上下文
+    pub res: Option<TaskUserRes>,
+    // Trap 上下文所在的物理页帧, 内核通过它直接访问 Trap 上下文
+    pub trap_cx_ppn: PhysPageNum,
     pub task_ctx: TaskContext,
     pub task_info: TaskInfo,
+    pub exit_reason: Option<ExitReason>,
+    // 已收到但尚未处理的信号
+    pub signals: SignalFlags,
+    // 被屏蔽的信号, 它们会保持 pending 直到解除屏蔽
+    pub signal_mask: SignalFlags,
+    // 正在执行用户处理函数的信号, -1 表示没有
+    pub handling_sig: isize,
+    // 进入用户处理函数前备份的 Trap 上下文, 由 sigreturn 恢复
+    pub trap_ctx_backup: Option<TrapContext>,
+    // 因默认动作而需要终止当前线程的信号
+    pub killed_by: Option<SignalFlags>,
+    // 最近一次导致信号的异常, 若该信号终止了线程, 则作为退出原因
+    pub last_fault: Option<FaultInfo>,
+    // 是否打印该线程的每次系统调用, 见 syscall::trace
+    pub strace: bool,
+}
+
+impl TaskControlBlockInner {
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+}
+
+impl TaskControlBlock {
+    pub fn new(process: Arc<ProcessControlBlock>, ustack_base: usize) -> Self {
+        let res = TaskUserRes::new(Arc::clone(&process), ustack_base);
+        let trap_cx_ppn = res.trap_cx_ppn();
+        let kstack = kstack_alloc();
+        let kstack_top = kstack.get_top();
+        let mut task_info = TaskInfo::init();
+        task_info.status = TaskStatus::Ready;
+        Self {
+            process: Arc::downgrade(&process),
+            kstack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    res: Some(res),
+                    trap_cx_ppn,
+                    task_ctx: TaskContext::goto_trap_return(kstack_top),
+                    task_info,
+                    exit_reason: None,
+                    signals: SignalFlags::empty(),
+                    signal_mask: SignalFlags::empty(),
+                    handling_sig: -1,
+                    trap_ctx_backup: None,
+                    killed_by: None,
+                    last_fault: None,
+                    strace: STRACE_DEFAULT,
+                })
+            },
+        }
+    }
+
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    pub fn try_inner_exclusive_access(&self) -> Option<RefMut<'_, TaskControlBlockInner>> {
+        self.inner.try_exclusive_access()
+    }
+
+    pub fn get_user_token(&self) -> usize {
+        let process = self.process.upgrade().unwrap();
+        let inner = process.inner_exclusive_access();
+        inner.memory_set.token()
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct TaskInfo {
     // task id
     //pub id: usize,
     pub status: TaskStatus,
-    pub syscall: [SyscallInfo; core::mem::variant_count::<SyscallID>()],
+    // 各个系统调用触发的次数, 而不是运行时间
+    // 只记录调用过的系统调用, 新增的系统调用不需要修改这里
+    pub syscall: BTreeMap<SyscallID, usize>,
     //pub time: usize,
     pub user_time: usize,
     pub kernel_time: usize,
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct SyscallInfo {
-    // syscall id
-    pub id: SyscallID,
-    // 这里是统计系统调用触发的次数, 而不是运行时间
-    pub times: usize,
-}
-
 impl TaskInfo {
     pub fn init() -> Self {
         Self {
             //id: 0,
             status: TaskStatus::Uninit,
-            syscall: [SyscallInfo {
-                id: SyscallID::Invalid,
-                times: 0,
-            }; core::mem::variant_count::<SyscallID>()],
+            syscall: BTreeMap::new(),
             //time: 0,
             user_time: 0,
             kernel_time: 0,
@@ -6,7 +6,7 @@ pub fn get_time() -> usize {
     time::read()
 }
 
-const TICKS_PER_SEC: usize = 100;
+pub const TICKS_PER_SEC: usize = 100;
 
 pub fn set_next_trigger() {
     sbi::set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
@@ -1,9 +1,10 @@
+use crate::task::hart_id;
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 //Trap上下文（即数据结构 TrapContext ），
 //类似函数调用上下文，即在 Trap 发生时需要保存的物理资源内容
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct TrapContext {
     // 通用寄存器 x0 ~ x31
     // 不过x0其实硬编码为0,不可能有变化
@@ -17,19 +18,41 @@ pub struct TrapContext {
     pub sstatus: Sstatus,
     // sepc reg
     pub sepc: usize,
+
+    // 以下三项在应用初始化时由内核写入, 之后不再修改
+    // 它们是 __alltraps 切换到内核地址空间所必需的信息
+    // 内核地址空间的token, 即内核页表的起始物理地址
+    pub kernel_satp: usize,
+    // 当前线程在内核地址空间中的内核栈栈顶的虚拟地址
+    pub kernel_sp: usize,
+    // 内核中 trap handler 入口点的虚拟地址
+    pub trap_handler: usize,
+    // 线程所在 hart 的 id, __alltraps 用它恢复内核的 tp
+    // 线程可能被调度到不同的 hart 上, 因此每次 trap_return 时都会重新写入
+    pub kernel_tp: usize,
 }
 
 impl TrapContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.x[2] = sp;
     }
-    pub fn app_init_context(entry: usize, sp: usize) -> Self {
+    pub fn app_init_context(
+        entry: usize,
+        sp: usize,
+        kernel_satp: usize,
+        kernel_sp: usize,
+        trap_handler: usize,
+    ) -> Self {
         let mut sstatus = sstatus::read();
         sstatus.set_spp(SPP::User);
         let mut ctx = Self {
             x: [0; 32],
             sstatus,
             sepc: entry,
+            kernel_satp,
+            kernel_sp,
+            trap_handler,
+            kernel_tp: hart_id(),
         };
         ctx.set_sp(sp);
         ctx
@@ -0,0 +1,31 @@
+//! 内核异常表
+//!
+//! 可能因用户指针而出错的内核访存指令(如 __copy_user 中的 lb/sb), 会在 __ex_table 段中
+//! 登记一项 (出错指令地址, 修复代码地址)
+//! 内核态发生访存异常时先查这张表, 命中则跳转到修复代码继续执行, 否则才 panic
+
+#[repr(C)]
+struct ExceptionTableEntry {
+    insn: usize,
+    fixup: usize,
+}
+
+extern "C" {
+    fn __start___ex_table();
+    fn __stop___ex_table();
+}
+
+fn exception_table() -> &'static [ExceptionTableEntry] {
+    let start = __start___ex_table as usize;
+    let stop = __stop___ex_table as usize;
+    let len = (stop - start) / core::mem::size_of::<ExceptionTableEntry>();
+    unsafe { core::slice::from_raw_parts(start as *const ExceptionTableEntry, len) }
+}
+
+// 查找 sepc 处指令对应的修复代码地址
+pub fn search_exception_table(sepc: usize) -> Option<usize> {
+    exception_table()
+        .iter()
+        .find(|entry| entry.insn == sepc)
+        .map(|entry| entry.fixup)
+}
@@ -0,0 +1,191 @@
+//! 应用非对齐访存的软件模拟
+//!
+//! 硬件不支持非对齐访存时会触发 Load/StoreMisaligned 异常
+//! 这里解码出错的指令, 按字节完成访存, 再跳过该指令返回应用
+
+use super::TrapContext;
+use crate::mm::{user_read_byte, user_write_byte};
+
+// scause 中的异常号
+// 旧版 riscv crate 的 Exception 中缺少 LoadMisaligned, 因此直接按异常号判断
+pub const EXC_LOAD_MISALIGNED: usize = 4;
+pub const EXC_STORE_MISALIGNED: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    // 宽度(字节), 是否符号扩展, 目的寄存器
+    Load {
+        width: usize,
+        signed: bool,
+        rd: usize,
+    },
+    // 宽度(字节), 源寄存器
+    Store {
+        width: usize,
+        rs2: usize,
+    },
+}
+
+// 解码后的访存指令
+struct Decoded {
+    access: Access,
+    // 指令长度, 压缩指令为 2, 否则为 4
+    len: usize,
+}
+
+// 模拟失败的原因, 由调用者决定向应用发送什么信号
+#[derive(Debug)]
+pub enum EmulateError {
+    // 无法读取出错的指令, 或者不是能够模拟的访存指令(如浮点访存)
+    Unsupported,
+    // 访存地址不可访问
+    AccessFault,
+}
+
+fn read_u16(token: usize, va: usize) -> Option<u16> {
+    let lo = user_read_byte(token, va)? as u16;
+    let hi = user_read_byte(token, va + 1)? as u16;
+    Some(lo | hi << 8)
+}
+
+// 取出 sepc 处的指令, 指令本身可能因为压缩指令而只按 2 字节对齐
+fn fetch_inst(token: usize, pc: usize) -> Option<(u32, usize)> {
+    let lo = read_u16(token, pc)? as u32;
+    if lo & 0b11 != 0b11 {
+        return Some((lo, 2));
+    }
+    let hi = read_u16(token, pc + 2)? as u32;
+    Some((lo | hi << 16, 4))
+}
+
+fn bits(inst: u32, hi: u32, lo: u32) -> usize {
+    ((inst >> lo) & ((1 << (hi - lo + 1)) - 1)) as usize
+}
+
+fn decode(inst: u32, len: usize) -> Option<Decoded> {
+    let access = if len == 4 {
+        decode_32(inst)?
+    } else {
+        decode_16(inst)?
+    };
+    Some(Decoded { access, len })
+}
+
+fn decode_32(inst: u32) -> Option<Access> {
+    let opcode = bits(inst, 6, 0);
+    let funct3 = bits(inst, 14, 12);
+    match opcode {
+        // LB/LH/LW/LD/LBU/LHU/LWU
+        0b0000011 => {
+            let (width, signed) = match funct3 {
+                0 => (1, true),
+                1 => (2, true),
+                2 => (4, true),
+                3 => (8, true),
+                4 => (1, false),
+                5 => (2, false),
+                6 => (4, false),
+                _ => return None,
+            };
+            Some(Access::Load {
+                width,
+                signed,
+                rd: bits(inst, 11, 7),
+            })
+        }
+        // SB/SH/SW/SD
+        0b0100011 => {
+            if funct3 > 3 {
+                return None;
+            }
+            Some(Access::Store {
+                width: 1 << funct3,
+                rs2: bits(inst, 24, 20),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn decode_16(inst: u32) -> Option<Access> {
+    let op = bits(inst, 1, 0);
+    let funct3 = bits(inst, 15, 13);
+    // 压缩指令中 3 位的寄存器编号对应 x8~x15
+    let rd_prime = bits(inst, 4, 2) + 8;
+    let rd = bits(inst, 11, 7);
+    let rs2 = bits(inst, 6, 2);
+    match (op, funct3) {
+        // C.LW / C.LD
+        (0b00, 0b010) => Some(Access::Load {
+            width: 4,
+            signed: true,
+            rd: rd_prime,
+        }),
+        (0b00, 0b011) => Some(Access::Load {
+            width: 8,
+            signed: true,
+            rd: rd_prime,
+        }),
+        // C.SW / C.SD
+        (0b00, 0b110) => Some(Access::Store {
+            width: 4,
+            rs2: rd_prime,
+        }),
+        (0b00, 0b111) => Some(Access::Store {
+            width: 8,
+            rs2: rd_prime,
+        }),
+        // C.LWSP / C.LDSP, rd 不能为 x0
+        (0b10, 0b010) if rd != 0 => Some(Access::Load {
+            width: 4,
+            signed: true,
+            rd,
+        }),
+        (0b10, 0b011) if rd != 0 => Some(Access::Load {
+            width: 8,
+            signed: true,
+            rd,
+        }),
+        // C.SWSP / C.SDSP
+        (0b10, 0b110) => Some(Access::Store { width: 4, rs2 }),
+        (0b10, 0b111) => Some(Access::Store { width: 8, rs2 }),
+        _ => None,
+    }
+}
+
+// 模拟 sepc 处的非对齐访存指令, 访存地址取自 stval
+// 成功时更新目的寄存器并跳过该指令
+pub fn emulate_misaligned(
+    ctx: &mut TrapContext,
+    token: usize,
+    addr: usize,
+) -> Result<(), EmulateError> {
+    let (inst, len) = fetch_inst(token, ctx.sepc).ok_or(EmulateError::Unsupported)?;
+    let decoded = decode(inst, len).ok_or(EmulateError::Unsupported)?;
+    match decoded.access {
+        Access::Load { width, signed, rd } => {
+            let mut value: usize = 0;
+            for i in 0..width {
+                let byte = user_read_byte(token, addr + i).ok_or(EmulateError::AccessFault)?;
+                value |= (byte as usize) << (8 * i);
+            }
+            if signed && width < 8 {
+                let shift = 64 - 8 * width;
+                value = (((value << shift) as isize) >> shift) as usize;
+            }
+            if rd != 0 {
+                ctx.x[rd] = value;
+            }
+        }
+        Access::Store { width, rs2 } => {
+            let value = ctx.x[rs2];
+            // 与硬件一样, 中途出错时前面的字节可能已经写入
+            for i in 0..width {
+                user_write_byte(token, addr + i, (value >> (8 * i)) as u8)
+                    .ok_or(EmulateError::AccessFault)?;
+            }
+        }
+    }
+    ctx.sepc += decoded.len;
+    Ok(())
+}
@@ -1,43 +1,64 @@
-use crate::syscall::syscall;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next, trace_syscall_info};
+use crate::config::TRAMPOLINE;
+use crate::console::stdin_push;
+use crate::drivers::handle_external_irq;
+use crate::syscall::{strace_enter, strace_exit, syscall};
+use crate::task::{
+    check_signals_error_of_current, current_add_fault, current_add_signal, current_task,
+    current_trap_cx, current_trap_cx_user_va, current_user_token, exit_current_and_run_next,
+    handle_signals, hart_id, suspend_current_and_run_next, trace_syscall_info, FaultInfo,
+    FaultKind, SignalFlags,
+};
 //use crate::{batch::run_next_app, timer::set_next_trigger};
+use crate::stack_trace::print_stack_trace_from;
 use crate::timer::set_next_trigger;
-use core::arch::global_asm;
+use core::arch::{asm, global_asm};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sie;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
     stval, stvec,
 };
-use riscv::register::{sie, sstatus};
 
 use log::*;
 
 mod context;
+mod extable;
+mod misaligned;
 pub use context::TrapContext;
+use extable::search_exception_table;
+use misaligned::{emulate_misaligned, EmulateError, EXC_LOAD_MISALIGNED, EXC_STORE_MISALIGNED};
 
 global_asm!(include_str!("trap.S"));
 
 // 一般不去使用 clear_sie/set_sie来开关中断
 // 因为riscv在中断触发时,自动关闭sstatus.SIE,sret返回时自动打开sstatus.SIE
-static mut KERNEL_INTERRUPT_TRIGGERED: bool = false;
+// 内核态时钟中断的次数, 目前只有启动阶段会在内核态打开中断
+static KERNEL_TICKS: AtomicUsize = AtomicUsize::new(0);
 
-pub fn check_kernel_interrupt() -> bool {
-    unsafe { (&raw mut KERNEL_INTERRUPT_TRIGGERED).read_volatile() }
-    //unsafe { KERNEL_INTERRUPT_TRIGGERED }
-    //false
+pub fn kernel_ticks() -> usize {
+    KERNEL_TICKS.load(Ordering::Acquire)
 }
 
-pub fn mark_kernel_interrupt() {
-    unsafe { (&raw mut KERNEL_INTERRUPT_TRIGGERED).write_volatile(true) }
+/// initialize CSR `stvec` as the entry of `__kerneltrap`
+pub fn init() {
+    set_kernel_trap_entry();
 }
 
-/// initialize CSR `stvec` as the entry of `__alltraps`
-pub fn init() {
+// 在内核中执行时, Trap 直接进入 __kerneltrap
+fn set_kernel_trap_entry() {
     extern "C" {
-        fn __alltraps();
+        fn __kerneltrap();
+    }
+    unsafe {
+        stvec::write(__kerneltrap as usize, TrapMode::Direct);
     }
+}
+
+// 返回用户态前, 将 stvec 指向跳板页上的 __alltraps
+fn set_user_trap_entry() {
     unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);
+        stvec::write(TRAMPOLINE, TrapMode::Direct);
     }
 }
 
@@ -49,14 +70,15 @@ pub fn enable_timer_interrupt() {
     }
 }
 
-#[no_mangle]
-pub fn trap_handler(ctx: &mut TrapContext) -> &mut TrapContext {
-    match sstatus::read().spp() {
-        sstatus::SPP::Supervisor => kernel_trap_handler(ctx),
-        sstatus::SPP::User => user_trap_handler(ctx),
+// 外部中断经 PLIC 送达, 由 drivers 模块分发给注册的处理函数
+pub fn enable_external_interrupt() {
+    debug!("[kernel] enable external interrupt");
+    unsafe {
+        sie::set_sext();
     }
 }
 
+#[no_mangle]
 fn kernel_trap_handler(ctx: &mut TrapContext) -> &mut TrapContext {
     let scause = scause::read();
     let stval = stval::read();
@@ -68,52 +90,137 @@ fn kernel_trap_handler(ctx: &mut TrapContext) -> &mut TrapContext {
             trace!("[kenrel] interrupt: from timer");
             // println is ok...
             //println!("[kenrel] interrupt: from timer");
-            mark_kernel_interrupt();
+            KERNEL_TICKS.fetch_add(1, Ordering::Release);
+            #[cfg(feature = "profile")]
+            crate::profile::sample_kernel(ctx.sepc);
             set_next_trigger();
+            #[cfg(feature = "gdbstub")]
+            crate::gdbstub::poll(Some(ctx));
             // 这是kernel自己的异常,暂不涉及调度
         }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            panic!("[kernel] PageFault in kernel, bad addr = {:#x}, bad instruction = {:#x}, ctx = {:#x?}, kernel killed it",
-            stval, ctx.sepc, ctx);
-            // 这是kernel自己的异常,暂不涉及调度
-            // exit_current_and_run_next();
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_irq();
         }
+        // 访存异常若发生在异常表登记过的指令上(如拷贝用户数据时), 跳转到修复代码
+        Trap::Exception(
+            Exception::LoadFault
+            | Exception::LoadPageFault
+            | Exception::StoreFault
+            | Exception::StorePageFault,
+        ) if search_exception_table(ctx.sepc).is_some() => {
+            let fixup = search_exception_table(ctx.sepc).unwrap();
+            debug!(
+                "[kernel] {:?} at {:#x}, bad addr = {:#x}, fixup to {:#x}",
+                scause.cause(),
+                ctx.sepc,
+                stval,
+                fixup
+            );
+            ctx.sepc = fixup;
+        }
+        // 启用 gdbstub 时, 内核中的 ebreak 交给调试器处理
+        #[cfg(feature = "gdbstub")]
+        Trap::Exception(Exception::Breakpoint) if crate::gdbstub::handle_breakpoint(ctx) => {}
+        // 其余的内核异常及未知中断均无法恢复, 若直接返回会反复执行出错的指令
         _ => {
-            error!("[kernel] unknown exception or interrupt");
-            // 这是kernel自己的异常,暂不涉及调度
-            //exit_current_and_run_next();
+            dump_kernel_trap(ctx, scause.bits(), stval);
+            panic!(
+                "[kernel] unrecoverable {:?} in kernel, bad addr = {:#x}, bad instruction = {:#x}",
+                scause.cause(),
+                stval,
+                ctx.sepc
+            );
         }
     }
 
     ctx
 }
 
-fn user_trap_handler(ctx: &mut TrapContext) -> &mut TrapContext {
+// 寄存器的 ABI 名称, 按 x0 ~ x31 排列
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+// 打印内核 trap 发生时的全部寄存器, 并从出错处回溯调用栈
+fn dump_kernel_trap(ctx: &TrapContext, scause: usize, stval: usize) {
+    println!("=========KERNEL TRAP=========");
+    println!(
+        "scause = {:#018x}, stval = {:#018x}, sepc = {:#018x}, sstatus = {:#018x}",
+        scause,
+        stval,
+        ctx.sepc,
+        ctx.sstatus.bits()
+    );
+    // __kerneltrap 未保存 x0/tp, 对应位置的值没有意义
+    for (i, chunk) in ctx.x.chunks(4).enumerate() {
+        for (j, value) in chunk.iter().enumerate() {
+            let reg = i * 4 + j;
+            print!("{:>4}(x{:<2}) = {:#018x}  ", REG_NAMES[reg], reg, value);
+        }
+        println!();
+    }
+    unsafe {
+        // x8 即 fp, 从 trap 发生处的栈帧开始回溯
+        print_stack_trace_from(Some(ctx.sepc), ctx.x[8]);
+    }
+}
+
+// 应用 Trap 进内核后, 由 __alltraps 跳转到这里
+// 此时已经处于内核地址空间, Trap 上下文在当前线程的 TRAP_CONTEXT 页面中
+#[no_mangle]
+pub fn trap_handler() -> ! {
+    set_kernel_trap_entry();
     // 从此开始属于kernel, 也是user time的暂停/停止点
     crate::task::user_end_and_kernel_time_start();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
+            let mut ctx = current_trap_cx();
             ctx.sepc += 4;
-            trace_syscall_info(ctx.x[17]);
-            ctx.x[10] = syscall(ctx.x[17], [ctx.x[10], ctx.x[11], ctx.x[12]]) as usize;
+            let syscall_id = ctx.x[17];
+            let args = [ctx.x[10], ctx.x[11], ctx.x[12]];
+            let strace = trace_syscall_info(syscall_id);
+            let entry = strace_enter(syscall_id, args, strace);
+            let result = syscall(syscall_id, args);
+            strace_exit(entry, result);
+            // 系统调用期间可能发生了线程切换或新建, 要重新获取 Trap 上下文
+            ctx = current_trap_cx();
+            ctx.x[10] = result as usize;
         }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            error!("[kernel] PageFault in application, kernel killed it.");
-            //run_next_app();
-            exit_current_and_run_next();
+        // 非对齐访存先尝试模拟, 失败时才向应用发送信号
+        Trap::Exception(_)
+            if scause.code() == EXC_LOAD_MISALIGNED || scause.code() == EXC_STORE_MISALIGNED =>
+        {
+            let is_load = scause.code() == EXC_LOAD_MISALIGNED;
+            if let Err(err) = emulate_misaligned(current_trap_cx(), current_user_token(), stval) {
+                let kind = match (err, is_load) {
+                    (EmulateError::Unsupported, true) => FaultKind::LoadMisaligned,
+                    (EmulateError::Unsupported, false) => FaultKind::StoreMisaligned,
+                    (EmulateError::AccessFault, true) => FaultKind::LoadPageFault,
+                    (EmulateError::AccessFault, false) => FaultKind::StorePageFault,
+                };
+                report_user_fault(kind, stval);
+            }
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            error!("[kernel] IllegalInstruction in application, kernel killed it.");
+        Trap::Exception(exception) => {
             //run_next_app();
-            exit_current_and_run_next();
+            report_user_fault(fault_kind(exception), stval);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             debug!("[kernel] SupervisorTimer");
+            #[cfg(feature = "profile")]
+            crate::profile::sample_user(current_trap_cx().sepc);
             set_next_trigger();
+            #[cfg(feature = "gdbstub")]
+            crate::gdbstub::poll(None);
             suspend_current_and_run_next();
         }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_irq();
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
@@ -122,7 +229,85 @@ fn user_trap_handler(ctx: &mut TrapContext) -> &mut TrapContext {
             );
         }
     }
+    handle_signals();
+    // 信号的默认动作为终止时, 以 -signum 作为退出码
+    if let Some(reason) = check_signals_error_of_current() {
+        error!("[kernel] application {}", reason);
+        exit_current_and_run_next(reason);
+    }
+    trap_return();
+}
+
+fn fault_kind(exception: Exception) -> FaultKind {
+    match exception {
+        Exception::StoreFault => FaultKind::StoreFault,
+        Exception::StorePageFault => FaultKind::StorePageFault,
+        Exception::IllegalInstruction => FaultKind::IllegalInstruction,
+        Exception::LoadFault => FaultKind::LoadFault,
+        Exception::LoadPageFault => FaultKind::LoadPageFault,
+        Exception::InstructionFault => FaultKind::InstructionFault,
+        Exception::InstructionPageFault => FaultKind::InstructionPageFault,
+        Exception::InstructionMisaligned => FaultKind::InstructionMisaligned,
+        Exception::StoreMisaligned => FaultKind::StoreMisaligned,
+        Exception::Breakpoint => FaultKind::Breakpoint,
+        _ => FaultKind::Unknown,
+    }
+}
+
+// 应用触发了异常: 打印报告, 并向当前线程发送对应的信号
+// 信号未被处理时, 线程会以该异常作为退出原因被终止
+fn report_user_fault(kind: FaultKind, stval: usize) {
+    let sepc = current_trap_cx().sepc;
+    error!(
+        "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, send {:?}.",
+        kind,
+        stval,
+        sepc,
+        kind.signal()
+    );
+    current_add_fault(FaultInfo {
+        kind,
+        addr: stval,
+        pc: sepc,
+    });
+}
+
+const CTRL_C: u8 = 0x03;
+
+// 控制台串口收到一个字符时由其中断处理函数调用
+// Ctrl-C 向当前线程发送 SIGINT, 其余字符放入标准输入的缓冲区, 留给 sys_read
+pub fn console_input(c: u8) {
+    if c != CTRL_C {
+        stdin_push(c);
+    } else if current_task().is_some() {
+        current_add_signal(SignalFlags::SIGINT);
+    }
+}
+
+// 回到用户态
+// 跳转到跳板页上 __restore 的虚拟地址, 并传入当前线程 Trap 上下文的虚拟地址及应用地址空间的 token
+#[no_mangle]
+pub fn trap_return() -> ! {
+    set_user_trap_entry();
+    // 线程可能在其他 hart 上被调度执行, 记录下本次返回用户态时所在的 hart
+    current_trap_cx().kernel_tp = hart_id();
+    let trap_cx_ptr = current_trap_cx_user_va();
+    let user_satp = current_user_token();
+    extern "C" {
+        fn __alltraps();
+        fn __restore();
+    }
+    let restore_va = __restore as usize - __alltraps as usize + TRAMPOLINE;
     // 从此开始属于user, 也是kernel time 的暂停/停止点
     crate::task::kernel_end_and_user_time_start();
-    ctx
+    unsafe {
+        asm!(
+            "fence.i",
+            "jr {restore_va}",
+            restore_va = in(reg) restore_va,
+            in("a0") trap_cx_ptr,
+            in("a1") user_satp,
+            options(noreturn)
+        );
+    }
 }
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{exit, gettid, print, println, thread_create, waittid};
+
+fn thread_a() -> ! {
+    for _ in 0..100 {
+        print!("a");
+    }
+    exit(1);
+    unreachable!()
+}
+
+fn thread_b() -> ! {
+    for _ in 0..100 {
+        print!("b");
+    }
+    exit(2);
+    unreachable!()
+}
+
+fn thread_c() -> ! {
+    for _ in 0..100 {
+        print!("c");
+    }
+    exit(3);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test threads Start! main tid = {}", gettid());
+    let tids = [
+        thread_create(thread_a as usize, 0),
+        thread_create(thread_b as usize, 0),
+        thread_create(thread_c as usize, 0),
+    ];
+    for (i, tid) in tids.iter().enumerate() {
+        let exit_code = waittid(*tid as usize);
+        println!("\nthread#{} exited with code {}", tid, exit_code);
+        assert_eq!(exit_code, i as isize + 1);
+    }
+    // 已经回收过的线程以及自身都不能再 wait
+    assert_eq!(waittid(tids[0] as usize), -1);
+    assert_eq!(waittid(gettid() as usize), -1);
+    println!("Test threads OK!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{exit, println, thread_create, waittid, yield_};
+
+const THREAD_NUM: usize = 8;
+const PER_THREAD: usize = 1000;
+
+// 所有线程共享同一个地址空间, 因此可以直接访问同一个全局变量
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static mut SLOTS: [usize; THREAD_NUM] = [0; THREAD_NUM];
+
+struct Argument {
+    idx: usize,
+    value: usize,
+}
+
+fn worker(arg: *const Argument) -> ! {
+    let arg = unsafe { &*arg };
+    for i in 0..PER_THREAD {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+        // 偶尔让出CPU, 让其他线程交错执行
+        if i % 100 == 0 {
+            yield_();
+        }
+    }
+    unsafe {
+        SLOTS[arg.idx] = arg.value;
+    }
+    exit(arg.idx as i32);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test threads with args Start!");
+    let args: [Argument; THREAD_NUM] = core::array::from_fn(|idx| Argument {
+        idx,
+        value: (idx + 1) * 10,
+    });
+    let mut tids = [0usize; THREAD_NUM];
+    for (i, arg) in args.iter().enumerate() {
+        tids[i] = thread_create(worker as usize, arg as *const _ as usize) as usize;
+    }
+    for (i, tid) in tids.iter().enumerate() {
+        assert_eq!(waittid(*tid), i as isize);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREAD_NUM * PER_THREAD);
+    for i in 0..THREAD_NUM {
+        assert_eq!(unsafe { SLOTS[i] }, (i + 1) * 10);
+    }
+    println!("Test threads with args OK!");
+    0
+}
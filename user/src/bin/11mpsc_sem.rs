@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{exit, println, thread_create, waittid};
+use user_lib::{semaphore_create, semaphore_down, semaphore_up};
+
+// 多生产者单消费者, 使用信号量实现的有界缓冲区
+const SEM_MUTEX: usize = 0;
+const SEM_EMPTY: usize = 1;
+const SEM_AVAIL: usize = 2;
+const BUFFER_SIZE: usize = 8;
+const PRODUCER_COUNT: usize = 4;
+const NUMBER_PER_PRODUCER: usize = 100;
+
+static mut BUFFER: [usize; BUFFER_SIZE] = [0; BUFFER_SIZE];
+static mut FRONT: usize = 0;
+static mut TAIL: usize = 0;
+
+fn producer(id: usize) -> ! {
+    for _ in 0..NUMBER_PER_PRODUCER {
+        semaphore_down(SEM_EMPTY);
+        semaphore_down(SEM_MUTEX);
+        unsafe {
+            BUFFER[TAIL] = id;
+            TAIL = (TAIL + 1) % BUFFER_SIZE;
+        }
+        semaphore_up(SEM_MUTEX);
+        semaphore_up(SEM_AVAIL);
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test mpsc semaphore Start!");
+    assert_eq!(semaphore_create(1) as usize, SEM_MUTEX);
+    assert_eq!(semaphore_create(BUFFER_SIZE) as usize, SEM_EMPTY);
+    assert_eq!(semaphore_create(0) as usize, SEM_AVAIL);
+    let mut tids = [0usize; PRODUCER_COUNT];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        *tid = thread_create(producer as usize, id) as usize;
+    }
+    // 统计每个生产者产出的数量
+    let mut received = [0usize; PRODUCER_COUNT];
+    for _ in 0..PRODUCER_COUNT * NUMBER_PER_PRODUCER {
+        semaphore_down(SEM_AVAIL);
+        semaphore_down(SEM_MUTEX);
+        let id = unsafe {
+            let id = BUFFER[FRONT];
+            FRONT = (FRONT + 1) % BUFFER_SIZE;
+            id
+        };
+        semaphore_up(SEM_MUTEX);
+        semaphore_up(SEM_EMPTY);
+        received[id] += 1;
+    }
+    for tid in tids.iter() {
+        waittid(*tid);
+    }
+    for count in received.iter() {
+        assert_eq!(*count, NUMBER_PER_PRODUCER);
+    }
+    println!("Test mpsc semaphore OK!");
+    0
+}
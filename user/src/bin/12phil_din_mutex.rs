@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{exit, get_time, println, thread_create, waittid, yield_};
+use user_lib::{mutex_blocking_create, mutex_lock, mutex_unlock};
+
+// 哲学家就餐问题
+// 每位哲学家总是先拿编号较小的叉子, 从而打破循环等待, 避免死锁
+const N: usize = 5;
+const ROUND: usize = 4;
+// 思考与就餐的时长, 单位与 get_time 相同
+const THINK: [isize; N] = [700, 500, 900, 300, 600];
+const EAT: [isize; N] = [500, 300, 200, 400, 100];
+
+static mut EAT_TIMES: [usize; N] = [0; N];
+
+fn busy_wait(duration: isize) {
+    let end = get_time() + duration;
+    while get_time() < end {
+        yield_();
+    }
+}
+
+fn philosopher(id: usize) -> ! {
+    let left = id;
+    let right = (id + 1) % N;
+    let (min, max) = if left < right {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    for _ in 0..ROUND {
+        busy_wait(THINK[id]);
+        mutex_lock(min);
+        mutex_lock(max);
+        busy_wait(EAT[id]);
+        unsafe {
+            EAT_TIMES[id] += 1;
+        }
+        mutex_unlock(max);
+        mutex_unlock(min);
+    }
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test philosopher dining problem Start!");
+    for i in 0..N {
+        assert_eq!(mutex_blocking_create() as usize, i);
+    }
+    let mut tids = [0usize; N];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        *tid = thread_create(philosopher as usize, id) as usize;
+    }
+    for tid in tids.iter() {
+        waittid(*tid);
+    }
+    for i in 0..N {
+        assert_eq!(unsafe { EAT_TIMES[i] }, ROUND);
+    }
+    println!("Test philosopher dining problem OK!");
+    0
+}
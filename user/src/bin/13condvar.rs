@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{condvar_create, condvar_signal, condvar_wait};
+use user_lib::{exit, println, thread_create, waittid, yield_};
+use user_lib::{mutex_blocking_create, mutex_lock, mutex_unlock};
+
+static mut A: usize = 0;
+
+const CONDVAR_ID: usize = 0;
+const MUTEX_ID: usize = 0;
+
+fn first() -> ! {
+    // 让 second 先进入等待
+    for _ in 0..10 {
+        yield_();
+    }
+    println!("First work, Change A --> 1 and wakeup Second");
+    mutex_lock(MUTEX_ID);
+    unsafe {
+        A = 1;
+    }
+    condvar_signal(CONDVAR_ID);
+    mutex_unlock(MUTEX_ID);
+    exit(0);
+    unreachable!()
+}
+
+fn second() -> ! {
+    println!("Second want to continue, but need to wait A=1");
+    mutex_lock(MUTEX_ID);
+    while unsafe { A } == 0 {
+        println!("Second: A is {}", unsafe { A });
+        condvar_wait(CONDVAR_ID, MUTEX_ID);
+    }
+    mutex_unlock(MUTEX_ID);
+    println!("A is {}, Second can work now", unsafe { A });
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test condvar Start!");
+    assert_eq!(condvar_create() as usize, CONDVAR_ID);
+    assert_eq!(mutex_blocking_create() as usize, MUTEX_ID);
+    let tids = [
+        thread_create(first as usize, 0),
+        thread_create(second as usize, 0),
+    ];
+    for tid in tids.iter() {
+        waittid(*tid as usize);
+    }
+    assert_eq!(unsafe { A }, 1);
+    println!("Test condvar OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use user_lib::{enable_deadlock_detect, exit, println, thread_create, waittid, yield_, EDEADLK};
+use user_lib::{mutex_blocking_create, mutex_lock, mutex_unlock};
+
+// main 持有锁 0 后, 子线程持有锁 1 并等待锁 0
+// 此时 main 再去请求锁 1 就会形成循环等待, 应当被拒绝
+static WAITING: AtomicBool = AtomicBool::new(false);
+
+fn worker() -> ! {
+    assert_eq!(mutex_lock(1), 0);
+    WAITING.store(true, Ordering::Relaxed);
+    // 这里会阻塞, 直到 main 释放锁 0
+    assert_eq!(mutex_lock(0), 0);
+    mutex_unlock(0);
+    mutex_unlock(1);
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test deadlock detect (mutex) Start!");
+    assert_eq!(enable_deadlock_detect(true), 0);
+    assert_eq!(mutex_blocking_create(), 0);
+    assert_eq!(mutex_blocking_create(), 1);
+    // 不能释放没有上锁的锁
+    assert_eq!(mutex_unlock(0), -1);
+    assert_eq!(mutex_lock(0), 0);
+    let tid = thread_create(worker as usize, 0) as usize;
+    while !WAITING.load(Ordering::Relaxed) {
+        yield_();
+    }
+    // 保证 worker 已经阻塞在锁 0 上
+    yield_();
+    assert_eq!(mutex_lock(1), -EDEADLK);
+    // 也不能释放其他线程持有的锁
+    assert_eq!(mutex_unlock(1), -1);
+    println!("deadlock detected");
+    mutex_unlock(0);
+    assert_eq!(waittid(tid), 0);
+    // worker 已经释放了所有锁, 再次请求是安全的
+    assert_eq!(mutex_lock(1), 0);
+    mutex_unlock(1);
+    println!("Test deadlock detect (mutex) OK!");
+    0
+}
@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{enable_deadlock_detect, exit, println, thread_create, waittid, yield_, EDEADLK};
+use user_lib::{semaphore_create, semaphore_down, semaphore_up};
+
+// 三个线程, 三个初值为 1 的信号量
+// 线程 i 先拿到信号量 i, 再请求信号量 (i + 1) % 3
+// 前两个线程的请求是安全的, 最后一个会构成环路, 应当被拒绝
+const N: usize = 3;
+static READY: AtomicUsize = AtomicUsize::new(0);
+static REJECTED: AtomicUsize = AtomicUsize::new(0);
+
+fn worker(id: usize) -> ! {
+    assert_eq!(semaphore_down(id), 0);
+    READY.fetch_add(1, Ordering::Relaxed);
+    // 等待所有线程都拿到各自的第一个信号量
+    while READY.load(Ordering::Relaxed) < N {
+        yield_();
+    }
+    // 按 id 顺序依次发出第二个请求
+    while READY.load(Ordering::Relaxed) < N + id {
+        yield_();
+    }
+    let next = (id + 1) % N;
+    READY.fetch_add(1, Ordering::Relaxed);
+    if semaphore_down(next) == -EDEADLK {
+        println!("thread {} rejected", id);
+        REJECTED.fetch_add(1, Ordering::Relaxed);
+        semaphore_up(id);
+        exit(-1);
+    } else {
+        semaphore_up(next);
+        semaphore_up(id);
+        exit(0);
+    }
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test deadlock detect (semaphore) Start!");
+    assert_eq!(enable_deadlock_detect(true), 0);
+    for i in 0..N {
+        assert_eq!(semaphore_create(1) as usize, i);
+    }
+    let mut tids = [0usize; N];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        *tid = thread_create(worker as usize, id) as usize;
+    }
+    let mut rejected = 0;
+    for tid in tids.iter() {
+        if waittid(*tid) == -1 {
+            rejected += 1;
+        }
+    }
+    assert_eq!(rejected, 1);
+    assert_eq!(REJECTED.load(Ordering::Relaxed), 1);
+    println!("Test deadlock detect (semaphore) OK!");
+    0
+}
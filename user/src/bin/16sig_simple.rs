@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{getpid, kill, println, sig_mask, sigaction, sigprocmask, sigreturn, yield_};
+use user_lib::{SignalAction, SIGKILL, SIGUSR1};
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+fn func(signum: usize) {
+    assert_eq!(signum, SIGUSR1 as usize);
+    println!("user_sig_test succsess");
+    HANDLED.fetch_add(1, Ordering::Relaxed);
+    sigreturn();
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test signal Start!");
+    let new = SignalAction {
+        handler: func as usize,
+        mask: 0,
+    };
+    let mut old = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, Some(&new), Some(&mut old)), 0);
+    assert_eq!(old.handler, 0);
+    // SIGKILL 不能被捕获
+    assert_eq!(sigaction(SIGKILL, Some(&new), None), -1);
+
+    println!("signal_simple: kill");
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    // 信号在 kill 返回用户态之前就已经被处理
+    assert_eq!(HANDLED.load(Ordering::Relaxed), 1);
+
+    // 屏蔽后信号保持 pending, 解除屏蔽时才递送
+    assert_eq!(sigprocmask(sig_mask(SIGUSR1)), 0);
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    yield_();
+    assert_eq!(HANDLED.load(Ordering::Relaxed), 1);
+    assert_eq!(sigprocmask(0), sig_mask(SIGUSR1) as isize);
+    assert_eq!(HANDLED.load(Ordering::Relaxed), 2);
+    println!("Test signal OK!");
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+use core::ptr::null_mut;
+use user_lib::{exit, println, sigaction, SignalAction, SIGSEGV};
+
+// 访存异常会被转换为 SIGSEGV, 由用户注册的处理函数接管
+// 处理函数不能简单地 sigreturn, 否则会回到出错的指令反复触发异常
+fn segv_handler(signum: usize) {
+    assert_eq!(signum, SIGSEGV as usize);
+    println!("SIGSEGV caught, exit gracefully");
+    println!("Test SIGSEGV handler OK!");
+    exit(0);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test SIGSEGV handler Start!");
+    let action = SignalAction {
+        handler: segv_handler as usize,
+        mask: 0,
+    };
+    assert_eq!(sigaction(SIGSEGV, Some(&action), None), 0);
+    unsafe {
+        null_mut::<u8>().write_volatile(1);
+    }
+    panic!("should not reach here");
+}
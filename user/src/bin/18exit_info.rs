@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{getpid, println, spawn, waitpid, ExitInfo};
+use user_lib::{
+    EXIT_FAULT, EXIT_NORMAL, FAULT_ILLEGAL_INSTRUCTION, FAULT_STORE_PAGE, SIGILL, SIGSEGV,
+};
+
+// 启动时加载的应用按名称排序, pid 从 0 开始分配, 它们不是本进程的子进程
+const BOOT_HELLO_WORLD_PID: usize = 0;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test exit info Start!");
+    let mut info = ExitInfo::default();
+
+    let hello_world = spawn("00hello_world");
+    assert!(hello_world > 0);
+    assert_eq!(waitpid(hello_world as usize, &mut info), hello_world);
+    assert_eq!(info.reason, EXIT_NORMAL);
+    assert_eq!(info.code, 0);
+    // 子进程已被回收, 再次等待失败
+    assert_eq!(waitpid(hello_world as usize, &mut info), -1);
+
+    // 01store_fault 向空指针写入, 因 SIGSEGV 被终止
+    let store_fault = spawn("01store_fault");
+    assert_eq!(waitpid(store_fault as usize, &mut info), store_fault);
+    println!("store_fault: {:?}", info);
+    assert_eq!(info.reason, EXIT_FAULT);
+    assert_eq!(info.code, -SIGSEGV as isize);
+    assert_eq!(info.fault_kind, FAULT_STORE_PAGE);
+    assert_eq!(info.fault_addr, 0);
+
+    // 03priv_inst 在用户态执行 sret, 因 SIGILL 被终止
+    let priv_inst = spawn("03priv_inst");
+    assert_eq!(waitpid(priv_inst as usize, &mut info), priv_inst);
+    println!("priv_inst: {:?}", info);
+    assert_eq!(info.reason, EXIT_FAULT);
+    assert_eq!(info.code, -SIGILL as isize);
+    assert_eq!(info.fault_kind, FAULT_ILLEGAL_INSTRUCTION);
+
+    // 不存在的应用
+    assert_eq!(spawn("no_such_app"), -1);
+    // 不是子进程的进程与不存在的进程
+    assert_eq!(waitpid(BOOT_HELLO_WORLD_PID, &mut info), -1);
+    assert_eq!(waitpid(getpid() as usize, &mut info), -1);
+    assert_eq!(waitpid(usize::MAX, &mut info), -1);
+    println!("pid {} Test exit info OK!", getpid());
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use core::arch::asm;
+use user_lib::println;
+
+// 非对齐访存: 若硬件(或 SBI)不支持, 内核会解码指令并按字节模拟
+// 这里直接用汇编生成访存指令, 避免编译器将其拆成按字节访问
+#[repr(align(8))]
+struct Buffer([u8; 32]);
+
+static mut BUF: Buffer = Buffer([0; 32]);
+
+fn base() -> usize {
+    core::ptr::addr_of_mut!(BUF) as usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test misaligned access Start!");
+    let addr = base() + 1;
+    let value: usize = 0x8877_6655_4433_2211;
+    let (mut d, mut w, mut wu, mut h, mut hu): (usize, usize, usize, usize, usize);
+    unsafe {
+        asm!("sd {v}, 0({a})", v = in(reg) value, a = in(reg) addr);
+        asm!("ld {d}, 0({a})", d = out(reg) d, a = in(reg) addr);
+        asm!("lw {d}, 4({a})", d = out(reg) w, a = in(reg) addr);
+        asm!("lwu {d}, 4({a})", d = out(reg) wu, a = in(reg) addr);
+        asm!("lh {d}, 6({a})", d = out(reg) h, a = in(reg) addr);
+        asm!("lhu {d}, 6({a})", d = out(reg) hu, a = in(reg) addr);
+    }
+    assert_eq!(d, value);
+    // 高位为 1, 需要正确地符号扩展
+    assert_eq!(w, 0xffff_ffff_8877_6655);
+    assert_eq!(wu, 0x8877_6655);
+    assert_eq!(h, 0xffff_ffff_ffff_8877);
+    assert_eq!(hu, 0x8877);
+
+    unsafe {
+        asm!("sw {v}, 10({a})", v = in(reg) 0xdead_beefusize, a = in(reg) addr);
+        asm!("sh {v}, 16({a})", v = in(reg) 0x1234usize, a = in(reg) addr);
+        assert_eq!(BUF.0[11..15], [0xef, 0xbe, 0xad, 0xde]);
+        assert_eq!(BUF.0[17..19], [0x34, 0x12]);
+    }
+    println!("Test misaligned access OK!");
+    0
+}
@@ -0,0 +1,18 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{println, write, EFAULT};
+
+// 向内核传入不可访问的指针时, 系统调用应返回 -EFAULT 而不是让内核 panic
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test bad user pointer Start!");
+    // 0 号页未被映射
+    let unmapped = unsafe { core::slice::from_raw_parts(0x10 as *const u8, 16) };
+    assert_eq!(write(1, unmapped), -EFAULT);
+    // 内核所在的地址在应用地址空间中不可访问
+    let kernel = unsafe { core::slice::from_raw_parts(0x8020_0000 as *const u8, 16) };
+    assert_eq!(write(1, kernel), -EFAULT);
+    println!("Test bad user pointer OK!");
+    0
+}
@@ -0,0 +1,13 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{debug_backtrace, println};
+
+// 内核应打印带符号的调用栈, 最外层为 trap_handler
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test kernel backtrace Start!");
+    assert_eq!(debug_backtrace(), 0);
+    println!("Test kernel backtrace OK!");
+    0
+}
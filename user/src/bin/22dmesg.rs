@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+use core::ptr::addr_of_mut;
+use user_lib::{
+    dmesg, println, strace, syslog, syslog_set_level, write, LOG_BUF_SIZE, SYSLOG_ACTION_READ,
+    SYSLOG_ACTION_SIZE_BUFFER, SYSLOG_ACTION_SIZE_UNREAD,
+};
+
+// 用户栈放不下整个日志缓冲区
+static mut LOG: [u8; LOG_BUF_SIZE] = [0; LOG_BUF_SIZE];
+
+const MARKER: &str = "buf=\"dmesg marker\\n\"";
+
+// 在 strace 打开时写一行, 向内核日志写入一条只属于本应用的记录
+fn log_marker() {
+    assert!(!strace(true));
+    assert_eq!(write(1, b"dmesg marker\n"), 13);
+    assert!(strace(false));
+}
+
+// 读取内核日志, 并在运行时修改内核的日志级别
+// 其他应用同时在运行, 也会产生日志, 因此不清空缓冲区, 也只检查本应用的记录
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test dmesg Start!");
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, &mut []);
+    assert_eq!(size, LOG_BUF_SIZE as isize);
+
+    // 内核启动时已经输出过日志
+    let mut buf = [0u8; 1024];
+    let len = dmesg(&mut buf);
+    assert!(len > 0);
+    println!("last {} bytes of kernel log:", len);
+    println!("{}", core::str::from_utf8(&buf[..len as usize]).unwrap());
+
+    // strace 以 info 级别输出; 23strace 同时在运行, 因此之后不恢复该模块的级别
+    assert_eq!(syslog_set_level("os::syscall::trace=info"), 0);
+    let was_enabled = strace(false);
+    let log = unsafe { &mut *addr_of_mut!(LOG) };
+    // 读走已有的日志, 之后 READ 只能读到新的日志
+    syslog(SYSLOG_ACTION_READ, log);
+
+    log_marker();
+    assert!(syslog(SYSLOG_ACTION_SIZE_UNREAD, &mut []) > 0);
+    let len = syslog(SYSLOG_ACTION_READ, log);
+    assert!(len > 0);
+    let text = core::str::from_utf8(&log[..len as usize]).unwrap();
+    assert!(text.contains(MARKER));
+
+    // 读过的日志不会再被 READ 读到
+    let len = syslog(SYSLOG_ACTION_READ, log);
+    assert!(len >= 0);
+    let text = core::str::from_utf8(&log[..len as usize]).unwrap();
+    assert!(!text.contains(MARKER));
+    strace(was_enabled);
+
+    assert_eq!(syslog_set_level("os::task=debug"), 0);
+    assert_eq!(syslog_set_level("os::task=default"), 0);
+    assert_eq!(syslog_set_level("os::task=loud"), -1);
+    assert_eq!(syslog_set_level("=info"), -1);
+    println!("Test dmesg OK!");
+    0
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+use core::ptr::addr_of_mut;
+use user_lib::{dmesg, getpid, println, strace, syslog_set_level, write, yield_, LOG_BUF_SIZE};
+
+// 用户栈放不下整个日志缓冲区
+static mut LOG: [u8; LOG_BUF_SIZE] = [0; LOG_BUF_SIZE];
+
+// 打开 strace 后执行几个系统调用, 再从内核日志中检查它们是否被记录
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test strace Start!");
+    // strace 以 info 级别输出, 避免被编译时指定的日志级别过滤掉
+    // 22dmesg 同时在运行, 因此不清空日志缓冲区, 之后也不恢复该模块的级别
+    assert_eq!(syslog_set_level("os::syscall::trace=info"), 0);
+
+    // 编译内核时设置了 STRACE 的话, strace 默认就是打开的
+    let was_enabled = strace(true);
+    assert!(getpid() >= 0);
+    assert_eq!(yield_(), 0);
+    assert_eq!(write(1, b"traced write\n"), 13);
+    assert!(strace(was_enabled));
+
+    // 其他应用同时在输出日志, 要在整个缓冲区中查找, 不能只看最新的一部分
+    let buf = unsafe { &mut *addr_of_mut!(LOG) };
+    let len = dmesg(buf);
+    assert!(len > 0);
+    let log = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    assert!(log.contains("getpid() = "));
+    assert!(log.contains("yield() = 0"));
+    assert!(log.contains("write(fd=1, buf=\"traced write\\n\", len=13) = 13"));
+    assert!(log.contains("strace(enable=0) = 1") || was_enabled);
+
+    println!("Test strace OK!");
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+use user_lib::{exit, getpid, kill, println, thread_create, yield_};
+use user_lib::{semaphore_create, semaphore_down, SIGKILL};
+
+// 主线程阻塞在信号量上时被子线程发来的 SIGKILL 终止
+fn killer(_arg: usize) -> ! {
+    // 让主线程先进入阻塞
+    for _ in 0..10 {
+        yield_();
+    }
+    println!("kill blocked main thread");
+    kill(getpid() as usize, SIGKILL);
+    exit(0);
+    unreachable!()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Test kill blocked Start!");
+    assert_eq!(semaphore_create(0), 0);
+    thread_create(killer as usize, 0);
+    // 没有线程会 up, 只能被 SIGKILL 唤醒并终止
+    semaphore_down(0);
+    println!("should not reach here");
+    1
+}
@@ -39,6 +39,10 @@ fn main() -> i32 {
 }
 
 use syscall::*;
+pub fn read(fd: usize, buffer: &mut [u8]) -> isize {
+    sys_read(fd, buffer)
+}
+
 pub fn write(fd: usize, buffer: &[u8]) -> isize {
     sys_write(fd, buffer)
 }
@@ -55,3 +59,221 @@ pub fn yield_() -> isize {
 pub fn get_time() -> isize {
     sys_get_time()
 }
+
+// 信号编号, 与内核保持一致
+pub const SIGINT: i32 = 2;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGTERM: i32 = 15;
+
+// 编号为 signum 的信号在屏蔽字中对应的位
+pub const fn sig_mask(signum: i32) -> u32 {
+    1 << signum
+}
+
+// 布局需要与内核中的 SignalAction 保持一致
+// handler 为 0 表示使用默认动作, mask 为执行处理函数期间额外屏蔽的信号
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub mask: u32,
+}
+
+pub fn kill(pid: usize, signum: i32) -> isize {
+    sys_kill(pid, signum)
+}
+
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |a| a as *const _),
+        old_action.map_or(core::ptr::null_mut(), |a| a as *mut _),
+    )
+}
+
+pub fn sigprocmask(mask: u32) -> isize {
+    sys_sigprocmask(mask)
+}
+
+// 信号处理函数不能直接返回, 必须以 sigreturn 结束
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+
+// 退出原因
+pub const EXIT_NORMAL: usize = 0;
+pub const EXIT_FAULT: usize = 1;
+pub const EXIT_SIGNALED: usize = 2;
+
+// 异常类型, 仅在 reason 为 EXIT_FAULT 时有意义
+pub const FAULT_STORE: usize = 1;
+pub const FAULT_STORE_PAGE: usize = 2;
+pub const FAULT_ILLEGAL_INSTRUCTION: usize = 3;
+pub const FAULT_LOAD: usize = 4;
+pub const FAULT_LOAD_PAGE: usize = 5;
+pub const FAULT_INSTRUCTION: usize = 6;
+pub const FAULT_INSTRUCTION_PAGE: usize = 7;
+pub const FAULT_INSTRUCTION_MISALIGNED: usize = 8;
+pub const FAULT_LOAD_MISALIGNED: usize = 9;
+pub const FAULT_STORE_MISALIGNED: usize = 10;
+pub const FAULT_BREAKPOINT: usize = 11;
+pub const FAULT_UNKNOWN: usize = 12;
+
+// 布局需要与内核中的 ExitInfo 保持一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExitInfo {
+    pub reason: usize,
+    pub code: isize,
+    pub fault_kind: usize,
+    pub fault_addr: usize,
+    pub fault_pc: usize,
+    pub user_time: usize,
+    pub kernel_time: usize,
+}
+
+// 阻塞式等待子进程 pid 退出, 并取得它的退出信息
+pub fn waitpid(pid: usize, info: &mut ExitInfo) -> isize {
+    loop {
+        match sys_waitpid(pid, info as *mut _) {
+            -2 => {
+                yield_();
+            }
+            ret => return ret,
+        }
+    }
+}
+
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+
+pub fn spawn(name: &str) -> isize {
+    sys_spawn(name)
+}
+
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    sys_thread_create(entry, arg)
+}
+
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+// 阻塞式等待: 线程尚未退出时主动让出CPU, 直到拿到退出码
+pub fn waittid(tid: usize) -> isize {
+    loop {
+        match sys_waittid(tid) {
+            -2 => {
+                yield_();
+            }
+            exit_code => return exit_code,
+        }
+    }
+}
+
+// Interrupted system call, 阻塞的等待被终止信号打断
+pub const EINTR: isize = 4;
+// Resource deadlock would occur
+pub const EDEADLK: isize = 35;
+// 传入的指针不可访问
+pub const EFAULT: isize = 14;
+
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    sys_enable_deadlock_detect(enabled as usize)
+}
+
+pub fn mutex_create() -> isize {
+    sys_mutex_create(false)
+}
+
+pub fn mutex_blocking_create() -> isize {
+    sys_mutex_create(true)
+}
+
+pub fn mutex_lock(mutex_id: usize) -> isize {
+    sys_mutex_lock(mutex_id)
+}
+
+// 锁没有上锁或者由其他线程持有时返回 -1
+pub fn mutex_unlock(mutex_id: usize) -> isize {
+    sys_mutex_unlock(mutex_id)
+}
+
+pub fn semaphore_create(res_count: usize) -> isize {
+    sys_semaphore_create(res_count)
+}
+
+pub fn semaphore_up(sem_id: usize) {
+    sys_semaphore_up(sem_id);
+}
+
+pub fn semaphore_down(sem_id: usize) -> isize {
+    sys_semaphore_down(sem_id)
+}
+
+pub fn condvar_create() -> isize {
+    sys_condvar_create()
+}
+
+pub fn condvar_signal(condvar_id: usize) {
+    sys_condvar_signal(condvar_id);
+}
+
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
+    sys_condvar_wait(condvar_id, mutex_id);
+}
+
+pub fn debug_backtrace() -> isize {
+    sys_debug_backtrace()
+}
+
+// 打开或关闭当前线程的 strace, 返回之前是否打开
+pub fn strace(enable: bool) -> bool {
+    sys_strace(enable as usize) == 1
+}
+
+// sys_syslog 的 action
+pub const SYSLOG_ACTION_READ: usize = 2;
+pub const SYSLOG_ACTION_READ_ALL: usize = 3;
+pub const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+pub const SYSLOG_ACTION_CLEAR: usize = 5;
+pub const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+pub const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+pub const SYSLOG_ACTION_SET_LEVEL: usize = 11;
+
+// 内核日志缓冲区的大小, 与内核保持一致
+pub const LOG_BUF_SIZE: usize = 16 * 1024;
+
+pub fn syslog(action: usize, buf: &mut [u8]) -> isize {
+    sys_syslog(action, buf.as_mut_ptr(), buf.len())
+}
+
+// 读取最新的内核日志, 不清空缓冲区, 返回读取的字节数
+pub fn dmesg(buf: &mut [u8]) -> isize {
+    sys_syslog(SYSLOG_ACTION_READ_ALL, buf.as_mut_ptr(), buf.len())
+}
+
+pub fn syslog_clear() -> isize {
+    sys_syslog(SYSLOG_ACTION_CLEAR, core::ptr::null_mut(), 0)
+}
+
+// 修改内核日志级别, spec 为 "级别" 或 "模块=级别", 如 "os::task=debug"
+pub fn syslog_set_level(spec: &str) -> isize {
+    sys_syslog(
+        SYSLOG_ACTION_SET_LEVEL,
+        spec.as_ptr() as *mut u8,
+        spec.len(),
+    )
+}
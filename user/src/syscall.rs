@@ -1,6 +1,8 @@
 // 需要内嵌汇编, 因此需要引入 core::arch::asm 模块
 use core::arch::asm;
 
+use crate::{ExitInfo, SignalAction};
+
 /// 所有的syscall都是通过 ecall 指令出发
 /// x10 ~ x17 别名 a0 ~ a7, x1 别名 ra
 /// 约定 a0~a6保存系统调用的参数, 并且 a0 保存系统调用的返回值
@@ -32,6 +34,17 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+/// 功能: 从标准输入读取数据到缓冲区, 不会阻塞
+/// 返回值: 读到的字节数, 没有输入时为 0; 出错时返回负数
+/// syscall ID: 63
+const SYSCALL_READ: usize = 63;
+pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_READ,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
 /// 功能: 将内存中缓冲区中的数据写入文件
 /// syscall ID: 64
 const SYSCALL_WRITE: usize = 64;
@@ -46,12 +59,174 @@ pub fn sys_exit(exit_code: i32) -> isize {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0])
 }
 
+/// 功能: 读取/清空内核日志缓冲区, 或者修改内核的日志级别, action 的取值见 lib.rs
+/// 返回值: 读取的字节数, 或者 action 对应的结果; 出错时返回负数
+/// syscall ID: 116
+const SYSCALL_SYSLOG: usize = 116;
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    syscall(SYSCALL_SYSLOG, [action, buf as usize, len])
+}
+
 const SYSCALL_YIELD: usize = 124;
 pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
+const SYSCALL_KILL: usize = 129;
+pub fn sys_kill(pid: usize, signum: i32) -> isize {
+    syscall(SYSCALL_KILL, [pid, signum as usize, 0])
+}
+
+/// 功能: 设置信号 signum 的处理函数, action 为 null 时只查询
+/// old_action 非 null 时写回原来的处理函数
+/// syscall ID: 134
+const SYSCALL_SIGACTION: usize = 134;
+pub fn sys_sigaction(
+    signum: i32,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum as usize, action as usize, old_action as usize],
+    )
+}
+
+/// 功能: 设置当前线程的信号屏蔽字
+/// 返回值: 原来的屏蔽字
+/// syscall ID: 135
+const SYSCALL_SIGPROCMASK: usize = 135;
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+}
+
+/// 功能: 从信号处理函数返回, 必须在处理函数的末尾调用
+/// syscall ID: 139
+const SYSCALL_SIGRETURN: usize = 139;
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
 const SYSCALL_GET_TIME: usize = 169;
 pub fn sys_get_time() -> isize {
     syscall(SYSCALL_GET_TIME, [0, 0, 0])
 }
+
+/// 功能: 查询子进程 pid 的退出信息
+/// 返回值: -1 进程不存在或者不是子进程, -2 进程尚未退出, 否则为 pid
+/// syscall ID: 260
+const SYSCALL_WAITPID: usize = 260;
+pub fn sys_waitpid(pid: usize, info: *mut ExitInfo) -> isize {
+    syscall(SYSCALL_WAITPID, [pid, info as usize, 0])
+}
+
+const SYSCALL_GETPID: usize = 172;
+pub fn sys_getpid() -> isize {
+    syscall(SYSCALL_GETPID, [0, 0, 0])
+}
+
+/// 功能: 创建子进程运行名为 name 的应用
+/// 返回值: 子进程的 pid, 应用不存在时为 -1
+/// syscall ID: 400
+const SYSCALL_SPAWN: usize = 400;
+pub fn sys_spawn(name: &str) -> isize {
+    syscall(SYSCALL_SPAWN, [name.as_ptr() as usize, name.len(), 0])
+}
+
+/// 功能: 在当前进程中创建一个线程, 从 entry 开始执行, arg 作为其第一个参数
+/// 返回值: 新线程的 tid
+/// syscall ID: 1000
+const SYSCALL_THREAD_CREATE: usize = 1000;
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
+}
+
+const SYSCALL_GETTID: usize = 1001;
+pub fn sys_gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0, 0, 0])
+}
+
+/// 功能: 等待当前进程内的线程 tid 退出
+/// 返回值: -1 线程不存在或等待自己, -2 线程尚未退出, 否则为线程退出码
+/// syscall ID: 1002
+const SYSCALL_WAITTID: usize = 1002;
+pub fn sys_waittid(tid: usize) -> isize {
+    syscall(SYSCALL_WAITTID, [tid, 0, 0])
+}
+
+/// 功能: 为当前进程开启(enabled = 1)或关闭(enabled = 0)死锁检测
+/// 开启后 mutex_lock/semaphore_down 在会导致死锁时返回 -EDEADLK 而不是阻塞
+/// syscall ID: 469
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    syscall(SYSCALL_ENABLE_DEADLOCK_DETECT, [enabled, 0, 0])
+}
+
+/// 功能: 创建互斥锁, blocking 为 true 时为阻塞锁, 否则为让权自旋锁
+/// 返回值: 锁的 id
+/// syscall ID: 1010
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    syscall(SYSCALL_MUTEX_CREATE, [blocking as usize, 0, 0])
+}
+
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+pub fn sys_mutex_lock(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_LOCK, [id, 0, 0])
+}
+
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+pub fn sys_mutex_unlock(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_UNLOCK, [id, 0, 0])
+}
+
+/// 功能: 创建信号量, 初始资源数为 res_count
+/// 返回值: 信号量的 id
+/// syscall ID: 1020
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_CREATE, [res_count, 0, 0])
+}
+
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_UP, [sem_id, 0, 0])
+}
+
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DOWN, [sem_id, 0, 0])
+}
+
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+pub fn sys_condvar_create() -> isize {
+    syscall(SYSCALL_CONDVAR_CREATE, [0, 0, 0])
+}
+
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_SIGNAL, [condvar_id, 0, 0])
+}
+
+/// 功能: 释放 mutex_id 对应的锁并阻塞在条件变量上, 被唤醒后重新持有该锁
+/// syscall ID: 1032
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
+}
+
+/// 功能: 让内核打印当前系统调用在内核中的调用栈, 用于调试
+/// syscall ID: 1100
+const SYSCALL_DEBUG_BACKTRACE: usize = 1100;
+pub fn sys_debug_backtrace() -> isize {
+    syscall(SYSCALL_DEBUG_BACKTRACE, [0, 0, 0])
+}
+
+/// 功能: 打开或关闭当前线程的系统调用跟踪, 打开后内核会打印每次系统调用的参数, 返回值与耗时
+/// 参数: enable 非 0 表示打开
+/// 返回值: 之前的状态, 1 表示打开
+/// syscall ID: 1101
+const SYSCALL_STRACE: usize = 1101;
+pub fn sys_strace(enable: usize) -> isize {
+    syscall(SYSCALL_STRACE, [enable, 0, 0])
+}